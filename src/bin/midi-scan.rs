@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use midi_parser::scan::{collect_files, scan_files, ScanOptions};
+
+const USAGE: &str = "usage: midi-scan <directory> [--format json|csv] [--output <file>] \
+                     [--threads <count>] [--sample <count>] [--extensions mid,midi,...] \
+                     [--no-round-trip] [--quiet]";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+}
+
+fn main() {
+    let mut options = ScanOptions::default();
+    let mut root = None;
+    let mut csv = false;
+    let mut output = None;
+    let mut quiet = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--format" => {
+                csv = match value().as_str() {
+                    "json" => false,
+                    "csv" => true,
+                    _ => usage(),
+                }
+            }
+            "--output" | "-o" => output = Some(PathBuf::from(value())),
+            "--threads" => options.threads = value().parse().unwrap_or_else(|_| usage()),
+            "--sample" => options.sample = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--extensions" => {
+                options.extensions = value().split(',').map(|ext| ext.to_string()).collect()
+            }
+            "--no-round-trip" => options.round_trip = false,
+            "--quiet" | "-q" => quiet = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            arg if arg.starts_with('-') || root.is_some() => usage(),
+            arg => root = Some(PathBuf::from(arg)),
+        }
+    }
+    let root = root.unwrap_or_else(|| usage());
+
+    let paths = collect_files(&root, &options);
+    let pb = match quiet {
+        true => ProgressBar::hidden(),
+        false => ProgressBar::new(paths.len() as u64),
+    };
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta_precise}) [{per_sec}]",
+            )
+            .unwrap()
+            .progress_chars("##-"),
+    );
+    // Panics while scanning are caught and reported per file, the default hook would only add
+    // noise, but panics of the tool itself should still be printed
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let report = scan_files(&paths, &options, &|_| pb.inc(1));
+    std::panic::set_hook(hook);
+    pb.finish_and_clear();
+
+    let text = match csv {
+        true => report.to_csv(),
+        false => report.to_json(),
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, text) {
+                eprintln!("Could not write {}: {}", path.display(), e);
+                std::process::exit(2);
+            }
+        }
+        None => print!("{}", text),
+    }
+    if !quiet {
+        eprintln!(
+            "{} files, {} errors, {} with warnings",
+            report.files.len(),
+            report.errors(),
+            report.warnings()
+        );
+    }
+    if report.errors() > 0 {
+        std::process::exit(1);
+    }
+}
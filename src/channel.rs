@@ -0,0 +1,245 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    event::{meta_event::MetaEvent, midi_event::MidiMessage, Event},
+    midi_file::MidiFile,
+    track::TrackChunk,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ChannelMap {
+    map: [Option<u8>; 16],
+}
+
+impl ChannelMap {
+    pub fn identity() -> Self {
+        let mut map = [None; 16];
+        for (channel, target) in map.iter_mut().enumerate() {
+            *target = Some(channel as u8);
+        }
+        Self { map }
+    }
+
+    pub fn map(mut self, from: u8, to: u8) -> Self {
+        assert!(from < 16 && to < 16);
+        self.map[from as usize] = Some(to);
+        self
+    }
+
+    pub fn remove(mut self, channel: u8) -> Self {
+        assert!(channel < 16);
+        self.map[channel as usize] = None;
+        self
+    }
+
+    pub fn get(&self, channel: u8) -> Option<u8> {
+        self.map[channel as usize]
+    }
+
+    fn apply(&self, event: &Event) -> Option<Event> {
+        match event {
+            Event::MidiEvent(MidiMessage::ChannelMessage(message)) => {
+                let channel = self.get(message.get_channel())?;
+                let mut message = *message;
+                message.set_channel(channel);
+                Some(Event::MidiEvent(MidiMessage::ChannelMessage(message)))
+            }
+            Event::MetaEvent(MetaEvent::MidiChannelPrefix { channel }) => {
+                let channel = self.get(*channel)?;
+                Some(Event::MetaEvent(MetaEvent::MidiChannelPrefix { channel }))
+            }
+            _ => Some(event.clone()),
+        }
+    }
+}
+
+impl Default for ChannelMap {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+fn event_channel(event: &Event) -> Option<u8> {
+    match event {
+        Event::MetaEvent(MetaEvent::MidiChannelPrefix { channel }) => Some(*channel),
+        _ => event.get_channel(),
+    }
+}
+
+impl TrackChunk {
+    pub fn remap_channels(&mut self, map: &ChannelMap) {
+        *self = self.filter_map_events(|event| map.apply(event));
+    }
+
+    pub fn retain_channels<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(u8) -> bool,
+    {
+        *self = self.filter_map_events(|event| match event_channel(event) {
+            Some(channel) if !predicate(channel) => None,
+            _ => Some(event.clone()),
+        });
+    }
+
+    pub fn extract_channel(&self, channel: u8) -> TrackChunk {
+        self.filter_map_events(|event| match event {
+            Event::MetaEvent(MetaEvent::EndOfTrack) => Some(event.clone()),
+            _ if event_channel(event) == Some(channel) => Some(event.clone()),
+            _ => None,
+        })
+    }
+
+    pub fn split_by_channel(&self) -> Vec<TrackChunk> {
+        let mut channels = BTreeSet::new();
+        for event in self.data.iter() {
+            if let Some(channel) = event_channel(&event.event) {
+                channels.insert(channel);
+            }
+        }
+        let rest = self.filter_map_events(|event| match event_channel(event) {
+            Some(_) => None,
+            None => Some(event.clone()),
+        });
+        let mut tracks = Vec::new();
+        if channels.is_empty() || rest.data.len() > 1 {
+            tracks.push(rest);
+        }
+        tracks.extend(
+            channels
+                .into_iter()
+                .map(|channel| self.extract_channel(channel)),
+        );
+        tracks
+    }
+}
+
+impl MidiFile {
+    pub fn remap_channels(&mut self, map: &ChannelMap) {
+        self.tracks
+            .iter_mut()
+            .for_each(|track| track.remap_channels(map));
+    }
+
+    pub fn retain_channels<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(u8) -> bool,
+    {
+        self.tracks
+            .iter_mut()
+            .for_each(|track| track.retain_channels(&mut predicate));
+    }
+
+    pub fn split_by_channel(&self) -> MidiFile {
+        let tracks = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.split_by_channel())
+            .collect::<Vec<_>>();
+        let mut header = self.header.clone();
+        if header.data.format == 0 && tracks.len() > 1 {
+            header.data.format = 1;
+        }
+        header.data.ntrks = tracks.len() as u16;
+        MidiFile { header, tracks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{
+            meta_event::MetaEvent,
+            midi_event::{
+                ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiMessage, ModeMessage,
+            },
+            Event,
+        },
+        track::TrackChunk,
+    };
+
+    use super::ChannelMap;
+
+    fn note_on(channel: u8, note: u8) -> Event {
+        Event::from(ChannelVoiceMessage::NoteOn {
+            channel,
+            note,
+            velocity: 64,
+        })
+    }
+
+    fn all_notes_off(channel: u8) -> Event {
+        Event::MidiEvent(MidiMessage::ChannelMessage(
+            ChannelMessage::ChannelModeMessage(ChannelModeMessage::new(
+                channel,
+                ModeMessage::AllNotesOff,
+            )),
+        ))
+    }
+
+    fn track() -> TrackChunk {
+        TrackChunk::from_absolute(vec![
+            (
+                0,
+                Event::MetaEvent(MetaEvent::MidiChannelPrefix { channel: 3 }),
+            ),
+            (0, note_on(3, 60)),
+            (10, note_on(9, 36)),
+            (20, note_on(3, 62)),
+            (30, all_notes_off(3)),
+            (40, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ])
+    }
+
+    #[test]
+    fn test_remap_channels() {
+        let mut track = track();
+        track.remap_channels(&ChannelMap::identity().map(3, 1).remove(9));
+        assert_eq!(
+            track.to_absolute(),
+            vec![
+                (
+                    0,
+                    Event::MetaEvent(MetaEvent::MidiChannelPrefix { channel: 1 })
+                ),
+                (0, note_on(1, 60)),
+                (20, note_on(1, 62)),
+                (30, all_notes_off(1)),
+                (40, Event::MetaEvent(MetaEvent::EndOfTrack)),
+            ]
+        );
+        assert_eq!(track.length as usize, track.to_bytes().len() - 8);
+    }
+
+    #[test]
+    fn test_split_by_channel() {
+        let tracks = track().split_by_channel();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].data.len(), 5);
+        assert_eq!(
+            tracks[1].to_absolute(),
+            vec![
+                (10, note_on(9, 36)),
+                (40, Event::MetaEvent(MetaEvent::EndOfTrack)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_channel() {
+        let track = TrackChunk::from_absolute(vec![
+            (0, note_on(3, 60)),
+            (0x0800_0000, note_on(9, 36)),
+            (0x0fff_ffff, note_on(3, 62)),
+            (0x0fff_ffff, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let extracted = track.extract_channel(3);
+        assert_eq!(
+            extracted
+                .data
+                .iter()
+                .map(|event| event.delta_time.value)
+                .collect::<Vec<_>>(),
+            [0, 0x0fff_ffff, 0]
+        );
+    }
+}
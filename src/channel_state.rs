@@ -0,0 +1,388 @@
+use crate::{
+    controller::{ControllerDecoder, ControllerEvent},
+    event::{
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage, ModeMessage},
+        Event,
+    },
+    midi_file::MidiFile,
+    timing::TimePosition,
+};
+
+const BANK_SELECT_MSB: u8 = 0x00;
+const BANK_SELECT_LSB: u8 = 0x20;
+const SUSTAIN: u8 = 0x40;
+const ALL_SOUND_OFF: u8 = 0x78;
+const RESET_ALL_CONTROLLERS: u8 = 0x79;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Channel {
+    pub program: u8,
+    pub controllers: [u8; 128],
+    pub pitch_bend: u16,
+    pub pitch_bend_sensitivity: (u8, u8),
+    pub channel_pressure: u8,
+    pub poly_pressure: [u8; 128],
+    pub notes: [Option<u8>; 128],
+    pub sustained: [bool; 128],
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        let mut controllers = [0; 128];
+        controllers[0x07] = 100;
+        controllers[0x0a] = 64;
+        controllers[0x0b] = 127;
+        controllers[0x62] = 127;
+        controllers[0x63] = 127;
+        controllers[0x64] = 127;
+        controllers[0x65] = 127;
+        Self {
+            program: 0,
+            controllers,
+            pitch_bend: 0x2000,
+            pitch_bend_sensitivity: (2, 0),
+            channel_pressure: 0,
+            poly_pressure: [0; 128],
+            notes: [None; 128],
+            sustained: [false; 128],
+        }
+    }
+}
+
+impl Channel {
+    pub fn bank_select_msb(&self) -> u8 {
+        self.controllers[BANK_SELECT_MSB as usize]
+    }
+
+    pub fn bank_select_lsb(&self) -> u8 {
+        self.controllers[BANK_SELECT_LSB as usize]
+    }
+
+    pub fn bank(&self) -> u16 {
+        ((self.bank_select_msb() as u16) << 7) | self.bank_select_lsb() as u16
+    }
+
+    pub fn sustain(&self) -> bool {
+        self.controllers[SUSTAIN as usize] >= 64
+    }
+
+    pub fn pitch_bend_range(&self) -> f64 {
+        let (semitones, cents) = self.pitch_bend_sensitivity;
+        semitones as f64 + cents as f64 / 100.0
+    }
+
+    // The channel number does not matter for the conversions
+    fn pitch_bend_message(&self) -> ChannelVoiceMessage {
+        ChannelVoiceMessage::PitchBendChange {
+            channel: 0,
+            value: self.pitch_bend,
+        }
+    }
+
+    pub fn pitch_bend_centered(&self) -> i16 {
+        self.pitch_bend_message().pitch_bend_centered().unwrap()
+    }
+
+    pub fn pitch_bend_semitones(&self) -> f64 {
+        self.pitch_bend_message()
+            .pitch_bend_semitones(self.pitch_bend_range())
+            .unwrap()
+    }
+
+    pub fn pitch_bend_cents(&self) -> f64 {
+        self.pitch_bend_message()
+            .pitch_bend_cents(self.pitch_bend_range())
+            .unwrap()
+    }
+
+    pub fn sounding_notes(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter_map(|(note, velocity)| velocity.map(|velocity| (note as u8, velocity)))
+    }
+
+    fn release(&mut self, note: u8) {
+        if self.notes[note as usize].is_none() {
+            return;
+        }
+        if self.sustain() {
+            self.sustained[note as usize] = true;
+        } else {
+            self.notes[note as usize] = None;
+        }
+    }
+
+    fn release_sustained(&mut self) {
+        for note in 0..128 {
+            if self.sustained[note] {
+                self.sustained[note] = false;
+                self.notes[note] = None;
+            }
+        }
+    }
+
+    fn reset_controllers(&mut self) {
+        let default = Channel::default();
+        for controller in [0x01, 0x0b, 0x40, 0x41, 0x42, 0x43, 0x62, 0x63, 0x64, 0x65] {
+            self.controllers[controller] = default.controllers[controller];
+        }
+        self.pitch_bend = default.pitch_bend;
+        self.channel_pressure = 0;
+        self.poly_pressure = [0; 128];
+        self.release_sustained();
+    }
+
+    fn process(&mut self, message: &ChannelMessage) {
+        match message {
+            ChannelMessage::ChannelVoiceMessage(message) => match *message {
+                ChannelVoiceMessage::NoteOn { note, velocity, .. } if velocity > 0 => {
+                    self.notes[note as usize] = Some(velocity);
+                    self.sustained[note as usize] = false;
+                }
+                ChannelVoiceMessage::NoteOn { note, .. }
+                | ChannelVoiceMessage::NoteOff { note, .. } => self.release(note),
+                ChannelVoiceMessage::PolyphonicKeyPressure { note, pressure, .. } => {
+                    self.poly_pressure[note as usize] = pressure
+                }
+                ChannelVoiceMessage::ControlChange {
+                    controller, value, ..
+                } => match controller {
+                    ALL_SOUND_OFF => {
+                        self.notes = [None; 128];
+                        self.sustained = [false; 128];
+                    }
+                    RESET_ALL_CONTROLLERS => self.reset_controllers(),
+                    _ => {
+                        self.controllers[controller as usize] = value;
+                        if controller == SUSTAIN && value < 64 {
+                            self.release_sustained();
+                        }
+                    }
+                },
+                ChannelVoiceMessage::ProgramChange { program, .. } => self.program = program,
+                ChannelVoiceMessage::ChannelPressure { pressure, .. } => {
+                    self.channel_pressure = pressure
+                }
+                ChannelVoiceMessage::PitchBendChange { value, .. } => self.pitch_bend = value,
+            },
+            ChannelMessage::ChannelModeMessage(message) => match message.get_message() {
+                ModeMessage::AllNotesOff
+                | ModeMessage::OmniModeOff
+                | ModeMessage::OmniModeOn
+                | ModeMessage::MonoModeOn { .. }
+                | ModeMessage::PolyModeOn => {
+                    for note in 0..128 {
+                        self.release(note);
+                    }
+                }
+                ModeMessage::LocalControlOff | ModeMessage::LocalControlOn => {}
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ChannelState {
+    pub channels: [Channel; 16],
+    decoder: ControllerDecoder,
+}
+
+impl ChannelState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn process(&mut self, message: &MidiMessage) {
+        if let MidiMessage::ChannelMessage(message) = message {
+            let channel = message.get_channel();
+            self.channels[channel as usize].process(message);
+            if let ChannelMessage::ChannelVoiceMessage(message) = message {
+                // A held back MSB already sets the sensitivity, its LSB may refine it later
+                let events = self.decoder.process(message);
+                for event in events.into_iter().chain(self.decoder.pending(channel)) {
+                    if let ControllerEvent::PitchBendSensitivity {
+                        semitones, cents, ..
+                    } = event
+                    {
+                        self.channels[channel as usize].pitch_bend_sensitivity = (semitones, cents)
+                    }
+                }
+                if let ChannelVoiceMessage::ControlChange {
+                    controller: RESET_ALL_CONTROLLERS,
+                    ..
+                } = message
+                {
+                    self.decoder.deselect(channel)
+                }
+            }
+        }
+    }
+
+    pub fn process_event(&mut self, event: &Event) {
+        if let Event::MidiEvent(message) = event {
+            self.process(message);
+        }
+    }
+
+    pub fn channel(&self, channel: u8) -> &Channel {
+        &self.channels[channel as usize]
+    }
+}
+
+// The value in effect at a tick from changes sorted by tick
+pub(crate) fn value_at<T: Copy>(changes: &[(u64, T)], tick: u64) -> Option<T> {
+    let index = changes.partition_point(|(change, _)| *change <= tick);
+    index.checked_sub(1).map(|index| changes[index].1)
+}
+
+impl MidiFile {
+    // Changes of each channel sorted by tick, whichever track they are in
+    pub(crate) fn channel_changes<T>(
+        &self,
+        pick: impl Fn(&ChannelVoiceMessage) -> Option<T>,
+    ) -> Vec<Vec<(u64, T)>> {
+        let mut changes = (0..16).map(|_| Vec::new()).collect::<Vec<_>>();
+        for track in self.tracks.iter() {
+            for (tick, event) in track.to_absolute() {
+                if let Event::MidiEvent(MidiMessage::ChannelMessage(
+                    ChannelMessage::ChannelVoiceMessage(message),
+                )) = event
+                {
+                    if let Some(value) = pick(&message) {
+                        changes[message.get_channel() as usize].push((tick, value));
+                    }
+                }
+            }
+        }
+        changes
+            .iter_mut()
+            .for_each(|changes| changes.sort_by_key(|(tick, _)| *tick));
+        changes
+    }
+
+    pub(crate) fn program_changes(&self) -> Vec<Vec<(u64, u8)>> {
+        self.channel_changes(|message| match *message {
+            ChannelVoiceMessage::ProgramChange { program, .. } => Some(program),
+            _ => None,
+        })
+    }
+
+    pub fn channel_state_at(&self, position: TimePosition) -> ChannelState {
+        let tick = self.tempo_map().to_tick(position);
+        let mut events = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.to_absolute())
+            .filter(|(event_tick, _)| *event_tick <= tick)
+            .collect::<Vec<_>>();
+        events.sort_by_key(|(event_tick, _)| *event_tick);
+        let mut state = ChannelState::new();
+        events
+            .iter()
+            .for_each(|(_, event)| state.process_event(event));
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        controller::ControllerEvent,
+        event::midi_event::{
+            ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiMessage, ModeMessage,
+        },
+    };
+
+    use super::ChannelState;
+
+    fn control(controller: u8, value: u8) -> MidiMessage {
+        MidiMessage::from(ChannelVoiceMessage::ControlChange {
+            channel: 2,
+            controller,
+            value,
+        })
+    }
+
+    #[test]
+    fn test_program_and_bank() {
+        let mut state = ChannelState::new();
+        state.process(&control(0x00, 1));
+        state.process(&control(0x20, 3));
+        state.process(&MidiMessage::from(ChannelVoiceMessage::ProgramChange {
+            channel: 2,
+            program: 24,
+        }));
+        assert_eq!(state.channel(2).program, 24);
+        assert_eq!(state.channel(2).bank(), 131);
+        assert_eq!(state.channel(0).program, 0);
+    }
+
+    #[test]
+    fn test_sustain() {
+        let mut state = ChannelState::new();
+        state.process(&MidiMessage::from(ChannelVoiceMessage::NoteOn {
+            channel: 2,
+            note: 60,
+            velocity: 80,
+        }));
+        state.process(&control(0x40, 127));
+        state.process(&MidiMessage::from(ChannelVoiceMessage::NoteOff {
+            channel: 2,
+            note: 60,
+            velocity: 0,
+        }));
+        assert_eq!(
+            state.channel(2).sounding_notes().collect::<Vec<_>>(),
+            [(60, 80)]
+        );
+        state.process(&control(0x40, 0));
+        assert_eq!(state.channel(2).sounding_notes().count(), 0);
+    }
+
+    #[test]
+    fn test_pitch_bend_sensitivity() {
+        let mut state = ChannelState::new();
+        for message in (ControllerEvent::PitchBendSensitivity {
+            channel: 2,
+            semitones: 12,
+            cents: 0,
+        })
+        .to_messages()
+        {
+            state.process(&MidiMessage::from(message));
+        }
+        state.process(&MidiMessage::from(
+            ChannelVoiceMessage::pitch_bend_from_semitones(2, -6.0, 12.0),
+        ));
+        assert_eq!(state.channel(2).pitch_bend, 0x1000);
+        assert_eq!(state.channel(2).pitch_bend_semitones(), -6.0);
+        assert_eq!(state.channel(0).pitch_bend_range(), 2.0);
+    }
+
+    #[test]
+    fn test_all_notes_off_and_reset() {
+        let mut state = ChannelState::new();
+        state.process(&MidiMessage::from(ChannelVoiceMessage::NoteOn {
+            channel: 2,
+            note: 64,
+            velocity: 80,
+        }));
+        state.process(&MidiMessage::from(ChannelVoiceMessage::PitchBendChange {
+            channel: 2,
+            value: 0,
+        }));
+        state.process(&control(0x01, 50));
+        state.process(&MidiMessage::ChannelMessage(
+            ChannelMessage::ChannelModeMessage(ChannelModeMessage::new(
+                2,
+                ModeMessage::AllNotesOff,
+            )),
+        ));
+        assert_eq!(state.channel(2).sounding_notes().count(), 0);
+        state.process(&control(0x79, 0));
+        assert_eq!(state.channel(2).pitch_bend, 0x2000);
+        assert_eq!(state.channel(2).controllers[0x01], 0);
+        assert_eq!(state.channel(2).controllers[0x07], 100);
+    }
+}
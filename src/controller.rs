@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use crate::{
+    event::{
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        Event,
+    },
+    track::TrackChunk,
+};
+
+const DATA_ENTRY_MSB: u8 = 0x06;
+const DATA_ENTRY_LSB: u8 = 0x26;
+const DATA_INCREMENT: u8 = 0x60;
+const DATA_DECREMENT: u8 = 0x61;
+const NRPN_LSB: u8 = 0x62;
+const NRPN_MSB: u8 = 0x63;
+const RPN_LSB: u8 = 0x64;
+const RPN_MSB: u8 = 0x65;
+
+pub const RPN_PITCH_BEND_SENSITIVITY: u16 = 0x0000;
+pub const RPN_FINE_TUNING: u16 = 0x0001;
+pub const RPN_COARSE_TUNING: u16 = 0x0002;
+pub const RPN_TUNING_PROGRAM_SELECT: u16 = 0x0003;
+pub const RPN_TUNING_BANK_SELECT: u16 = 0x0004;
+pub const RPN_MODULATION_DEPTH_RANGE: u16 = 0x0005;
+pub const RPN_NULL: u16 = 0x3fff;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Parameter {
+    Registered(u16),
+    NonRegistered(u16),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ControllerEvent {
+    PitchBendSensitivity {
+        channel: u8,
+        semitones: u8,
+        cents: u8,
+    },
+    FineTuning {
+        channel: u8,
+        value: u16,
+    },
+    CoarseTuning {
+        channel: u8,
+        semitones: i8,
+    },
+    TuningProgramSelect {
+        channel: u8,
+        program: u8,
+    },
+    TuningBankSelect {
+        channel: u8,
+        bank: u8,
+    },
+    ModulationDepthRange {
+        channel: u8,
+        value: u16,
+    },
+    Rpn {
+        channel: u8,
+        parameter: u16,
+        value: u16,
+    },
+    Nrpn {
+        channel: u8,
+        parameter: u16,
+        value: u16,
+    },
+    Controller14Bit {
+        channel: u8,
+        controller: u8,
+        value: u16,
+    },
+}
+
+impl ControllerEvent {
+    fn from_parameter(channel: u8, parameter: Parameter, value: u16) -> Self {
+        match parameter {
+            Parameter::Registered(RPN_PITCH_BEND_SENSITIVITY) => Self::PitchBendSensitivity {
+                channel,
+                semitones: (value >> 7) as u8,
+                cents: (value & 0x7f) as u8,
+            },
+            Parameter::Registered(RPN_FINE_TUNING) => Self::FineTuning { channel, value },
+            Parameter::Registered(RPN_COARSE_TUNING) => Self::CoarseTuning {
+                channel,
+                semitones: (value >> 7) as i8 - 64,
+            },
+            Parameter::Registered(RPN_TUNING_PROGRAM_SELECT) => Self::TuningProgramSelect {
+                channel,
+                program: (value >> 7) as u8,
+            },
+            Parameter::Registered(RPN_TUNING_BANK_SELECT) => Self::TuningBankSelect {
+                channel,
+                bank: (value >> 7) as u8,
+            },
+            Parameter::Registered(RPN_MODULATION_DEPTH_RANGE) => {
+                Self::ModulationDepthRange { channel, value }
+            }
+            Parameter::Registered(parameter) => Self::Rpn {
+                channel,
+                parameter,
+                value,
+            },
+            Parameter::NonRegistered(parameter) => Self::Nrpn {
+                channel,
+                parameter,
+                value,
+            },
+        }
+    }
+
+    pub fn get_channel(&self) -> u8 {
+        match self {
+            Self::PitchBendSensitivity { channel, .. }
+            | Self::FineTuning { channel, .. }
+            | Self::CoarseTuning { channel, .. }
+            | Self::TuningProgramSelect { channel, .. }
+            | Self::TuningBankSelect { channel, .. }
+            | Self::ModulationDepthRange { channel, .. }
+            | Self::Rpn { channel, .. }
+            | Self::Nrpn { channel, .. }
+            | Self::Controller14Bit { channel, .. } => *channel,
+        }
+    }
+
+    pub fn to_messages(&self) -> Vec<ChannelVoiceMessage> {
+        let channel = self.get_channel();
+        let control = |controller: u8, value: u8| ChannelVoiceMessage::ControlChange {
+            channel,
+            controller,
+            value: value & 0x7f,
+        };
+        let (parameter, value, lsb) = match *self {
+            Self::PitchBendSensitivity {
+                semitones, cents, ..
+            } => (
+                Parameter::Registered(RPN_PITCH_BEND_SENSITIVITY),
+                ((semitones as u16) << 7) | cents as u16,
+                true,
+            ),
+            Self::FineTuning { value, .. } => (Parameter::Registered(RPN_FINE_TUNING), value, true),
+            Self::CoarseTuning { semitones, .. } => (
+                Parameter::Registered(RPN_COARSE_TUNING),
+                ((semitones as i16 + 64) as u16) << 7,
+                false,
+            ),
+            Self::TuningProgramSelect { program, .. } => (
+                Parameter::Registered(RPN_TUNING_PROGRAM_SELECT),
+                (program as u16) << 7,
+                false,
+            ),
+            Self::TuningBankSelect { bank, .. } => (
+                Parameter::Registered(RPN_TUNING_BANK_SELECT),
+                (bank as u16) << 7,
+                false,
+            ),
+            Self::ModulationDepthRange { value, .. } => (
+                Parameter::Registered(RPN_MODULATION_DEPTH_RANGE),
+                value,
+                true,
+            ),
+            Self::Rpn {
+                parameter, value, ..
+            } => (Parameter::Registered(parameter), value, true),
+            Self::Nrpn {
+                parameter, value, ..
+            } => (Parameter::NonRegistered(parameter), value, true),
+            Self::Controller14Bit {
+                controller, value, ..
+            } => {
+                assert!(controller < 0x20);
+                return vec![
+                    control(controller, (value >> 7) as u8),
+                    control(controller + 0x20, value as u8),
+                ];
+            }
+        };
+        let (msb_controller, lsb_controller, number) = match parameter {
+            Parameter::Registered(number) => (RPN_MSB, RPN_LSB, number),
+            Parameter::NonRegistered(number) => (NRPN_MSB, NRPN_LSB, number),
+        };
+        let mut messages = vec![
+            control(msb_controller, (number >> 7) as u8),
+            control(lsb_controller, number as u8),
+            control(DATA_ENTRY_MSB, (value >> 7) as u8),
+        ];
+        if lsb {
+            messages.push(control(DATA_ENTRY_LSB, value as u8));
+        }
+        messages.push(control(RPN_MSB, 0x7f));
+        messages.push(control(RPN_LSB, 0x7f));
+        messages
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+struct ChannelDecoder {
+    rpn: (u8, u8),
+    nrpn: (u8, u8),
+    selected: Option<Parameter>,
+    values: HashMap<Parameter, u16>,
+    controllers: [u8; 0x20],
+    // An MSB event held back until the next message shows whether its LSB follows
+    pending: Option<(u8, ControllerEvent)>,
+}
+
+impl ChannelDecoder {
+    fn select(&mut self, parameter: Parameter) {
+        self.selected = match parameter {
+            Parameter::Registered(RPN_NULL) | Parameter::NonRegistered(RPN_NULL) => None,
+            _ => Some(parameter),
+        };
+    }
+
+    fn update<F>(&mut self, channel: u8, f: F) -> Option<ControllerEvent>
+    where
+        F: FnOnce(u16) -> u16,
+    {
+        let parameter = self.selected?;
+        let current = match self.values.get(&parameter) {
+            Some(value) => *value,
+            None if parameter == Parameter::Registered(RPN_PITCH_BEND_SENSITIVITY) => 2 << 7,
+            None if parameter == Parameter::Registered(RPN_FINE_TUNING) => 0x2000,
+            None if parameter == Parameter::Registered(RPN_COARSE_TUNING) => 0x2000,
+            None => 0,
+        };
+        let value = f(current) & 0x3fff;
+        self.values.insert(parameter, value);
+        Some(ControllerEvent::from_parameter(channel, parameter, value))
+    }
+
+    fn process(&mut self, message: &ChannelVoiceMessage) -> Vec<ControllerEvent> {
+        let (channel, controller, value) = match *message {
+            ChannelVoiceMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => (channel, controller, value),
+            _ => return self.flush(),
+        };
+        let mut events = match self.pending.take() {
+            Some((msb, _)) if controller == msb + 0x20 => Vec::new(),
+            pending => pending.map(|(_, event)| event).into_iter().collect(),
+        };
+        match self.control(channel, controller, value) {
+            Some(event) if controller < 0x20 => self.pending = Some((controller, event)),
+            Some(event) => events.push(event),
+            None => {}
+        }
+        events
+    }
+
+    fn flush(&mut self) -> Vec<ControllerEvent> {
+        self.pending
+            .take()
+            .map(|(_, event)| event)
+            .into_iter()
+            .collect()
+    }
+
+    fn control(&mut self, channel: u8, controller: u8, value: u8) -> Option<ControllerEvent> {
+        match controller {
+            RPN_MSB => {
+                self.rpn.0 = value;
+                self.select(Parameter::Registered(parameter_number(self.rpn)));
+                None
+            }
+            RPN_LSB => {
+                self.rpn.1 = value;
+                self.select(Parameter::Registered(parameter_number(self.rpn)));
+                None
+            }
+            NRPN_MSB => {
+                self.nrpn.0 = value;
+                self.select(Parameter::NonRegistered(parameter_number(self.nrpn)));
+                None
+            }
+            NRPN_LSB => {
+                self.nrpn.1 = value;
+                self.select(Parameter::NonRegistered(parameter_number(self.nrpn)));
+                None
+            }
+            DATA_ENTRY_MSB => self.update(channel, |_| (value as u16) << 7),
+            DATA_ENTRY_LSB => self.update(channel, |current| (current & !0x7f) | value as u16),
+            DATA_INCREMENT => self.update(channel, |current| (current + 1).min(0x3fff)),
+            DATA_DECREMENT => self.update(channel, |current| current.saturating_sub(1)),
+            0x00..=0x1f => {
+                self.controllers[controller as usize] = value;
+                Some(ControllerEvent::Controller14Bit {
+                    channel,
+                    controller,
+                    value: (value as u16) << 7,
+                })
+            }
+            0x20..=0x3f => {
+                let controller = controller - 0x20;
+                Some(ControllerEvent::Controller14Bit {
+                    channel,
+                    controller,
+                    value: ((self.controllers[controller as usize] as u16) << 7) | value as u16,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn parameter_number((msb, lsb): (u8, u8)) -> u16 {
+    ((msb as u16) << 7) | lsb as u16
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ControllerDecoder {
+    channels: [ChannelDecoder; 16],
+}
+
+impl ControllerDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deselect(&mut self, channel: u8) {
+        self.channels[channel as usize].selected = None;
+    }
+
+    // The event an MSB would produce if no LSB follows it
+    pub fn pending(&self, channel: u8) -> Option<ControllerEvent> {
+        self.channels[channel as usize]
+            .pending
+            .map(|(_, event)| event)
+    }
+
+    // An MSB is reported together with its LSB, or once the next message on the channel
+    // shows that no LSB follows
+    pub fn process(&mut self, message: &ChannelVoiceMessage) -> Vec<ControllerEvent> {
+        self.channels[message.get_channel() as usize].process(message)
+    }
+
+    pub fn process_event(&mut self, event: &Event) -> Vec<ControllerEvent> {
+        match event {
+            Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelVoiceMessage(
+                message,
+            ))) => self.process(message),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn flush(&mut self) -> Vec<ControllerEvent> {
+        self.channels
+            .iter_mut()
+            .flat_map(|channel| channel.flush())
+            .collect()
+    }
+}
+
+impl TrackChunk {
+    pub fn controller_events(&self) -> Vec<(u64, ControllerEvent)> {
+        let mut decoder = ControllerDecoder::new();
+        // Held back MSB events keep the tick of the MSB
+        let mut pending_ticks = [0; 16];
+        let mut events = Vec::new();
+        for (tick, event) in self.to_absolute() {
+            let Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelVoiceMessage(
+                message,
+            ))) = event
+            else {
+                continue;
+            };
+            let channel = message.get_channel();
+            let mut ticks = match decoder.pending(channel) {
+                Some(_) => vec![pending_ticks[channel as usize]],
+                None => Vec::new(),
+            };
+            events.extend(
+                decoder
+                    .process(&message)
+                    .into_iter()
+                    .map(|event| (ticks.pop().unwrap_or(tick), event)),
+            );
+            if decoder.pending(channel).is_some() {
+                pending_ticks[channel as usize] = tick;
+            }
+        }
+        events.extend(
+            decoder
+                .flush()
+                .into_iter()
+                .map(|event| (pending_ticks[event.get_channel() as usize], event)),
+        );
+        events.sort_by_key(|(tick, _)| *tick);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{midi_event::ChannelVoiceMessage, Event},
+        track::TrackChunk,
+    };
+
+    use super::{ControllerDecoder, ControllerEvent};
+
+    fn decode(messages: &[ChannelVoiceMessage]) -> Vec<ControllerEvent> {
+        let mut decoder = ControllerDecoder::new();
+        let mut events = messages
+            .iter()
+            .flat_map(|message| decoder.process(message))
+            .collect::<Vec<_>>();
+        events.extend(decoder.flush());
+        events
+    }
+
+    fn control(channel: u8, controller: u8, value: u8) -> ChannelVoiceMessage {
+        ChannelVoiceMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let events = [
+            ControllerEvent::PitchBendSensitivity {
+                channel: 1,
+                semitones: 12,
+                cents: 50,
+            },
+            ControllerEvent::CoarseTuning {
+                channel: 1,
+                semitones: -3,
+            },
+            ControllerEvent::Nrpn {
+                channel: 1,
+                parameter: 0x0123,
+                value: 0x0456,
+            },
+        ];
+        for event in events {
+            assert_eq!(decode(&event.to_messages()), [event]);
+        }
+    }
+
+    #[test]
+    fn test_increment() {
+        let mut messages = ControllerEvent::PitchBendSensitivity {
+            channel: 0,
+            semitones: 2,
+            cents: 0,
+        }
+        .to_messages();
+        messages.truncate(messages.len() - 2);
+        messages.push(control(0, 0x60, 0));
+        assert_eq!(
+            decode(&messages),
+            [
+                ControllerEvent::PitchBendSensitivity {
+                    channel: 0,
+                    semitones: 2,
+                    cents: 0,
+                },
+                ControllerEvent::PitchBendSensitivity {
+                    channel: 0,
+                    semitones: 2,
+                    cents: 1,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_14_bit_controller() {
+        let event = ControllerEvent::Controller14Bit {
+            channel: 3,
+            controller: 7,
+            value: 0x1234,
+        };
+        assert_eq!(decode(&event.to_messages()), [event]);
+
+        // An MSB without its LSB is reported when the next message arrives
+        let mut decoder = ControllerDecoder::new();
+        assert_eq!(decoder.process(&control(3, 7, 0x24)), []);
+        assert_eq!(
+            decoder.pending(3),
+            Some(ControllerEvent::Controller14Bit {
+                channel: 3,
+                controller: 7,
+                value: 0x24 << 7,
+            })
+        );
+        assert_eq!(decoder.process(&control(4, 0x27, 0x34)).len(), 1);
+        assert_eq!(
+            decoder.process(&control(3, 0x40, 0x7f)),
+            [ControllerEvent::Controller14Bit {
+                channel: 3,
+                controller: 7,
+                value: 0x24 << 7,
+            }]
+        );
+        assert_eq!(decoder.flush(), []);
+    }
+
+    #[test]
+    fn test_controller_events() {
+        let track = TrackChunk::from_absolute(vec![
+            (0, Event::from(control(0, 0x65, 0))),
+            (0, Event::from(control(0, 0x64, 2))),
+            (10, Event::from(control(0, 0x06, 60))),
+            (20, Event::from(control(1, 0x01, 5))),
+            (20, Event::from(control(1, 0x21, 6))),
+            (
+                30,
+                Event::from(ChannelVoiceMessage::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 100,
+                }),
+            ),
+            (40, Event::from(control(1, 0x01, 7))),
+        ]);
+        assert_eq!(
+            track.controller_events(),
+            [
+                (
+                    10,
+                    ControllerEvent::CoarseTuning {
+                        channel: 0,
+                        semitones: -4,
+                    }
+                ),
+                (
+                    20,
+                    ControllerEvent::Controller14Bit {
+                        channel: 1,
+                        controller: 1,
+                        value: (5 << 7) | 6,
+                    }
+                ),
+                (
+                    40,
+                    ControllerEvent::Controller14Bit {
+                        channel: 1,
+                        controller: 1,
+                        value: 7 << 7,
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,583 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    event::{
+        meta_event::MetaEvent,
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        sysex_event::SysExEvent,
+        Event,
+    },
+    header::Division,
+    midi_file::MidiFile,
+    track::TrackChunk,
+    ump::translate::midi1_message,
+    variable_length_quantity::VariableLengthQuantity,
+};
+
+const TEXT_MNEMONICS: [(u8, &str); 7] = [
+    (0x01, "Text"),
+    (0x02, "Copyright"),
+    (0x03, "TrkName"),
+    (0x04, "InstrName"),
+    (0x05, "Lyric"),
+    (0x06, "Marker"),
+    (0x07, "Cue"),
+];
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            character if character.is_control() => {
+                quoted.push_str(&format!("\\x{:02x}", character as u32))
+            }
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote(literal: &str) -> Option<String> {
+    let mut text = String::new();
+    let mut characters = literal.strip_prefix('"')?.strip_suffix('"')?.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => return None,
+            '\\' => match characters.next()? {
+                'x' => {
+                    let code = characters.by_ref().take(2).collect::<String>();
+                    text.push(u8::from_str_radix(&code, 16).ok()? as char);
+                }
+                character @ ('"' | '\\') => text.push(character),
+                _ => return None,
+            },
+            character => text.push(character),
+        }
+    }
+    Some(text)
+}
+
+// Everything after an unquoted '#' is a comment
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn meta_data(event: &MetaEvent) -> (u8, Vec<u8>) {
+    let bytes = event.to_bytes();
+    let (data, _) = VariableLengthQuantity::parse(&bytes[2..]).unwrap();
+    (bytes[1], data.to_vec())
+}
+
+fn disassemble_event(event: &Event) -> String {
+    match event {
+        Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelVoiceMessage(
+            message,
+        ))) => match *message {
+            ChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => format!("Off ch={} n={} v={}", channel, note, velocity),
+            ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => format!("On ch={} n={} v={}", channel, note, velocity),
+            ChannelVoiceMessage::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            } => format!("PoPr ch={} n={} v={}", channel, note, pressure),
+            ChannelVoiceMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => format!("Par ch={} c={} v={}", channel, controller, value),
+            ChannelVoiceMessage::ProgramChange { channel, program } => {
+                format!("PrCh ch={} p={}", channel, program)
+            }
+            ChannelVoiceMessage::ChannelPressure { channel, pressure } => {
+                format!("ChPr ch={} v={}", channel, pressure)
+            }
+            ChannelVoiceMessage::PitchBendChange { channel, value } => {
+                format!("Pb ch={} v={}", channel, value)
+            }
+        },
+        Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelModeMessage(
+            message,
+        ))) => {
+            let bytes = message.to_bytes();
+            format!(
+                "Par ch={} c={} v={}",
+                message.get_channel(),
+                bytes[1],
+                bytes[2]
+            )
+        }
+        Event::MidiEvent(MidiMessage::SystemMessage(message)) => {
+            format!("Sys {}", hex(&message.to_bytes()))
+        }
+        Event::SysExEvent(event) => match event.get_status() {
+            0xf0 => format!("SysEx {}", hex(event.get_data())),
+            _ => format!("Arb {}", hex(event.get_data()))
+                .trim_end()
+                .to_string(),
+        },
+        Event::MetaEvent(event) => match event {
+            MetaEvent::SequenceNumber { number } => format!("SeqNr {}", number),
+            MetaEvent::EndOfTrack => "Meta TrkEnd".to_string(),
+            MetaEvent::SetTempo { tempo } => format!("Tempo {}", tempo),
+            MetaEvent::SmpteOffset {
+                hour,
+                minute,
+                second,
+                frame,
+                subframe,
+            } => format!(
+                "SMPTE {} {} {} {} {}",
+                hour, minute, second, frame, subframe
+            ),
+            MetaEvent::TimeSignature {
+                numerator,
+                denominator,
+                clocks_per_metronome_click,
+                thirty_seconds_per_quarter_note,
+            } if *denominator < 32 => format!(
+                "TimeSig {}/{} {} {}",
+                numerator,
+                1u32 << denominator,
+                clocks_per_metronome_click,
+                thirty_seconds_per_quarter_note
+            ),
+            MetaEvent::KeySignature { key, scale } if *scale < 2 => format!(
+                "KeySig {} {}",
+                *key as i8,
+                match scale {
+                    0 => "major",
+                    _ => "minor",
+                }
+            ),
+            MetaEvent::SequencerSpecificEvent { data, .. } => format!("SeqSpec {}", hex(data)),
+            event => {
+                let (meta_type, data) = meta_data(event);
+                match (
+                    event.get_text(),
+                    TEXT_MNEMONICS
+                        .iter()
+                        .find(|(text_type, _)| *text_type == meta_type),
+                ) {
+                    (Some(text), Some((_, mnemonic))) => {
+                        format!("Meta {} {}", mnemonic, quote(text))
+                    }
+                    _ => format!("Meta 0x{:02x} {}", meta_type, hex(&data))
+                        .trim_end()
+                        .to_string(),
+                }
+            }
+        },
+    }
+}
+
+fn number<'a, T: std::str::FromStr>(token: &&'a str) -> Result<T, &'a str> {
+    token.parse().map_err(|_| *token)
+}
+
+fn argument<'a>(token: &'a str, key: &str) -> Result<u16, &'a str> {
+    token
+        .strip_prefix(key)
+        .and_then(|value| value.strip_prefix('='))
+        .and_then(|value| value.parse().ok())
+        .ok_or(token)
+}
+
+fn hex_bytes<'a>(tokens: &[&'a str]) -> Result<Vec<u8>, &'a str> {
+    tokens
+        .iter()
+        .map(|token| u8::from_str_radix(token, 16).map_err(|_| *token))
+        .collect()
+}
+
+fn channel_event<'a>(
+    mnemonic: &'a str,
+    status: u8,
+    tokens: &[&'a str],
+    keys: &[&str],
+) -> Result<Event, &'a str> {
+    if tokens.len() != keys.len() + 1 {
+        return Err(tokens.get(keys.len() + 1).copied().unwrap_or(mnemonic));
+    }
+    let channel = argument(tokens[0], "ch")?;
+    if channel > 0x0f {
+        return Err(tokens[0]);
+    }
+    let values = keys
+        .iter()
+        .zip(tokens[1..].iter())
+        .map(|(key, token)| argument(token, key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let status = status | channel as u8;
+    let limit = if status >> 4 == 0xe { 0x3fff } else { 0x7f };
+    if let Some(index) = values.iter().position(|value| *value > limit) {
+        return Err(tokens[index + 1]);
+    }
+    let (data1, data2) = match values[..] {
+        [value] if status >> 4 == 0xe => ((value & 0x7f) as u8, (value >> 7) as u8),
+        [value] => (value as u8, 0),
+        [data1, data2] => (data1 as u8, data2 as u8),
+        _ => return Err(mnemonic),
+    };
+    midi1_message(status, data1, data2)
+        .map(Event::MidiEvent)
+        .ok_or(mnemonic)
+}
+
+fn meta_event(meta_type: u8, data: &[u8]) -> Option<MetaEvent> {
+    let length = VariableLengthQuantity {
+        value: data.len() as u32,
+    };
+    let bytes = [&[0xff, meta_type][..], &length.to_bytes(), data].concat();
+    match MetaEvent::parse(&bytes) {
+        Ok(([], event)) => Some(event),
+        _ => None,
+    }
+}
+
+// Errors carry the token that could not be assembled
+fn assemble_event<'a>(tokens: &[&'a str], literal: Option<&'a str>) -> Result<Event, &'a str> {
+    let (mnemonic, arguments) = (tokens[0], &tokens[1..]);
+    let event = match (mnemonic, arguments) {
+        ("On", arguments) => channel_event(mnemonic, 0x90, arguments, &["n", "v"])?,
+        ("Off", arguments) => channel_event(mnemonic, 0x80, arguments, &["n", "v"])?,
+        ("PoPr", arguments) => channel_event(mnemonic, 0xa0, arguments, &["n", "v"])?,
+        ("Par", arguments) => channel_event(mnemonic, 0xb0, arguments, &["c", "v"])?,
+        ("PrCh", arguments) => channel_event(mnemonic, 0xc0, arguments, &["p"])?,
+        ("ChPr", arguments) => channel_event(mnemonic, 0xd0, arguments, &["v"])?,
+        ("Pb", arguments) => channel_event(mnemonic, 0xe0, arguments, &["v"])?,
+        ("Sys", arguments) => {
+            let bytes = hex_bytes(arguments)?;
+            let message = bytes
+                .split_first()
+                .and_then(|(status, data)| {
+                    midi1_message(
+                        *status,
+                        data.first().copied().unwrap_or(0),
+                        data.get(1).copied().unwrap_or(0),
+                    )
+                })
+                .filter(|message| message.to_bytes() == bytes)
+                .ok_or(mnemonic)?;
+            Event::MidiEvent(message)
+        }
+        ("SysEx", arguments) => {
+            Event::SysExEvent(SysExEvent::try_new(0xf0, hex_bytes(arguments)?).ok_or(mnemonic)?)
+        }
+        ("Arb", arguments) => Event::SysExEvent(SysExEvent::new(0xf7, hex_bytes(arguments)?)),
+        ("SeqNr", [sequence_number]) => Event::MetaEvent(MetaEvent::SequenceNumber {
+            number: number(sequence_number)?,
+        }),
+        ("Tempo", [tempo]) => match number(tempo)? {
+            value if value <= 0xffffff => Event::MetaEvent(MetaEvent::SetTempo { tempo: value }),
+            _ => return Err(tempo),
+        },
+        ("SMPTE", [hour, minute, second, frame, subframe]) => {
+            Event::MetaEvent(MetaEvent::SmpteOffset {
+                hour: number(hour)?,
+                minute: number(minute)?,
+                second: number(second)?,
+                frame: number(frame)?,
+                subframe: number(subframe)?,
+            })
+        }
+        ("TimeSig", [signature, clocks, thirty_seconds]) => {
+            let (numerator, denominator) = signature.split_once('/').ok_or(*signature)?;
+            let denominator = denominator
+                .parse::<u32>()
+                .ok()
+                .filter(|denominator| denominator.is_power_of_two())
+                .ok_or(*signature)?;
+            Event::MetaEvent(MetaEvent::TimeSignature {
+                numerator: numerator.parse().map_err(|_| *signature)?,
+                denominator: denominator.trailing_zeros() as u8,
+                clocks_per_metronome_click: number(clocks)?,
+                thirty_seconds_per_quarter_note: number(thirty_seconds)?,
+            })
+        }
+        ("KeySig", [key, scale]) => Event::MetaEvent(MetaEvent::KeySignature {
+            key: number::<i8>(key)? as u8,
+            scale: match *scale {
+                "major" => 0,
+                "minor" => 1,
+                _ => return Err(scale),
+            },
+        }),
+        ("SeqSpec", arguments) => {
+            Event::MetaEvent(meta_event(0x7f, &hex_bytes(arguments)?).ok_or(mnemonic)?)
+        }
+        ("Meta", ["TrkEnd"]) => Event::MetaEvent(MetaEvent::EndOfTrack),
+        ("Meta", [text_mnemonic]) if literal.is_some() => {
+            let (meta_type, _) = TEXT_MNEMONICS
+                .iter()
+                .find(|(_, name)| name == text_mnemonic)
+                .ok_or(*text_mnemonic)?;
+            let literal = literal.unwrap();
+            Event::MetaEvent(MetaEvent::text(
+                *meta_type,
+                &unquote(literal).ok_or(literal)?,
+            ))
+        }
+        ("Meta", [meta_type, data @ ..]) => {
+            let meta_type = meta_type
+                .strip_prefix("0x")
+                .and_then(|meta_type| u8::from_str_radix(meta_type, 16).ok())
+                .filter(|meta_type| *meta_type <= 0x7f)
+                .ok_or(*meta_type)?;
+            Event::MetaEvent(meta_event(meta_type, &hex_bytes(data)?).ok_or(mnemonic)?)
+        }
+        _ => return Err(mnemonic),
+    };
+    match literal {
+        Some(literal) if mnemonic != "Meta" => Err(literal),
+        _ => Ok(event),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxError {
+    // Counted from 1
+    pub line: usize,
+    pub token: String,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(line: usize, token: &str) -> Self {
+        Self {
+            line,
+            token: token.to_string(),
+        }
+    }
+}
+
+impl MidiFile {
+    pub fn disassemble(&self) -> String {
+        let division = match self.header.data.division {
+            Division::TicksPerQuarterNote { ticks } => ticks.to_string(),
+            Division::FramesPerSecond { frames, ticks } => {
+                format!("smpte:{}/{}", -(frames as i8 as i16), ticks)
+            }
+        };
+        let mut lines = vec![format!(
+            "MFile {} {} {}",
+            self.header.data.format,
+            self.tracks.len(),
+            division
+        )];
+        for (index, track) in self.tracks.iter().enumerate() {
+            lines.push(String::new());
+            lines.push(format!("# track {}", index));
+            for (tick, event) in track.to_absolute() {
+                lines.push(format!("{} {} {}", tick, index, disassemble_event(&event)));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+
+    pub fn assemble(input: &str) -> Result<Self, SyntaxError> {
+        let mut lines = input
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, strip_comment(line).trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let (line_number, header) = lines.next().ok_or(SyntaxError::new(1, ""))?;
+        let error = |token| SyntaxError::new(line_number, token);
+        let (format, ntrks_token, division) = match header.split_whitespace().collect::<Vec<_>>()[..]
+        {
+            ["MFile", format, ntrks, division] => (
+                format
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|format| *format <= 2)
+                    .ok_or(error(format))?,
+                ntrks,
+                division,
+            ),
+            _ => return Err(error(header)),
+        };
+        let division = match division.strip_prefix("smpte:") {
+            Some(smpte) => smpte
+                .split_once('/')
+                .and_then(|(frames, ticks)| {
+                    Some(Division::FramesPerSecond {
+                        frames: frames.parse::<i8>().ok()?.checked_neg()? as u8,
+                        ticks: ticks.parse().ok()?,
+                    })
+                })
+                .ok_or(error(division))?,
+            None => match division.parse() {
+                Ok(ticks) if ticks & 0x8000 == 0 => Division::TicksPerQuarterNote { ticks },
+                _ => return Err(error(division)),
+            },
+        };
+        let ntrks = ntrks_token.parse::<u16>().map_err(|_| error(ntrks_token))? as usize;
+        // Only tracks that have lines are allocated, whatever the header claims
+        let mut tracks = BTreeMap::new();
+        for (line_number, line) in lines {
+            let error = |token| SyntaxError::new(line_number, token);
+            let (line, literal) = match line.find('"') {
+                Some(index) => (&line[..index], Some(&line[index..])),
+                None => (line, None),
+            };
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            let (tick_token, track_token, tokens) = match tokens[..] {
+                [tick, track, ref tokens @ ..] if !tokens.is_empty() => (tick, track, tokens),
+                _ => return Err(error(line)),
+            };
+            let tick = tick_token.parse::<u64>().map_err(|_| error(tick_token))?;
+            let track = track_token
+                .parse::<usize>()
+                .ok()
+                .filter(|track| *track < ntrks)
+                .ok_or(error(track_token))?;
+            let (last_line, last_token, events) =
+                tracks.entry(track).or_insert((line_number, "", Vec::new()));
+            let last_tick = events.last().map_or(0, |(last, _)| *last);
+            if tick
+                .checked_sub(last_tick)
+                .is_none_or(|delta| delta > 0x0fff_ffff)
+            {
+                return Err(error(tick_token));
+            }
+            events.push((tick, assemble_event(tokens, literal).map_err(error)?));
+            (*last_line, *last_token) = (line_number, tokens[0]);
+        }
+        // Every track has to be there and end with TrkEnd for the file to parse again
+        let tracks = (0..ntrks)
+            .map(|track| match tracks.remove(&track) {
+                Some((_, _, events))
+                    if matches!(
+                        events.last(),
+                        Some((_, Event::MetaEvent(MetaEvent::EndOfTrack)))
+                    ) =>
+                {
+                    Ok(TrackChunk::from_absolute(events))
+                }
+                Some((line, token, _)) => Err(SyntaxError::new(line, token)),
+                None => Err(error(ntrks_token)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(format, division, tracks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, sysex_event::SysExEvent, Event},
+        midi_file::MidiFile,
+    };
+
+    use super::SyntaxError;
+
+    #[test]
+    fn test_disassemble() {
+        let text = "MFile 1 2 96\n\
+                    \n\
+                    # track 0\n\
+                    0 0 TimeSig 4/4 24 8\n\
+                    0 0 Tempo 500000\n\
+                    0 0 Meta TrkName \"Song\\x0a\"\n\
+                    0 0 Meta 0x21 00\n\
+                    384 0 Meta TrkEnd\n\
+                    \n\
+                    # track 1\n\
+                    0 1 PrCh ch=0 p=5\n\
+                    0 1 SysEx 7e 7f 09 01 f7\n\
+                    192 1 On ch=0 n=76 v=32\n\
+                    384 1 On ch=0 n=76 v=0\n\
+                    384 1 Pb ch=0 v=8192\n\
+                    384 1 Meta TrkEnd\n";
+        let midi_file = MidiFile::assemble(text).unwrap();
+        assert_eq!(midi_file.disassemble(), text);
+        assert_eq!(
+            midi_file.tracks[0].data[2].event,
+            Event::MetaEvent(MetaEvent::text(0x03, "Song\n"))
+        );
+        assert_eq!(
+            midi_file.tracks[1].data[1].event,
+            Event::SysExEvent(SysExEvent::from_payload(&[0x7e, 0x7f, 0x09, 0x01]))
+        );
+        let bytes = midi_file.to_bytes();
+        let (rest, parsed) = MidiFile::parse(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(parsed, midi_file);
+        assert_eq!(parsed.disassemble(), text);
+
+        let edited = MidiFile::assemble(
+            "MFile 0 1 smpte:25/40 # single track\n\
+             0 0 Meta Marker \"# not a comment \\\"quoted\\\"\"\n\
+             10 0 Meta TrkEnd # end\n",
+        )
+        .unwrap();
+        assert_eq!(
+            edited.disassemble(),
+            "MFile 0 1 smpte:25/40\n\
+             \n\
+             # track 0\n\
+             0 0 Meta Marker \"# not a comment \\\"quoted\\\"\"\n\
+             10 0 Meta TrkEnd\n"
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 0 1 96\n0 1 Meta TrkEnd\n").unwrap_err(),
+            SyntaxError::new(2, "1")
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 0 1 96\n10 0 Tempo 1\n0 0 Meta TrkEnd\n").unwrap_err(),
+            SyntaxError::new(3, "0")
+        );
+        assert_eq!(
+            MidiFile::assemble("# fixture\nMFile 0 1 96\n\n0 0 On ch=0 n=60 v=128\n").unwrap_err(),
+            SyntaxError::new(4, "v=128")
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 0 1 96\n0 0 Tempo 500000 \"x\"\n").unwrap_err(),
+            SyntaxError::new(2, "\"x\"")
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 0 1 96\n300000000 0 Meta TrkEnd\n").unwrap_err(),
+            SyntaxError::new(2, "300000000")
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 0 1 96\n0 0 Meta TrkEnd\n0 0 Tempo 500000\n").unwrap_err(),
+            SyntaxError::new(3, "Tempo")
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 1 2 96\n0 1 Meta TrkEnd\n").unwrap_err(),
+            SyntaxError::new(1, "2")
+        );
+        assert_eq!(
+            MidiFile::assemble("MFile 1 4294967295 96\n0 0 Meta TrkEnd\n").unwrap_err(),
+            SyntaxError::new(1, "4294967295")
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+
+use crate::{
+    event::{
+        meta_event::MetaEvent,
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        Event,
+    },
+    header::Division,
+    midi_file::MidiFile,
+    timing::{ticks_per_second, DEFAULT_TEMPO},
+    track::TrackChunk,
+    utils::gcd,
+};
+
+#[derive(PartialEq)]
+enum Control {
+    Controller(u8),
+    PitchBend,
+    Pressure,
+    // Parameter numbers and data entry only make sense in sequence, so all of them are kept
+    Parameter,
+}
+
+fn control(message: &ChannelVoiceMessage) -> Option<Control> {
+    match *message {
+        ChannelVoiceMessage::ControlChange {
+            controller: 0x06 | 0x26 | 0x60..=0x65,
+            ..
+        } => Some(Control::Parameter),
+        ChannelVoiceMessage::ControlChange { controller, .. } => {
+            Some(Control::Controller(controller))
+        }
+        ChannelVoiceMessage::PitchBendChange { .. } => Some(Control::PitchBend),
+        ChannelVoiceMessage::ChannelPressure { .. } => Some(Control::Pressure),
+        _ => None,
+    }
+}
+
+impl TrackChunk {
+    pub fn merge(tracks: &[TrackChunk]) -> TrackChunk {
+        let mut events = Vec::new();
+        let mut end_tick = 0;
+        for track in tracks.iter() {
+            end_tick = end_tick.max(track.end_tick());
+            events.extend(
+                track
+                    .to_absolute()
+                    .into_iter()
+                    .filter(|(_, event)| event != &Event::MetaEvent(MetaEvent::EndOfTrack)),
+            );
+        }
+        events.sort_by_key(|(tick, _)| *tick);
+        events.push((end_tick, Event::MetaEvent(MetaEvent::EndOfTrack)));
+        TrackChunk::from_absolute(events)
+    }
+
+    pub fn split_by_program(&self) -> Vec<TrackChunk> {
+        let mut programs = [0u8; 16];
+        // The controller state of each channel, copied into groups that start part way through
+        let mut controls: [Vec<(Control, Event)>; 16] = Default::default();
+        let mut sounding = HashMap::new();
+        let mut keys = Vec::new();
+        let mut rest = Vec::new();
+        let mut groups: HashMap<(u8, u8), Vec<(u64, Event)>> = HashMap::new();
+        for (tick, event) in self.to_absolute() {
+            let message = match &event {
+                Event::MidiEvent(MidiMessage::ChannelMessage(message)) => message,
+                _ => {
+                    if event != Event::MetaEvent(MetaEvent::EndOfTrack) {
+                        rest.push((tick, event));
+                    }
+                    continue;
+                }
+            };
+            let channel = message.get_channel();
+            let key = match message {
+                ChannelMessage::ChannelVoiceMessage(ChannelVoiceMessage::ProgramChange {
+                    program,
+                    ..
+                }) => {
+                    programs[channel as usize] = *program;
+                    (channel, *program)
+                }
+                ChannelMessage::ChannelVoiceMessage(ChannelVoiceMessage::NoteOn {
+                    note,
+                    velocity,
+                    ..
+                }) if *velocity > 0 => {
+                    let key = (channel, programs[channel as usize]);
+                    sounding.insert((channel, *note), key);
+                    key
+                }
+                ChannelMessage::ChannelVoiceMessage(
+                    ChannelVoiceMessage::NoteOn { note, .. }
+                    | ChannelVoiceMessage::NoteOff { note, .. },
+                ) => sounding
+                    .remove(&(channel, *note))
+                    .unwrap_or((channel, programs[channel as usize])),
+                _ => (channel, programs[channel as usize]),
+            };
+            let group = match groups.get_mut(&key) {
+                Some(group) => group,
+                None => {
+                    keys.push(key);
+                    let state = controls[channel as usize]
+                        .iter()
+                        .map(|(_, event)| (tick, event.clone()));
+                    groups.entry(key).or_insert(state.collect())
+                }
+            };
+            group.push((tick, event.clone()));
+            if let ChannelMessage::ChannelVoiceMessage(message) = message {
+                if let Some(control) = control(message) {
+                    let controls = &mut controls[channel as usize];
+                    if control != Control::Parameter {
+                        controls.retain(|(other, _)| *other != control);
+                    }
+                    controls.push((control, event.clone()));
+                }
+            }
+        }
+
+        let end_tick = self.end_tick();
+        let end_of_track = (end_tick, Event::MetaEvent(MetaEvent::EndOfTrack));
+        let mut tracks = Vec::new();
+        if keys.is_empty() || !rest.is_empty() {
+            rest.push(end_of_track.clone());
+            tracks.push(TrackChunk::from_absolute(rest));
+        }
+        for key in keys {
+            let mut events = groups.remove(&key).unwrap_or_default();
+            events.push(end_of_track.clone());
+            tracks.push(TrackChunk::from_absolute(events));
+        }
+        tracks
+    }
+
+    pub fn scale_ticks(&mut self, numerator: u64, denominator: u64) -> bool {
+        if denominator == 0 {
+            return false;
+        }
+        let events = self
+            .to_absolute()
+            .into_iter()
+            .map(|(tick, event)| ((tick * numerator + denominator / 2) / denominator, event))
+            .collect();
+        *self = TrackChunk::from_absolute(events);
+        true
+    }
+}
+
+impl MidiFile {
+    fn sync_header(&mut self) {
+        assert!(self.tracks.len() <= u16::MAX as usize);
+        self.header.data.ntrks = self.tracks.len() as u16;
+        if self.header.data.format == 0 && self.tracks.len() > 1 {
+            self.header.data.format = 1;
+        }
+    }
+
+    pub fn insert_track(&mut self, index: usize, track: TrackChunk) {
+        self.tracks.insert(index, track);
+        self.sync_header();
+    }
+
+    pub fn push_track(&mut self, track: TrackChunk) {
+        self.tracks.push(track);
+        self.sync_header();
+    }
+
+    pub fn remove_track(&mut self, index: usize) -> TrackChunk {
+        let track = self.tracks.remove(index);
+        self.sync_header();
+        track
+    }
+
+    pub fn move_track(&mut self, from: usize, to: usize) {
+        let track = self.tracks.remove(from);
+        self.tracks.insert(to, track);
+    }
+
+    pub fn reorder_tracks(&mut self, order: &[usize]) {
+        assert_eq!(order.len(), self.tracks.len(), "Order is not a permutation");
+        let mut tracks = self.tracks.drain(..).map(Some).collect::<Vec<_>>();
+        self.tracks = order
+            .iter()
+            .map(|index| tracks[*index].take().expect("Order is not a permutation"))
+            .collect();
+    }
+
+    pub fn merge_tracks(&mut self, indices: &[usize]) {
+        let mut indices = indices.to_vec();
+        indices.sort_unstable();
+        indices.dedup();
+        let Some(&first) = indices.first() else {
+            return;
+        };
+        let merged = TrackChunk::merge(
+            &indices
+                .iter()
+                .map(|index| self.tracks[*index].clone())
+                .collect::<Vec<_>>(),
+        );
+        for index in indices.iter().rev() {
+            self.tracks.remove(*index);
+        }
+        self.tracks.insert(first, merged);
+        self.sync_header();
+    }
+
+    pub fn split_by_program(&self) -> MidiFile {
+        let mut midi_file = MidiFile {
+            header: self.header.clone(),
+            tracks: self
+                .tracks
+                .iter()
+                .flat_map(|track| track.split_by_program())
+                .collect(),
+        };
+        midi_file.sync_header();
+        midi_file
+    }
+
+    pub fn end_tick(&self) -> u64 {
+        self.tracks
+            .iter()
+            .map(|track| track.end_tick())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn rescale(&mut self, division: Division) -> bool {
+        let (numerator, denominator) = match (self.header.data.division, division) {
+            (
+                Division::TicksPerQuarterNote { ticks: from },
+                Division::TicksPerQuarterNote { ticks: to },
+            ) => (to as u64, from as u64),
+            (
+                Division::FramesPerSecond {
+                    frames: from_frames,
+                    ticks: from_ticks,
+                },
+                Division::FramesPerSecond {
+                    frames: to_frames,
+                    ticks: to_ticks,
+                },
+            ) => (
+                (ticks_per_second(to_frames, to_ticks) * 100.0).round() as u64,
+                (ticks_per_second(from_frames, from_ticks) * 100.0).round() as u64,
+            ),
+            _ => return false,
+        };
+        // A zero division in either file would collapse or divide every tick
+        if numerator == 0 || denominator == 0 {
+            return false;
+        }
+        if numerator != denominator {
+            self.tracks.iter_mut().for_each(|track| {
+                track.scale_ticks(numerator, denominator);
+            });
+        }
+        self.header.data.division = division;
+        true
+    }
+
+    pub fn concat(&self, other: &MidiFile) -> Option<MidiFile> {
+        let division = match (self.header.data.division, other.header.data.division) {
+            (
+                Division::TicksPerQuarterNote { ticks: a },
+                Division::TicksPerQuarterNote { ticks: b },
+            ) => {
+                let lcm = a as u64 / gcd(a as u64, b as u64) * b as u64;
+                Division::TicksPerQuarterNote {
+                    ticks: if lcm <= 0x7fff { lcm as u16 } else { a.max(b) },
+                }
+            }
+            (division @ Division::FramesPerSecond { .. }, Division::FramesPerSecond { .. }) => {
+                division
+            }
+            _ => return None,
+        };
+        let mut first = self.clone();
+        let mut second = other.clone();
+        if !first.rescale(division) || !second.rescale(division) {
+            return None;
+        }
+
+        if first.header.data.format == 2 || second.header.data.format == 2 {
+            first.tracks.extend(second.tracks);
+            first.header.data.format = 2;
+            first.sync_header();
+            return Some(first);
+        }
+
+        let offset = first.end_tick();
+        let carried_state = match division {
+            Division::TicksPerQuarterNote { .. } => {
+                initial_state_changes(&first.tracks, &second.tracks)
+            }
+            Division::FramesPerSecond { .. } => Vec::new(),
+        };
+        let count = first.tracks.len().max(second.tracks.len());
+        let tracks = (0..count)
+            .map(|index| {
+                let mut events = first
+                    .tracks
+                    .get(index)
+                    .map(|track| track.to_absolute())
+                    .unwrap_or_default();
+                let mut end_tick = events.last().map(|(tick, _)| *tick).unwrap_or(0);
+                events.retain(|(_, event)| event != &Event::MetaEvent(MetaEvent::EndOfTrack));
+                if index == 0 {
+                    events.extend(carried_state.iter().map(|event| (offset, event.clone())));
+                }
+                if let Some(track) = second.tracks.get(index) {
+                    events.extend(
+                        track
+                            .to_absolute()
+                            .into_iter()
+                            .filter(|(_, event)| event != &Event::MetaEvent(MetaEvent::EndOfTrack))
+                            .map(|(tick, event)| (tick + offset, event)),
+                    );
+                    end_tick = offset + track.end_tick();
+                }
+                events.push((end_tick, Event::MetaEvent(MetaEvent::EndOfTrack)));
+                TrackChunk::from_absolute(events)
+            })
+            .collect();
+        let mut midi_file = MidiFile {
+            header: first.header,
+            tracks,
+        };
+        midi_file.sync_header();
+        Some(midi_file)
+    }
+}
+
+type MetaMatcher = fn(&MetaEvent) -> bool;
+
+fn initial_state_changes(first: &[TrackChunk], second: &[TrackChunk]) -> Vec<Event> {
+    let last_meta = |tracks: &[TrackChunk], matches: MetaMatcher| {
+        let mut found: Option<(u64, MetaEvent)> = None;
+        for track in tracks.iter() {
+            for (tick, event) in track.to_absolute() {
+                if let Event::MetaEvent(meta) = event {
+                    if matches(&meta) && found.as_ref().is_none_or(|(last, _)| tick >= *last) {
+                        found = Some((tick, meta));
+                    }
+                }
+            }
+        }
+        found.map(|(_, meta)| meta)
+    };
+    let has_initial_meta = |tracks: &[TrackChunk], matches: MetaMatcher| {
+        tracks.iter().any(|track| {
+            track
+                .to_absolute()
+                .iter()
+                .take_while(|(tick, _)| *tick == 0)
+                .any(|(_, event)| matches!(event, Event::MetaEvent(meta) if matches(meta)))
+        })
+    };
+
+    let defaults: [(MetaMatcher, MetaEvent); 3] = [
+        (
+            |meta| matches!(meta, MetaEvent::SetTempo { .. }),
+            MetaEvent::SetTempo {
+                tempo: DEFAULT_TEMPO,
+            },
+        ),
+        (
+            |meta| matches!(meta, MetaEvent::TimeSignature { .. }),
+            MetaEvent::TimeSignature {
+                numerator: 4,
+                denominator: 2,
+                clocks_per_metronome_click: 24,
+                thirty_seconds_per_quarter_note: 8,
+            },
+        ),
+        (
+            |meta| matches!(meta, MetaEvent::KeySignature { .. }),
+            MetaEvent::KeySignature { key: 0, scale: 0 },
+        ),
+    ];
+    defaults
+        .into_iter()
+        .filter_map(|(matches, default)| {
+            if has_initial_meta(second, matches) {
+                return None;
+            }
+            match last_meta(first, matches) {
+                Some(last) if last != default => Some(Event::MetaEvent(default)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, midi_event::ChannelVoiceMessage, Event},
+        header::{Division, HeaderChunk, HeaderData},
+        midi_file::MidiFile,
+        track::TrackChunk,
+    };
+
+    fn end_of_track() -> Event {
+        Event::MetaEvent(MetaEvent::EndOfTrack)
+    }
+
+    fn midi_file(ticks: u16, tracks: Vec<TrackChunk>) -> MidiFile {
+        MidiFile {
+            header: HeaderChunk {
+                chunk_type: "MThd".to_string(),
+                length: 6,
+                data: HeaderData {
+                    format: 1,
+                    ntrks: tracks.len() as u16,
+                    division: Division::TicksPerQuarterNote { ticks },
+                },
+            },
+            tracks,
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let first = TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+            (100, end_of_track()),
+        ]);
+        let second = TrackChunk::from_absolute(vec![
+            (
+                50,
+                Event::from(ChannelVoiceMessage::ProgramChange {
+                    channel: 0,
+                    program: 1,
+                }),
+            ),
+            (200, end_of_track()),
+        ]);
+        let merged = TrackChunk::merge(&[first, second]);
+        assert_eq!(merged.data.len(), 3);
+        assert_eq!(merged.data[1].delta_time.value, 50);
+        assert_eq!(merged.data[2].delta_time.value, 150);
+    }
+
+    #[test]
+    fn test_split_by_program() {
+        let volume = |value| {
+            Event::from(ChannelVoiceMessage::ControlChange {
+                channel: 0,
+                controller: 7,
+                value,
+            })
+        };
+        let bend = Event::from(ChannelVoiceMessage::PitchBendChange {
+            channel: 0,
+            value: 0x3000,
+        });
+        let program = Event::from(ChannelVoiceMessage::ProgramChange {
+            channel: 0,
+            program: 40,
+        });
+        let track = TrackChunk::from_absolute(vec![
+            (0, volume(90)),
+            (
+                0,
+                Event::from(ChannelVoiceMessage::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 100,
+                }),
+            ),
+            (5, bend.clone()),
+            (8, volume(70)),
+            (10, program.clone()),
+            (
+                20,
+                Event::from(ChannelVoiceMessage::NoteOff {
+                    channel: 0,
+                    note: 60,
+                    velocity: 0,
+                }),
+            ),
+            (30, end_of_track()),
+        ]);
+        let tracks = track.split_by_program();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].data.len(), 6);
+        // The new group starts from the channel's latest controller values
+        assert_eq!(
+            tracks[1].to_absolute(),
+            vec![
+                (10, bend),
+                (10, volume(70)),
+                (10, program),
+                (30, end_of_track()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reorder_and_remove() {
+        let tracks = (0..3)
+            .map(|index| TrackChunk::from_absolute(vec![(index, end_of_track())]))
+            .collect();
+        let mut midi_file = midi_file(96, tracks);
+        midi_file.reorder_tracks(&[2, 0, 1]);
+        assert_eq!(midi_file.tracks[0].end_tick(), 2);
+        midi_file.remove_track(0);
+        assert_eq!(midi_file.header.data.ntrks, 2);
+        assert_eq!(midi_file.tracks[0].end_tick(), 0);
+    }
+
+    #[test]
+    fn test_rescale() {
+        let track = TrackChunk::from_absolute(vec![(96, end_of_track())]);
+        let mut midi_file = midi_file(0, vec![track.clone()]);
+        assert!(!midi_file.rescale(Division::TicksPerQuarterNote { ticks: 96 }));
+        midi_file.header.data.division = Division::TicksPerQuarterNote { ticks: 96 };
+        assert!(!midi_file.rescale(Division::TicksPerQuarterNote { ticks: 0 }));
+        assert_eq!(midi_file.tracks, [track]);
+        assert!(midi_file.rescale(Division::TicksPerQuarterNote { ticks: 48 }));
+        assert_eq!(midi_file.tracks[0].end_tick(), 48);
+        assert!(!midi_file.tracks[0].scale_ticks(1, 0));
+    }
+
+    #[test]
+    fn test_concat() {
+        let first = midi_file(
+            96,
+            vec![TrackChunk::from_absolute(vec![
+                (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+                (96, end_of_track()),
+            ])],
+        );
+        let second = midi_file(
+            48,
+            vec![
+                TrackChunk::from_absolute(vec![(48, end_of_track())]),
+                TrackChunk::from_absolute(vec![(24, end_of_track())]),
+            ],
+        );
+        let midi_file = first.concat(&second).unwrap();
+        assert_eq!(
+            midi_file.header.data.division,
+            Division::TicksPerQuarterNote { ticks: 96 }
+        );
+        assert_eq!(midi_file.header.data.ntrks, 2);
+        assert_eq!(
+            midi_file.tracks[0].to_absolute(),
+            vec![
+                (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+                (96, Event::MetaEvent(MetaEvent::SetTempo { tempo: 500000 })),
+                (192, end_of_track()),
+            ]
+        );
+        assert_eq!(
+            midi_file.tracks[1].to_absolute(),
+            vec![(144, end_of_track())]
+        );
+    }
+}
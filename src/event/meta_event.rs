@@ -1,14 +1,18 @@
 use encoding_rs::WINDOWS_1252;
 use nom::{
     bytes::complete::{tag, take},
+    number::complete::be_u16,
     IResult,
 };
 
 use crate::{utils::be_u7, variable_length_quantity::VariableLengthQuantity};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MetaEvent {
-    SequenceNumber,
+    SequenceNumber {
+        number: u16,
+    },
     TextEvent {
         length: VariableLengthQuantity,
         text: String,
@@ -72,16 +76,55 @@ pub enum MetaEvent {
     },
 }
 
+// The stored length is only what was read, the written one always matches the data
+fn with_length(meta_type: u8, data: &[u8]) -> Vec<u8> {
+    let length = VariableLengthQuantity {
+        value: data.len() as u32,
+    };
+    [&[0xff, meta_type], length.to_bytes().as_slice(), data].concat()
+}
+
 impl MetaEvent {
+    pub fn text(meta_type: u8, text: &str) -> Self {
+        let length = VariableLengthQuantity {
+            value: WINDOWS_1252.encode(text).0.len() as u32,
+        };
+        let text = text.to_string();
+        match meta_type {
+            0x01 => Self::TextEvent { length, text },
+            0x02 => Self::CopyrightNotice { length, text },
+            0x03 => Self::SequenceOrTrackName { length, text },
+            0x04 => Self::InstrumentName { length, text },
+            0x05 => Self::Lyric { length, text },
+            0x06 => Self::Marker { length, text },
+            0x07 => Self::CuePoint { length, text },
+            _ => panic!("Invalid text meta type {}", meta_type),
+        }
+    }
+
+    pub fn get_text(&self) -> Option<&str> {
+        match self {
+            Self::TextEvent { text, .. }
+            | Self::CopyrightNotice { text, .. }
+            | Self::SequenceOrTrackName { text, .. }
+            | Self::InstrumentName { text, .. }
+            | Self::Lyric { text, .. }
+            | Self::Marker { text, .. }
+            | Self::CuePoint { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
     pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
         let (input, _) = tag(&[0xff])(input)?;
         let (input, meta_type) = be_u7(input)?;
         match meta_type {
             0x00 => {
                 let (input, _) = tag(&[0x02])(input)?;
-                Ok((input, Self::SequenceNumber))
+                let (input, number) = be_u16(input)?;
+                Ok((input, Self::SequenceNumber { number }))
             }
-            0x01 | 0x02 | 0x03 | 0x04 | 0x05 | 0x06 | 0x07 => {
+            0x01..=0x07 => {
                 let (input, length) = VariableLengthQuantity::parse(input)?;
                 let (input, text) = take(length.value)(input)?;
                 let (text, _, replacement_used) = WINDOWS_1252.decode(text);
@@ -188,49 +231,18 @@ impl MetaEvent {
 
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
-            Self::SequenceNumber => vec![0xff, 0x00, 0x02],
-            Self::TextEvent { length, text } => [
-                &[0xff, 0x01],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
-            Self::CopyrightNotice { length, text } => [
-                &[0xff, 0x02],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
-            Self::SequenceOrTrackName { length, text } => [
-                &[0xff, 0x03],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
-            Self::InstrumentName { length, text } => [
-                &[0xff, 0x04],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
-            Self::Lyric { length, text } => [
-                &[0xff, 0x05],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
-            Self::Marker { length, text } => [
-                &[0xff, 0x06],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
-            Self::CuePoint { length, text } => [
-                &[0xff, 0x07],
-                length.to_bytes().as_slice(),
-                WINDOWS_1252.encode(text).0.as_ref(),
-            ]
-            .concat(),
+            Self::SequenceNumber { number } => {
+                [&[0xff, 0x00, 0x02][..], &number.to_be_bytes()].concat()
+            }
+            Self::TextEvent { text, .. } => with_length(0x01, &WINDOWS_1252.encode(text).0),
+            Self::CopyrightNotice { text, .. } => with_length(0x02, &WINDOWS_1252.encode(text).0),
+            Self::SequenceOrTrackName { text, .. } => {
+                with_length(0x03, &WINDOWS_1252.encode(text).0)
+            }
+            Self::InstrumentName { text, .. } => with_length(0x04, &WINDOWS_1252.encode(text).0),
+            Self::Lyric { text, .. } => with_length(0x05, &WINDOWS_1252.encode(text).0),
+            Self::Marker { text, .. } => with_length(0x06, &WINDOWS_1252.encode(text).0),
+            Self::CuePoint { text, .. } => with_length(0x07, &WINDOWS_1252.encode(text).0),
             Self::MidiChannelPrefix { channel } => vec![0xff, 0x20, 0x01, *channel],
             Self::EndOfTrack => vec![0xff, 0x2f, 0x00],
             Self::SetTempo { tempo } => {
@@ -262,14 +274,25 @@ impl MetaEvent {
                 ]
             }
             Self::KeySignature { key, scale } => vec![0xff, 0x59, 0x02, *key, *scale],
-            Self::SequencerSpecificEvent { length, data } => {
-                [&[0xff, 0x7f], length.to_bytes().as_slice(), data].concat()
-            }
+            Self::SequencerSpecificEvent { data, .. } => with_length(0x7f, data),
             Self::UnknownMetaEvent {
-                meta_type,
-                length,
-                data,
-            } => [&[0xff, *meta_type], length.to_bytes().as_slice(), data].concat(),
+                meta_type, data, ..
+            } => with_length(*meta_type, data),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MetaEvent;
+
+    #[test]
+    fn test_sequence_number() {
+        // The two data bytes are the number, the next event starts after them
+        let bytes = [0xff, 0x00, 0x02, 0x01, 0x07, 0xff, 0x2f, 0x00];
+        let (rest, event) = MetaEvent::parse(&bytes).unwrap();
+        assert_eq!(event, MetaEvent::SequenceNumber { number: 0x0107 });
+        assert_eq!(MetaEvent::parse(rest), Ok((&[][..], MetaEvent::EndOfTrack)));
+        assert_eq!(event.to_bytes(), bytes[..5]);
+    }
+}
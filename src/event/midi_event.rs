@@ -3,6 +3,8 @@ use nom::{combinator::peek, IResult};
 use crate::utils::be_u7;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawChannelVoiceMessage"))]
 pub enum ChannelVoiceMessage {
     NoteOff {
         channel: u8,
@@ -38,7 +40,134 @@ pub enum ChannelVoiceMessage {
     },
 }
 
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+enum RawChannelVoiceMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyphonicKeyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    PitchBendChange {
+        channel: u8,
+        value: u16,
+    },
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawChannelVoiceMessage> for ChannelVoiceMessage {
+    type Error = &'static str;
+
+    fn try_from(raw: RawChannelVoiceMessage) -> Result<Self, Self::Error> {
+        let message = match raw {
+            RawChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => Self::NoteOff {
+                channel,
+                note,
+                velocity,
+            },
+            RawChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => Self::NoteOn {
+                channel,
+                note,
+                velocity,
+            },
+            RawChannelVoiceMessage::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            } => Self::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            },
+            RawChannelVoiceMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => Self::ControlChange {
+                channel,
+                controller,
+                value,
+            },
+            RawChannelVoiceMessage::ProgramChange { channel, program } => {
+                Self::ProgramChange { channel, program }
+            }
+            RawChannelVoiceMessage::ChannelPressure { channel, pressure } => {
+                Self::ChannelPressure { channel, pressure }
+            }
+            RawChannelVoiceMessage::PitchBendChange { channel, value } => {
+                Self::PitchBendChange { channel, value }
+            }
+        };
+        if message.is_valid() {
+            Ok(message)
+        } else {
+            Err("channel above 15 or data byte above 127")
+        }
+    }
+}
+
 impl ChannelVoiceMessage {
+    // Everything the parser could have produced, so to_bytes writes it back unchanged
+    #[cfg(feature = "serde")]
+    fn is_valid(&self) -> bool {
+        match *self {
+            Self::NoteOff {
+                channel,
+                note,
+                velocity,
+            }
+            | Self::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => channel < 16 && note < 0x80 && velocity < 0x80,
+            Self::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            } => channel < 16 && note < 0x80 && pressure < 0x80,
+            Self::ControlChange {
+                channel,
+                controller,
+                value,
+            } => channel < 16 && controller < 0x80 && value < 0x80,
+            Self::ProgramChange { channel, program } => channel < 16 && program < 0x80,
+            Self::ChannelPressure { channel, pressure } => channel < 16 && pressure < 0x80,
+            Self::PitchBendChange { channel, value } => channel < 16 && value < 0x4000,
+        }
+    }
+
     pub fn parse(input: &[u8], status: u8) -> IResult<&[u8], Self> {
         let message_type = status >> 4;
         let channel = status & 0x0f;
@@ -145,9 +274,69 @@ impl ChannelVoiceMessage {
             Self::PitchBendChange { channel, .. } => 0xe0 | channel,
         }
     }
+
+    pub fn pitch_bend_from_centered(channel: u8, value: i16) -> Self {
+        Self::PitchBendChange {
+            channel,
+            value: (value.clamp(-0x2000, 0x1fff) + 0x2000) as u16,
+        }
+    }
+
+    pub fn pitch_bend_from_semitones(channel: u8, semitones: f64, sensitivity: f64) -> Self {
+        assert!(sensitivity > 0.0);
+        let value = (semitones / sensitivity * 8192.0).round();
+        Self::pitch_bend_from_centered(channel, value.clamp(-8192.0, 8191.0) as i16)
+    }
+
+    pub fn pitch_bend_from_cents(channel: u8, cents: f64, sensitivity: f64) -> Self {
+        Self::pitch_bend_from_semitones(channel, cents / 100.0, sensitivity)
+    }
+
+    pub fn pitch_bend_centered(&self) -> Option<i16> {
+        match self {
+            Self::PitchBendChange { value, .. } => Some(*value as i16 - 0x2000),
+            _ => None,
+        }
+    }
+
+    pub fn pitch_bend_semitones(&self, sensitivity: f64) -> Option<f64> {
+        self.pitch_bend_centered()
+            .map(|value| value as f64 / 8192.0 * sensitivity)
+    }
+
+    pub fn pitch_bend_cents(&self, sensitivity: f64) -> Option<f64> {
+        self.pitch_bend_semitones(sensitivity)
+            .map(|semitones| semitones * 100.0)
+    }
+
+    pub fn get_channel(&self) -> u8 {
+        match self {
+            Self::NoteOff { channel, .. }
+            | Self::NoteOn { channel, .. }
+            | Self::PolyphonicKeyPressure { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::ProgramChange { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::PitchBendChange { channel, .. } => *channel,
+        }
+    }
+
+    pub fn set_channel(&mut self, new_channel: u8) {
+        assert!(new_channel < 16);
+        match self {
+            Self::NoteOff { channel, .. }
+            | Self::NoteOn { channel, .. }
+            | Self::PolyphonicKeyPressure { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::ProgramChange { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::PitchBendChange { channel, .. } => *channel = new_channel,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ModeMessage {
     LocalControlOff,
     LocalControlOn,
@@ -179,8 +368,8 @@ impl ModeMessage {
 
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
-            Self::LocalControlOff => vec![0x7f, 0x00],
-            Self::LocalControlOn => vec![0x7f, 0x7f],
+            Self::LocalControlOff => vec![0x7a, 0x00],
+            Self::LocalControlOn => vec![0x7a, 0x7f],
             Self::AllNotesOff => vec![0x7b, 0x00],
             Self::OmniModeOff => vec![0x7c, 0x00],
             Self::OmniModeOn => vec![0x7d, 0x00],
@@ -191,11 +380,36 @@ impl ModeMessage {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawChannelModeMessage"))]
 pub struct ChannelModeMessage {
     channel: u8,
     message: ModeMessage,
 }
 
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawChannelModeMessage {
+    channel: u8,
+    message: ModeMessage,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawChannelModeMessage> for ChannelModeMessage {
+    type Error = &'static str;
+
+    fn try_from(raw: RawChannelModeMessage) -> Result<Self, Self::Error> {
+        match raw.message {
+            _ if raw.channel >= 16 => Err("channel above 15"),
+            ModeMessage::MonoModeOn { n } if n >= 0x80 => Err("mono mode channel count above 127"),
+            message => Ok(Self {
+                channel: raw.channel,
+                message,
+            }),
+        }
+    }
+}
+
 impl ChannelModeMessage {
     pub fn parse(input: &[u8], status: u8) -> IResult<&[u8], Self> {
         let message_type = status >> 4;
@@ -205,6 +419,11 @@ impl ChannelModeMessage {
         Ok((input, Self { channel, message }))
     }
 
+    pub fn new(channel: u8, message: ModeMessage) -> Self {
+        assert!(channel < 16);
+        Self { channel, message }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         [vec![self.get_status()], self.message.to_bytes()].concat()
     }
@@ -212,9 +431,23 @@ impl ChannelModeMessage {
     pub fn get_status(&self) -> u8 {
         0xb0 | self.channel
     }
+
+    pub fn get_channel(&self) -> u8 {
+        self.channel
+    }
+
+    pub fn get_message(&self) -> ModeMessage {
+        self.message
+    }
+
+    pub fn set_channel(&mut self, channel: u8) {
+        assert!(channel < 16);
+        self.channel = channel;
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ChannelMessage {
     ChannelVoiceMessage(ChannelVoiceMessage),
     ChannelModeMessage(ChannelModeMessage),
@@ -231,7 +464,7 @@ impl ChannelMessage {
             0xb => {
                 let (input, controller) = peek(be_u7)(input)?;
                 match controller {
-                    0x7a | 0x7b | 0x7c | 0x7d | 0x7e | 0x7f => {
+                    0x7a..=0x7f => {
                         let (input, message) = ChannelModeMessage::parse(input, status)?;
                         Ok((input, Self::ChannelModeMessage(message)))
                     }
@@ -258,9 +491,24 @@ impl ChannelMessage {
             Self::ChannelModeMessage(message) => message.get_status(),
         }
     }
+
+    pub fn get_channel(&self) -> u8 {
+        match self {
+            Self::ChannelVoiceMessage(message) => message.get_channel(),
+            Self::ChannelModeMessage(message) => message.get_channel(),
+        }
+    }
+
+    pub fn set_channel(&mut self, channel: u8) {
+        match self {
+            Self::ChannelVoiceMessage(message) => message.set_channel(channel),
+            Self::ChannelModeMessage(message) => message.set_channel(channel),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SystemCommonMessage {
     SongPositionPointer { value: u16 },
     SongSelect { song: u8 },
@@ -311,6 +559,7 @@ impl SystemCommonMessage {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SystemRealTimeMessage {
     TimingClock,
     Start,
@@ -359,6 +608,7 @@ impl SystemRealTimeMessage {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SystemMessage {
     SystemCommonMessage(SystemCommonMessage),
     SystemRealTimeMessage(SystemRealTimeMessage),
@@ -397,6 +647,7 @@ impl SystemMessage {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MidiMessage {
     ChannelMessage(ChannelMessage),
     SystemMessage(SystemMessage),
@@ -406,7 +657,7 @@ impl MidiMessage {
     pub fn parse(input: &[u8], status: u8) -> IResult<&[u8], Self> {
         let message_type = status >> 4;
         match message_type {
-            0x8 | 0x9 | 0xa | 0xb | 0xc | 0xd | 0xe => {
+            0x8..=0xe => {
                 let (input, message) = ChannelMessage::parse(input, status)?;
                 Ok((input, Self::ChannelMessage(message)))
             }
@@ -431,4 +682,79 @@ impl MidiMessage {
             Self::SystemMessage(message) => message.get_status(),
         }
     }
+
+    pub fn get_channel(&self) -> Option<u8> {
+        match self {
+            Self::ChannelMessage(message) => Some(message.get_channel()),
+            Self::SystemMessage(_) => None,
+        }
+    }
+}
+
+impl From<ChannelVoiceMessage> for MidiMessage {
+    fn from(message: ChannelVoiceMessage) -> Self {
+        Self::ChannelMessage(ChannelMessage::ChannelVoiceMessage(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, ModeMessage};
+
+    #[test]
+    fn test_pitch_bend_conversion() {
+        let message = ChannelVoiceMessage::PitchBendChange {
+            channel: 0,
+            value: 0x3000,
+        };
+        assert_eq!(message.pitch_bend_centered(), Some(0x1000));
+        assert_eq!(message.pitch_bend_semitones(2.0), Some(1.0));
+        assert_eq!(message.pitch_bend_cents(2.0), Some(100.0));
+        assert_eq!(
+            ChannelVoiceMessage::pitch_bend_from_cents(0, 100.0, 2.0),
+            message
+        );
+        assert_eq!(
+            ChannelVoiceMessage::pitch_bend_from_semitones(0, 5.0, 2.0),
+            ChannelVoiceMessage::PitchBendChange {
+                channel: 0,
+                value: 0x3fff,
+            }
+        );
+        assert_eq!(
+            ChannelVoiceMessage::pitch_bend_from_centered(0, -0x2000),
+            ChannelVoiceMessage::PitchBendChange {
+                channel: 0,
+                value: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_mode_messages() {
+        let local_control = ChannelMessage::ChannelModeMessage(ChannelModeMessage {
+            channel: 3,
+            message: ModeMessage::LocalControlOn,
+        });
+        assert_eq!(local_control.to_bytes(), [0xb3, 0x7a, 0x7f]);
+        for message in [
+            ModeMessage::LocalControlOff,
+            ModeMessage::LocalControlOn,
+            ModeMessage::AllNotesOff,
+            ModeMessage::OmniModeOff,
+            ModeMessage::OmniModeOn,
+            ModeMessage::MonoModeOn { n: 4 },
+            ModeMessage::PolyModeOn,
+        ] {
+            let message = ChannelMessage::ChannelModeMessage(ChannelModeMessage {
+                channel: 3,
+                message,
+            });
+            let bytes = message.to_bytes();
+            assert_eq!(
+                ChannelMessage::parse(&bytes[1..], bytes[0]),
+                Ok((&[][..], message))
+            );
+        }
+    }
 }
@@ -1,4 +1,8 @@
-use self::{meta_event::MetaEvent, midi_event::MidiMessage, sysex_event::SysExEvent};
+use self::{
+    meta_event::MetaEvent,
+    midi_event::{ChannelVoiceMessage, MidiMessage},
+    sysex_event::SysExEvent,
+};
 use crate::variable_length_quantity::VariableLengthQuantity;
 use nom::{combinator::peek, number::complete::be_u8, IResult};
 
@@ -7,6 +11,7 @@ pub mod midi_event;
 pub mod sysex_event;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Event {
     MidiEvent(MidiMessage),
     SysExEvent(SysExEvent),
@@ -53,9 +58,23 @@ impl Event {
             Self::MetaEvent(_) => 0xff,
         }
     }
+
+    pub fn get_channel(&self) -> Option<u8> {
+        match self {
+            Self::MidiEvent(midi_message) => midi_message.get_channel(),
+            _ => None,
+        }
+    }
+}
+
+impl From<ChannelVoiceMessage> for Event {
+    fn from(message: ChannelVoiceMessage) -> Self {
+        Self::MidiEvent(MidiMessage::from(message))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MTrkEvent {
     pub delta_time: VariableLengthQuantity,
     pub event: Event,
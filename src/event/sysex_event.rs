@@ -7,40 +7,41 @@ use nom::{
 use crate::variable_length_quantity::VariableLengthQuantity;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawSysExEvent"))]
 pub struct SysExEvent {
     prefix: u8,
     data: Vec<u8>,
 }
 
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSysExEvent {
+    prefix: u8,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawSysExEvent> for SysExEvent {
+    type Error = &'static str;
+
+    fn try_from(raw: RawSysExEvent) -> Result<Self, Self::Error> {
+        Self::try_new(raw.prefix, raw.data).ok_or("invalid sysex prefix or empty sysex message")
+    }
+}
+
 impl SysExEvent {
     pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
         let (input, prefix) = alt((tag(&[0xf0]), tag(&[0xf7])))(input)?;
         let (input, len) = VariableLengthQuantity::parse(input)?;
         let (input, data) = take(len.value)(input)?;
-        let (last_byte, penultimate_data) = match data.split_last() {
-            Some((last_byte, penultimate_data)) => (last_byte, penultimate_data),
-            None => {
-                return Err(nom::Err::Error(nom::error::Error::new(
-                    input,
-                    nom::error::ErrorKind::Verify,
-                )))
-            }
-        };
-        penultimate_data
-            .iter()
-            .for_each(|byte| assert_eq!(byte >> 7, 0, "Invalid data: {:x?}", data));
-        assert!(
-            *last_byte >> 7 == 0 || *last_byte == 0xf7,
-            "Data: {:x?}",
-            data
-        );
-        Ok((
-            input,
-            Self {
-                prefix: prefix[0],
-                data: data.to_vec(),
-            },
-        ))
+        match Self::try_new(prefix[0], data.to_vec()) {
+            Some(event) => Ok((input, event)),
+            None => Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Verify,
+            ))),
+        }
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -54,7 +55,43 @@ impl SysExEvent {
         bytes
     }
 
+    // A message must start with F0 and have data, an escape with F7 may be empty
+    pub fn try_new(prefix: u8, data: Vec<u8>) -> Option<Self> {
+        match prefix {
+            0xf0 if !data.is_empty() => Some(Self { prefix, data }),
+            0xf7 => Some(Self { prefix, data }),
+            _ => None,
+        }
+    }
+
+    pub fn new(prefix: u8, data: Vec<u8>) -> Self {
+        Self::try_new(prefix, data).expect("Invalid sysex event")
+    }
+
+    pub fn from_payload(payload: &[u8]) -> Self {
+        Self::new(0xf0, [payload, &[0xf7]].concat())
+    }
+
+    pub fn escape(bytes: &[u8]) -> Self {
+        Self::new(0xf7, bytes.to_vec())
+    }
+
     pub fn get_status(&self) -> u8 {
         self.prefix
     }
+
+    pub fn is_terminated(&self) -> bool {
+        self.data.last() == Some(&0xf7)
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn get_payload(&self) -> Option<&[u8]> {
+        match (self.prefix, self.data.split_last()) {
+            (0xf0, Some((0xf7, payload))) => Some(payload),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use crate::{
+    channel_state::value_at,
+    general_midi::{DRUMS, PERCUSSION_CHANNEL},
+    midi_file::MidiFile,
+};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+// Onsets are compared to the millisecond, so tick scaling with an equivalent tempo does not matter
+const EXACT_RESOLUTION: f64 = 0.001;
+// Onset intervals for the near duplicate hash are coarse enough to survive small tempo edits
+const INTERVAL_RESOLUTION: f64 = 0.05;
+const SHINGLE: usize = 3;
+// A song has far fewer shingles than a web page, so an edit moves more bits than the usual 3
+pub const NEAR_DUPLICATE_DISTANCE: u32 = 6;
+
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+pub fn simhash(features: impl IntoIterator<Item = u64>) -> u64 {
+    let mut weights = [0i64; 64];
+    for feature in features {
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if feature >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | 1 << bit)
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Fingerprint {
+    // Equal for files with the same notes, whatever the track layout, channels or division
+    pub exact: u64,
+    // Close in Hamming distance for files with mostly the same onsets and pitches
+    pub simhash: u64,
+}
+
+impl Fingerprint {
+    pub fn distance(&self, other: &Fingerprint) -> u32 {
+        (self.simhash ^ other.simhash).count_ones()
+    }
+
+    pub fn is_near_duplicate(&self, other: &Fingerprint) -> bool {
+        self.exact == other.exact || self.distance(other) <= NEAR_DUPLICATE_DISTANCE
+    }
+}
+
+impl MidiFile {
+    // Notes as (start, duration, key, velocity, instrument) in units of the resolution
+    fn canonical_notes(&self, resolution: f64) -> Vec<(u64, u64, u8, u8, u8)> {
+        let programs = self.program_changes();
+        let tempo_map = self.tempo_map();
+        let time = |tick: u64| (tempo_map.tick_to_seconds(tick) / resolution).round() as u64;
+        let mut notes = self
+            .tracks
+            .iter()
+            .flat_map(|track| track.notes())
+            .map(|note| {
+                // The channel only matters for telling drums apart from the program
+                let instrument = match note.channel {
+                    PERCUSSION_CHANNEL => DRUMS,
+                    channel => value_at(&programs[channel as usize], note.start).unwrap_or(0),
+                };
+                let start = time(note.start);
+                (
+                    start,
+                    time(note.end) - start,
+                    note.key,
+                    note.velocity,
+                    instrument,
+                )
+            })
+            .collect::<Vec<_>>();
+        notes.sort();
+        notes
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        let notes = self.canonical_notes(EXACT_RESOLUTION);
+        let mut data = Vec::new();
+        for (start, duration, key, velocity, instrument) in notes.iter().copied() {
+            data.extend(start.to_le_bytes());
+            data.extend(duration.to_le_bytes());
+            data.extend([key, velocity, instrument]);
+        }
+
+        // Shingles of consecutive onsets, each the pitches struck and the time to the next onset
+        let mut onsets: Vec<(u64, Vec<u8>)> = Vec::new();
+        for (start, _, key, _, instrument) in notes {
+            let key = match instrument {
+                DRUMS => key | 0x80,
+                _ => key,
+            };
+            match onsets.last_mut() {
+                Some((onset, keys)) if *onset == start => keys.push(key),
+                _ => onsets.push((start, vec![key])),
+            }
+        }
+        let events = onsets
+            .iter()
+            .enumerate()
+            .map(|(index, (start, keys))| {
+                let mut keys = keys.clone();
+                keys.sort();
+                keys.dedup();
+                let interval = onsets.get(index + 1).map_or(0, |(next, _)| {
+                    ((next - start) as f64 * EXACT_RESOLUTION / INTERVAL_RESOLUTION).round() as u16
+                });
+                let mut event = interval.to_le_bytes().to_vec();
+                event.extend(keys);
+                event.push(0xff);
+                event
+            })
+            .collect::<Vec<_>>();
+        let features = events
+            .windows(SHINGLE.min(events.len()).max(1))
+            .map(|shingle| fnv1a(&shingle.concat()));
+
+        Fingerprint {
+            exact: fnv1a(&data),
+            simhash: simhash(features),
+        }
+    }
+}
+
+// Groups of indices whose fingerprints are within the distance of each other, transitively
+pub fn duplicate_groups(fingerprints: &[Fingerprint], max_distance: u32) -> Vec<Vec<usize>> {
+    assert!(max_distance < 64);
+    let mut parents = (0..fingerprints.len()).collect::<Vec<_>>();
+    fn root(parents: &mut [usize], index: usize) -> usize {
+        let mut index = index;
+        while parents[index] != index {
+            parents[index] = parents[parents[index]];
+            index = parents[index];
+        }
+        index
+    }
+
+    // Hashes within the distance agree on at least one of distance + 1 bands
+    let bands = max_distance as usize + 1;
+    let mut buckets: HashMap<(usize, u64), Vec<usize>> = HashMap::new();
+    for (index, fingerprint) in fingerprints.iter().enumerate() {
+        for band in 0..bands {
+            let (low, high) = (band * 64 / bands, (band + 1) * 64 / bands);
+            let bits = (fingerprint.simhash >> low) & (u64::MAX >> (64 - (high - low)));
+            buckets.entry((band, bits)).or_default().push(index);
+        }
+        buckets
+            .entry((bands, fingerprint.exact))
+            .or_default()
+            .push(index);
+    }
+    for ((band, _), indices) in buckets.iter() {
+        for (position, a) in indices.iter().enumerate() {
+            for b in indices[position + 1..].iter() {
+                if *band == bands || fingerprints[*a].distance(&fingerprints[*b]) <= max_distance {
+                    let (a, b) = (root(&mut parents, *a), root(&mut parents, *b));
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of = HashMap::new();
+    for index in 0..fingerprints.len() {
+        let root = root(&mut parents, index);
+        let group = *group_of.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(index);
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, Event},
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+    };
+
+    use super::{duplicate_groups, fnv1a, NEAR_DUPLICATE_DISTANCE};
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        let melody = |channel, scale: u64, changed: bool| {
+            (0..48u64)
+                .map(|index| Note {
+                    channel,
+                    key: match (changed, index) {
+                        (true, 20) => 50,
+                        _ => [60, 62, 64, 65, 67, 69, 71, 72][(index * 5 % 8) as usize],
+                    },
+                    velocity: 100,
+                    start: index * 48 * scale,
+                    end: (index * 48 + 40) * scale,
+                })
+                .collect::<Vec<_>>()
+        };
+        let bass = |channel, scale: u64| {
+            (0..12u64)
+                .map(|index| Note {
+                    channel,
+                    key: 36 + (index % 3) as u8,
+                    velocity: 90,
+                    start: index * 192 * scale,
+                    end: (index + 1) * 192 * scale,
+                })
+                .collect::<Vec<_>>()
+        };
+        let original = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![
+                TrackChunk::from_notes(&melody(0, 1, false)),
+                TrackChunk::from_notes(&bass(1, 1)),
+            ],
+        );
+        // Reordered tracks on other channels at double the division, with a name
+        let mut named = TrackChunk::from_notes(&bass(3, 2)).to_absolute();
+        named.insert(0, (0, Event::MetaEvent(MetaEvent::text(0x03, "Bass"))));
+        let reupload = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 192 },
+            vec![
+                TrackChunk::from_absolute(named),
+                TrackChunk::from_notes(&melody(2, 2, false)),
+            ],
+        );
+        let edited = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![
+                TrackChunk::from_notes(&melody(0, 1, true)),
+                TrackChunk::from_notes(&bass(1, 1)),
+            ],
+        );
+        let other = MidiFile::new(
+            0,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![TrackChunk::from_notes(&bass(0, 1))],
+        );
+
+        let fingerprints = [&original, &reupload, &edited, &other]
+            .map(|midi_file| midi_file.fingerprint())
+            .to_vec();
+        assert_eq!(fingerprints[0], fingerprints[1]);
+        assert_ne!(fingerprints[0].exact, fingerprints[2].exact);
+        assert!(fingerprints[0].is_near_duplicate(&fingerprints[2]));
+        assert!(!fingerprints[0].is_near_duplicate(&fingerprints[3]));
+        assert_eq!(
+            duplicate_groups(&fingerprints, NEAR_DUPLICATE_DISTANCE),
+            [vec![0, 1, 2]]
+        );
+    }
+}
@@ -0,0 +1,1435 @@
+use crate::event::midi_event::ChannelVoiceMessage;
+
+pub const PERCUSSION_CHANNEL: u8 = 9;
+// The program given to the percussion channel where notes are grouped by program
+pub const DRUMS: u8 = 128;
+
+pub const INSTRUMENTS: [&str; 128] = [
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavinet",
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "Synth Strings 1",
+    "Synth Strings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "Synth Brass 1",
+    "Synth Brass 2",
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bag pipe",
+    "Fiddle",
+    "Shanai",
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+pub const FAMILIES: [&str; 16] = [
+    "Piano",
+    "Chromatic Percussion",
+    "Organ",
+    "Guitar",
+    "Bass",
+    "Strings",
+    "Ensemble",
+    "Brass",
+    "Reed",
+    "Pipe",
+    "Synth Lead",
+    "Synth Pad",
+    "Synth Effects",
+    "Ethnic",
+    "Percussive",
+    "Sound Effects",
+];
+
+const PERCUSSION: [&str; 61] = [
+    "High Q",
+    "Slap",
+    "Scratch Push",
+    "Scratch Pull",
+    "Sticks",
+    "Square Click",
+    "Metronome Click",
+    "Metronome Bell",
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+    "Shaker",
+    "Jingle Bell",
+    "Belltree",
+    "Castanets",
+    "Mute Surdo",
+    "Open Surdo",
+];
+
+const PERCUSSION_FIRST_NOTE: u8 = 27;
+const GM1_PERCUSSION: std::ops::RangeInclusive<u8> = 35..=81;
+
+const DRUM_KITS: [(u8, &str); 9] = [
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "Analog Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+
+const CONTROLLERS: &[(u8, &str)] = &[
+    (0, "Bank Select"),
+    (1, "Modulation Wheel"),
+    (2, "Breath Controller"),
+    (4, "Foot Controller"),
+    (5, "Portamento Time"),
+    (6, "Data Entry"),
+    (7, "Channel Volume"),
+    (8, "Balance"),
+    (10, "Pan"),
+    (11, "Expression Controller"),
+    (12, "Effect Control 1"),
+    (13, "Effect Control 2"),
+    (16, "General Purpose Controller 1"),
+    (17, "General Purpose Controller 2"),
+    (18, "General Purpose Controller 3"),
+    (19, "General Purpose Controller 4"),
+    (32, "Bank Select LSB"),
+    (33, "Modulation Wheel LSB"),
+    (34, "Breath Controller LSB"),
+    (36, "Foot Controller LSB"),
+    (37, "Portamento Time LSB"),
+    (38, "Data Entry LSB"),
+    (39, "Channel Volume LSB"),
+    (40, "Balance LSB"),
+    (42, "Pan LSB"),
+    (43, "Expression Controller LSB"),
+    (44, "Effect Control 1 LSB"),
+    (45, "Effect Control 2 LSB"),
+    (48, "General Purpose Controller 1 LSB"),
+    (49, "General Purpose Controller 2 LSB"),
+    (50, "General Purpose Controller 3 LSB"),
+    (51, "General Purpose Controller 4 LSB"),
+    (64, "Sustain Pedal"),
+    (65, "Portamento On/Off"),
+    (66, "Sostenuto"),
+    (67, "Soft Pedal"),
+    (68, "Legato Footswitch"),
+    (69, "Hold 2"),
+    (70, "Sound Variation"),
+    (71, "Timbre/Harmonic Intensity"),
+    (72, "Release Time"),
+    (73, "Attack Time"),
+    (74, "Brightness"),
+    (75, "Decay Time"),
+    (76, "Vibrato Rate"),
+    (77, "Vibrato Depth"),
+    (78, "Vibrato Delay"),
+    (79, "Sound Controller 10"),
+    (80, "General Purpose Controller 5"),
+    (81, "General Purpose Controller 6"),
+    (82, "General Purpose Controller 7"),
+    (83, "General Purpose Controller 8"),
+    (84, "Portamento Control"),
+    (88, "High Resolution Velocity Prefix"),
+    (91, "Reverb Send Level"),
+    (92, "Tremolo Depth"),
+    (93, "Chorus Send Level"),
+    (94, "Celeste Depth"),
+    (95, "Phaser Depth"),
+    (96, "Data Increment"),
+    (97, "Data Decrement"),
+    (98, "NRPN LSB"),
+    (99, "NRPN MSB"),
+    (100, "RPN LSB"),
+    (101, "RPN MSB"),
+    (120, "All Sound Off"),
+    (121, "Reset All Controllers"),
+    (122, "Local Control"),
+    (123, "All Notes Off"),
+    (124, "Omni Mode Off"),
+    (125, "Omni Mode On"),
+    (126, "Mono Mode On"),
+    (127, "Poly Mode On"),
+];
+
+// (program, bank select MSB, name) for the Roland GS sound set: capital tones on bank 0,
+// their variations, and the CM-64 map on bank 127.
+const GS_VARIATIONS: &[(u8, u8, &str)] = &[
+    (0, 0, "Piano 1"),
+    (0, 8, "Piano 1w"),
+    (0, 16, "Piano 1d"),
+    (0, 127, "Acou Piano 1"),
+    (1, 0, "Piano 2"),
+    (1, 8, "Piano 2w"),
+    (1, 127, "Acou Piano 2"),
+    (2, 0, "Piano 3"),
+    (2, 8, "Piano 3w"),
+    (2, 127, "Acou Piano 3"),
+    (3, 0, "Honky-tonk"),
+    (3, 8, "Honky-tonk w"),
+    (3, 127, "Elec Piano 1"),
+    (4, 0, "E.Piano 1"),
+    (4, 8, "Detuned EP 1"),
+    (4, 16, "E.Piano 1v"),
+    (4, 24, "60's E.Piano"),
+    (4, 127, "Elec Piano 2"),
+    (5, 0, "E.Piano 2"),
+    (5, 8, "Detuned EP 2"),
+    (5, 16, "E.Piano 2v"),
+    (5, 127, "Elec Piano 3"),
+    (6, 0, "Harpsichord"),
+    (6, 8, "Coupled Hps."),
+    (6, 16, "Harpsi.w"),
+    (6, 24, "Harpsi.o"),
+    (6, 127, "Elec Piano 4"),
+    (7, 0, "Clav."),
+    (7, 127, "Honkytonk"),
+    (8, 0, "Celesta"),
+    (8, 127, "Elec Org 1"),
+    (9, 0, "Glockenspiel"),
+    (9, 127, "Elec Org 2"),
+    (10, 0, "Music Box"),
+    (10, 127, "Elec Org 3"),
+    (11, 0, "Vibraphone"),
+    (11, 8, "Vib.w"),
+    (11, 127, "Elec Org 4"),
+    (12, 0, "Marimba"),
+    (12, 8, "Marimba w"),
+    (12, 127, "Pipe Org 1"),
+    (13, 0, "Xylophone"),
+    (13, 127, "Pipe Org 2"),
+    (14, 0, "Tubular-bell"),
+    (14, 8, "Church Bell"),
+    (14, 9, "Carillon"),
+    (14, 127, "Pipe Org 3"),
+    (15, 0, "Santur"),
+    (15, 127, "Accordion"),
+    (16, 0, "Organ 1"),
+    (16, 8, "Detuned Or.1"),
+    (16, 16, "60's Organ 1"),
+    (16, 32, "Organ 4"),
+    (16, 127, "Harpsi 1"),
+    (17, 0, "Organ 2"),
+    (17, 8, "Detuned Or.2"),
+    (17, 32, "Organ 5"),
+    (17, 127, "Harpsi 2"),
+    (18, 0, "Organ 3"),
+    (18, 127, "Harpsi 3"),
+    (19, 0, "Church Org.1"),
+    (19, 8, "Church Org.2"),
+    (19, 16, "Church Org.3"),
+    (19, 127, "Clavi 1"),
+    (20, 0, "Reed Organ"),
+    (20, 127, "Clavi 2"),
+    (21, 0, "Accordion Fr"),
+    (21, 8, "Accordion It"),
+    (21, 127, "Clavi 3"),
+    (22, 0, "Harmonica"),
+    (22, 127, "Celesta 1"),
+    (23, 0, "Bandneon"),
+    (23, 127, "Celesta 2"),
+    (24, 0, "Nylon-str.Gt"),
+    (24, 8, "Ukulele"),
+    (24, 16, "Nylon Gt.o"),
+    (24, 32, "Nylon Gt.2"),
+    (24, 127, "Syn Brass 1"),
+    (25, 0, "Steel-str.Gt"),
+    (25, 8, "12-str.Gt"),
+    (25, 16, "Mandolin"),
+    (25, 127, "Syn Brass 2"),
+    (26, 0, "Jazz Gt."),
+    (26, 8, "Hawaiian Gt."),
+    (26, 127, "Syn Brass 3"),
+    (27, 0, "Clean Gt."),
+    (27, 8, "Chorus Gt."),
+    (27, 127, "Syn Brass 4"),
+    (28, 0, "Muted Gt."),
+    (28, 8, "Funk Gt."),
+    (28, 127, "Syn Bass 1"),
+    (29, 0, "Overdrive Gt"),
+    (29, 127, "Syn Bass 2"),
+    (30, 0, "DistortionGt"),
+    (30, 8, "Feedback Gt."),
+    (30, 127, "Syn Bass 3"),
+    (31, 0, "Gt.Harmonics"),
+    (31, 8, "Gt. Feedback"),
+    (31, 127, "Syn Bass 4"),
+    (32, 0, "Acoustic Bs."),
+    (32, 127, "Fantasy"),
+    (33, 0, "Fingered Bs."),
+    (33, 127, "Harmo Pan"),
+    (34, 0, "Picked Bs."),
+    (34, 127, "Chorale"),
+    (35, 0, "Fretless Bs."),
+    (35, 127, "Glasses"),
+    (36, 0, "Slap Bass 1"),
+    (36, 127, "Soundtrack"),
+    (37, 0, "Slap Bass 2"),
+    (37, 127, "Atmosphere"),
+    (38, 0, "Synth Bass 1"),
+    (38, 8, "Synth Bass 3"),
+    (38, 127, "Warm Bell"),
+    (39, 0, "Synth Bass 2"),
+    (39, 8, "Synth Bass 4"),
+    (39, 127, "Funny Vox"),
+    (40, 0, "Violin"),
+    (40, 8, "Slow Violin"),
+    (40, 127, "Echo Bell"),
+    (41, 0, "Viola"),
+    (41, 127, "Ice Rain"),
+    (42, 0, "Cello"),
+    (42, 127, "Oboe 2001"),
+    (43, 0, "Contrabass"),
+    (43, 127, "Echo Pan"),
+    (44, 0, "Tremolo Str"),
+    (44, 127, "Doctor Solo"),
+    (45, 0, "PizzicatoStr"),
+    (45, 127, "Schooldaze"),
+    (46, 0, "Harp"),
+    (46, 127, "Bellsinger"),
+    (47, 0, "Timpani"),
+    (47, 127, "Square Wave"),
+    (48, 0, "Strings"),
+    (48, 8, "Orchestra"),
+    (48, 127, "Str Sect 1"),
+    (49, 0, "Slow Strings"),
+    (49, 127, "Str Sect 2"),
+    (50, 0, "Syn.Strings1"),
+    (50, 8, "Syn.Strings3"),
+    (50, 127, "Str Sect 3"),
+    (51, 0, "Syn.Strings2"),
+    (51, 127, "Pizzicato"),
+    (52, 0, "Choir Aahs"),
+    (52, 127, "Violin 1"),
+    (53, 0, "Voice Oohs"),
+    (53, 127, "Violin 2"),
+    (54, 0, "SynVox"),
+    (54, 127, "Cello 1"),
+    (55, 0, "OrchestraHit"),
+    (55, 127, "Cello 2"),
+    (56, 0, "Trumpet"),
+    (56, 127, "Contrabass"),
+    (57, 0, "Trombone"),
+    (57, 127, "Harp 1"),
+    (58, 0, "Tuba"),
+    (58, 127, "Harp 2"),
+    (59, 0, "MutedTrumpet"),
+    (59, 127, "Guitar 1"),
+    (60, 0, "French Horn"),
+    (60, 127, "Guitar 2"),
+    (61, 0, "Brass 1"),
+    (61, 8, "Brass 2"),
+    (61, 127, "Elec Gtr 1"),
+    (62, 0, "Synth Brass1"),
+    (62, 8, "Synth Brass3"),
+    (62, 127, "Elec Gtr 2"),
+    (63, 0, "Synth Brass2"),
+    (63, 8, "Synth Brass4"),
+    (63, 127, "Sitar"),
+    (64, 0, "Soprano Sax"),
+    (64, 127, "Acou Bass 1"),
+    (65, 0, "Alto Sax"),
+    (65, 127, "Acou Bass 2"),
+    (66, 0, "Tenor Sax"),
+    (66, 127, "Elec Bass 1"),
+    (67, 0, "Baritone Sax"),
+    (67, 127, "Elec Bass 2"),
+    (68, 0, "Oboe"),
+    (68, 127, "Slap Bass 1"),
+    (69, 0, "English Horn"),
+    (69, 127, "Slap Bass 2"),
+    (70, 0, "Bassoon"),
+    (70, 127, "Fretless 1"),
+    (71, 0, "Clarinet"),
+    (71, 127, "Fretless 2"),
+    (72, 0, "Piccolo"),
+    (72, 127, "Flute 1"),
+    (73, 0, "Flute"),
+    (73, 127, "Flute 2"),
+    (74, 0, "Recorder"),
+    (74, 127, "Piccolo 1"),
+    (75, 0, "Pan Flute"),
+    (75, 127, "Piccolo 2"),
+    (76, 0, "Bottle Blow"),
+    (76, 127, "Recorder"),
+    (77, 0, "Shakuhachi"),
+    (77, 127, "Pan Pipes"),
+    (78, 0, "Whistle"),
+    (78, 127, "Sax 1"),
+    (79, 0, "Ocarina"),
+    (79, 127, "Sax 2"),
+    (80, 0, "Square Wave"),
+    (80, 1, "Square"),
+    (80, 8, "Sine Wave"),
+    (80, 127, "Sax 3"),
+    (81, 0, "Saw Wave"),
+    (81, 1, "Saw"),
+    (81, 8, "Doctor Solo"),
+    (81, 127, "Sax 4"),
+    (82, 0, "Syn.Calliope"),
+    (82, 127, "Clarinet 1"),
+    (83, 0, "Chiffer Lead"),
+    (83, 127, "Clarinet 2"),
+    (84, 0, "Charang"),
+    (84, 127, "Oboe"),
+    (85, 0, "Solo Vox"),
+    (85, 127, "Engl Horn"),
+    (86, 0, "5th Saw Wave"),
+    (86, 127, "Bassoon"),
+    (87, 0, "Bass & Lead"),
+    (87, 127, "Harmonica"),
+    (88, 0, "Fantasia"),
+    (88, 127, "Trumpet 1"),
+    (89, 0, "Warm Pad"),
+    (89, 127, "Trumpet 2"),
+    (90, 0, "Polysynth"),
+    (90, 127, "Trombone 1"),
+    (91, 0, "Space Voice"),
+    (91, 127, "Trombone 2"),
+    (92, 0, "Bowed Glass"),
+    (92, 127, "Fr Horn 1"),
+    (93, 0, "Metal Pad"),
+    (93, 127, "Fr Horn 2"),
+    (94, 0, "Halo Pad"),
+    (94, 127, "Tuba"),
+    (95, 0, "Sweep Pad"),
+    (95, 127, "Brs Sect 1"),
+    (96, 0, "Ice Rain"),
+    (96, 127, "Brs Sect 2"),
+    (97, 0, "Soundtrack"),
+    (97, 127, "Vibe 1"),
+    (98, 0, "Crystal"),
+    (98, 127, "Vibe 2"),
+    (99, 0, "Atmosphere"),
+    (99, 127, "Syn Mallet"),
+    (100, 0, "Brightness"),
+    (100, 127, "Windbell"),
+    (101, 0, "Goblin"),
+    (101, 127, "Glock"),
+    (102, 0, "Echo Drops"),
+    (102, 127, "Tube Bell"),
+    (103, 0, "Star Theme"),
+    (103, 127, "Xylophone"),
+    (104, 0, "Sitar"),
+    (104, 127, "Marimba"),
+    (105, 0, "Banjo"),
+    (105, 127, "Koto"),
+    (106, 0, "Shamisen"),
+    (106, 127, "Sho"),
+    (107, 0, "Koto"),
+    (107, 8, "Taisho Koto"),
+    (107, 127, "Shakuhachi"),
+    (108, 0, "Kalimba"),
+    (108, 127, "Whistle 1"),
+    (109, 0, "Bag Pipe"),
+    (109, 127, "Whistle 2"),
+    (110, 0, "Fiddle"),
+    (110, 127, "Bottleblow"),
+    (111, 0, "Shanai"),
+    (111, 127, "Breathpipe"),
+    (112, 0, "Tinkle Bell"),
+    (112, 127, "Timpani"),
+    (113, 0, "Agogo"),
+    (113, 127, "Melodic Tom"),
+    (114, 0, "Steel Drums"),
+    (114, 127, "Deep Snare"),
+    (115, 0, "Woodblock"),
+    (115, 8, "Castanets"),
+    (115, 127, "Elec Perc 1"),
+    (116, 0, "Taiko"),
+    (116, 8, "Concert BD"),
+    (116, 127, "Elec Perc 2"),
+    (117, 0, "Melo. Tom 1"),
+    (117, 8, "Melo. Tom 2"),
+    (117, 127, "Taiko"),
+    (118, 0, "Synth Drum"),
+    (118, 8, "808 Tom"),
+    (118, 127, "Taiko Rim"),
+    (119, 0, "Reverse Cym."),
+    (119, 127, "Cymbal"),
+    (120, 0, "Gt.FretNoise"),
+    (120, 1, "Gt.Cut Noise"),
+    (120, 2, "String Slap"),
+    (120, 127, "Castanets"),
+    (121, 0, "Breath Noise"),
+    (121, 1, "Fl.Key Click"),
+    (121, 127, "Triangle"),
+    (122, 0, "Seashore"),
+    (122, 1, "Rain"),
+    (122, 2, "Thunder"),
+    (122, 3, "Wind"),
+    (122, 4, "Stream"),
+    (122, 5, "Bubble"),
+    (122, 127, "Orche Hit"),
+    (123, 0, "Bird"),
+    (123, 1, "Dog"),
+    (123, 2, "Horse-Gallop"),
+    (123, 3, "Bird 2"),
+    (123, 127, "Telephone"),
+    (124, 0, "Telephone 1"),
+    (124, 1, "Telephone 2"),
+    (124, 2, "DoorCreaking"),
+    (124, 3, "Door"),
+    (124, 4, "Scratch"),
+    (124, 5, "Windchime"),
+    (124, 127, "Bird Tweet"),
+    (125, 0, "Helicopter"),
+    (125, 1, "Car-Engine"),
+    (125, 2, "Car-Stop"),
+    (125, 3, "Car-Pass"),
+    (125, 4, "Car-Crash"),
+    (125, 5, "Siren"),
+    (125, 6, "Train"),
+    (125, 7, "Jetplane"),
+    (125, 8, "Starship"),
+    (125, 9, "Burst Noise"),
+    (125, 127, "One Note Jam"),
+    (126, 0, "Applause"),
+    (126, 1, "Laughing"),
+    (126, 2, "Screaming"),
+    (126, 3, "Punch"),
+    (126, 4, "Heart Beat"),
+    (126, 5, "Footsteps"),
+    (126, 127, "Water Bells"),
+    (127, 0, "Gun Shot"),
+    (127, 1, "Machine Gun"),
+    (127, 2, "Lasergun"),
+    (127, 3, "Explosion"),
+    (127, 127, "Jungle Tune"),
+];
+
+// (program, bank select MSB, bank select LSB, name) for Yamaha XG voices: normal voices on
+// MSB 0 with their variations on the LSB, and SFX voices on MSB 64.
+const XG_VOICES: &[(u8, u8, u8, &str)] = &[
+    (0, 0, 0, "GrandPno"),
+    (0, 0, 1, "GrndPnoK"),
+    (0, 0, 18, "MelloGrP"),
+    (0, 0, 40, "PianoStr"),
+    (0, 0, 41, "Dream"),
+    (1, 0, 0, "BritePno"),
+    (1, 0, 1, "BritPnoK"),
+    (2, 0, 0, "El.GrPno"),
+    (2, 0, 1, "ElGrPnoK"),
+    (2, 0, 32, "Det.CP80"),
+    (2, 0, 40, "LayerCP1"),
+    (2, 0, 41, "LayerCP2"),
+    (3, 0, 0, "HnkyTonk"),
+    (3, 0, 1, "HnkyTnkK"),
+    (4, 0, 0, "E.Piano1"),
+    (4, 0, 1, "El.Pno1K"),
+    (4, 0, 18, "MelloEP1"),
+    (4, 0, 32, "Chor.EP1"),
+    (4, 0, 40, "HardEl.P"),
+    (4, 0, 45, "VX El.P1"),
+    (4, 0, 64, "60sEl.P"),
+    (5, 0, 0, "E.Piano2"),
+    (5, 0, 1, "El.Pno2K"),
+    (5, 0, 32, "Chor.EP2"),
+    (5, 0, 33, "DX Hard"),
+    (5, 0, 34, "DXLegend"),
+    (5, 0, 40, "DX Phase"),
+    (5, 0, 41, "DX+Analg"),
+    (5, 0, 42, "DXKotoEP"),
+    (5, 0, 45, "VX El.P2"),
+    (6, 0, 0, "Harpsi."),
+    (6, 0, 1, "Harpsi.K"),
+    (6, 0, 25, "Harpsi.2"),
+    (6, 0, 35, "Harpsi.3"),
+    (7, 0, 0, "Clavi."),
+    (7, 0, 1, "Clavi. K"),
+    (7, 0, 27, "ClaviWah"),
+    (7, 0, 64, "PulseClv"),
+    (7, 0, 65, "PierceCl"),
+    (8, 0, 0, "Celesta"),
+    (9, 0, 0, "Glocken"),
+    (10, 0, 0, "MusicBox"),
+    (10, 0, 64, "Orgel"),
+    (11, 0, 0, "Vibes"),
+    (11, 0, 1, "VibesK"),
+    (11, 0, 45, "HardVibe"),
+    (12, 0, 0, "Marimba"),
+    (12, 0, 1, "MarimbaK"),
+    (12, 0, 64, "SineMrmb"),
+    (12, 0, 96, "Balimba"),
+    (12, 0, 97, "Log Drum"),
+    (13, 0, 0, "Xylophon"),
+    (14, 0, 0, "TubulBel"),
+    (14, 0, 96, "ChrchBel"),
+    (14, 0, 97, "Carillon"),
+    (15, 0, 0, "Dulcimer"),
+    (15, 0, 35, "Dulcimr2"),
+    (15, 0, 96, "Cimbalom"),
+    (15, 0, 97, "Santur"),
+    (16, 0, 0, "DrawOrgn"),
+    (16, 0, 32, "DetDrwOr"),
+    (16, 0, 33, "60sDrOr1"),
+    (16, 0, 34, "60sDrOr2"),
+    (16, 0, 35, "70sDrOr1"),
+    (16, 0, 36, "DrawOrg2"),
+    (16, 0, 37, "60sDrOr3"),
+    (16, 0, 38, "EvenBar"),
+    (16, 0, 40, "16+2\"2/3"),
+    (16, 0, 64, "Organ Ba"),
+    (16, 0, 65, "70sDrOr2"),
+    (16, 0, 66, "CheezOrg"),
+    (16, 0, 67, "DrawOrg3"),
+    (17, 0, 0, "PercOrgn"),
+    (17, 0, 24, "70sPcOr1"),
+    (17, 0, 32, "DetPrcOr"),
+    (17, 0, 33, "LiteOrg"),
+    (17, 0, 37, "PercOrg2"),
+    (18, 0, 0, "RockOrgn"),
+    (18, 0, 64, "RotaryOr"),
+    (18, 0, 65, "SloRotar"),
+    (18, 0, 66, "FstRotar"),
+    (19, 0, 0, "ChrchOrg"),
+    (19, 0, 32, "ChurOrg3"),
+    (19, 0, 35, "ChurOrg2"),
+    (19, 0, 40, "NotreDam"),
+    (19, 0, 64, "OrgFlute"),
+    (19, 0, 65, "TrmOrgFl"),
+    (20, 0, 0, "ReedOrgn"),
+    (20, 0, 40, "PuffOrgn"),
+    (21, 0, 0, "Acordion"),
+    (21, 0, 32, "AccordIt"),
+    (22, 0, 0, "Harmnica"),
+    (22, 0, 32, "Harmo 2"),
+    (23, 0, 0, "TangoAcd"),
+    (23, 0, 64, "TngoAcd2"),
+    (24, 0, 0, "NylonGtr"),
+    (24, 0, 16, "NylonGt2"),
+    (24, 0, 25, "NylonGt3"),
+    (24, 0, 43, "VelGtHrm"),
+    (24, 0, 96, "Ukulele"),
+    (25, 0, 0, "SteelGtr"),
+    (25, 0, 16, "SteelGt2"),
+    (25, 0, 35, "12StrGtr"),
+    (25, 0, 40, "Nyln&Stl"),
+    (25, 0, 41, "Stl&Body"),
+    (25, 0, 96, "Mandolin"),
+    (26, 0, 0, "Jazz Gtr"),
+    (26, 0, 18, "MelloGtr"),
+    (26, 0, 32, "JazzAmp"),
+    (27, 0, 0, "CleanGtr"),
+    (27, 0, 32, "ChorusGt"),
+    (28, 0, 0, "Mute.Gtr"),
+    (28, 0, 40, "FunkGtr1"),
+    (28, 0, 41, "MuteStlG"),
+    (28, 0, 43, "FunkGtr2"),
+    (28, 0, 45, "Jazz Man"),
+    (29, 0, 0, "Ovrdrive"),
+    (29, 0, 43, "Gt.Pinch"),
+    (30, 0, 0, "Dist.Gtr"),
+    (30, 0, 40, "FeedbkGt"),
+    (30, 0, 41, "FeedbGt2"),
+    (31, 0, 0, "GtrHarmo"),
+    (31, 0, 65, "GtFeedbk"),
+    (31, 0, 66, "GtrHrmo2"),
+    (32, 0, 0, "Aco.Bass"),
+    (32, 0, 40, "JazzRthm"),
+    (32, 0, 45, "VXUprght"),
+    (33, 0, 0, "FngrBass"),
+    (33, 0, 18, "FingrDrk"),
+    (33, 0, 27, "FlangeBa"),
+    (33, 0, 40, "Ba&DstEG"),
+    (33, 0, 43, "FngrSlap"),
+    (33, 0, 45, "FngBass2"),
+    (33, 0, 65, "ModAlem"),
+    (34, 0, 0, "PickBass"),
+    (34, 0, 28, "MutePkBa"),
+    (35, 0, 0, "Fretless"),
+    (35, 0, 32, "Fretles2"),
+    (35, 0, 33, "Fretles3"),
+    (35, 0, 34, "Fretles4"),
+    (35, 0, 96, "SynFretl"),
+    (35, 0, 97, "Smooth"),
+    (36, 0, 0, "SlapBas1"),
+    (36, 0, 27, "ResoSlap"),
+    (36, 0, 32, "PunchThm"),
+    (37, 0, 0, "SlapBas2"),
+    (37, 0, 43, "VeloSlap"),
+    (38, 0, 0, "SynBass1"),
+    (38, 0, 18, "SynBa1Dk"),
+    (38, 0, 20, "FastResB"),
+    (38, 0, 24, "AcidBass"),
+    (38, 0, 35, "Clv Bass"),
+    (38, 0, 40, "TeknoBa"),
+    (38, 0, 64, "Oscar"),
+    (38, 0, 65, "SqrBass"),
+    (38, 0, 66, "RubberBa"),
+    (38, 0, 96, "Hammer"),
+    (39, 0, 0, "SynBass2"),
+    (39, 0, 6, "MelloSB1"),
+    (39, 0, 12, "Seq Bass"),
+    (39, 0, 18, "ClkSynBa"),
+    (39, 0, 19, "SynBa2Dk"),
+    (39, 0, 32, "SmthBa 2"),
+    (39, 0, 40, "ModulrBa"),
+    (39, 0, 41, "DX Bass"),
+    (39, 0, 64, "X WireBa"),
+    (40, 0, 0, "Violin"),
+    (40, 0, 8, "SlowVln"),
+    (41, 0, 0, "Viola"),
+    (42, 0, 0, "Cello"),
+    (43, 0, 0, "Contrabs"),
+    (44, 0, 0, "Trem.Str"),
+    (44, 0, 8, "SlowTrStr"),
+    (44, 0, 40, "Susp Str"),
+    (45, 0, 0, "Pizz.Str"),
+    (46, 0, 0, "Harp"),
+    (46, 0, 40, "YangChin"),
+    (47, 0, 0, "Timpani"),
+    (48, 0, 0, "Strings1"),
+    (48, 0, 3, "S.Strngs"),
+    (48, 0, 8, "SlowStr"),
+    (48, 0, 24, "ArcoStr"),
+    (48, 0, 35, "60sStrng"),
+    (48, 0, 40, "Orchestr"),
+    (48, 0, 41, "Orchstr2"),
+    (48, 0, 42, "TremOrch"),
+    (48, 0, 45, "VeloStr"),
+    (49, 0, 0, "Strings2"),
+    (49, 0, 3, "S.SlwStr"),
+    (49, 0, 8, "LegatoSt"),
+    (49, 0, 40, "Warm Str"),
+    (49, 0, 41, "Kingdom"),
+    (49, 0, 64, "70s Str"),
+    (49, 0, 65, "StrEns3"),
+    (50, 0, 0, "Syn.Str1"),
+    (50, 0, 27, "ResoStr"),
+    (50, 0, 64, "SynStr4"),
+    (50, 0, 65, "SS Str"),
+    (51, 0, 0, "Syn.Str2"),
+    (52, 0, 0, "ChoirAah"),
+    (52, 0, 3, "S.Choir"),
+    (52, 0, 16, "Ch.Aahs2"),
+    (52, 0, 32, "MelChoir"),
+    (52, 0, 40, "ChoirStr"),
+    (53, 0, 0, "VoiceOoh"),
+    (54, 0, 0, "SynVoice"),
+    (54, 0, 40, "SynVox2"),
+    (54, 0, 41, "Choral"),
+    (54, 0, 64, "AnaVoice"),
+    (55, 0, 0, "Orch.Hit"),
+    (55, 0, 35, "OrchHit2"),
+    (55, 0, 64, "Impact"),
+    (56, 0, 0, "Trumpet"),
+    (56, 0, 16, "Trumpet2"),
+    (56, 0, 17, "BriteTrp"),
+    (56, 0, 32, "WarmTrp"),
+    (57, 0, 0, "Trombone"),
+    (57, 0, 18, "Trmbone2"),
+    (58, 0, 0, "Tuba"),
+    (58, 0, 16, "Tuba 2"),
+    (59, 0, 0, "Mute.Trp"),
+    (60, 0, 0, "Fr.Horn"),
+    (60, 0, 6, "FrHrSolo"),
+    (60, 0, 32, "FrHorn2"),
+    (60, 0, 37, "HornOrch"),
+    (61, 0, 0, "BrasSect"),
+    (61, 0, 35, "Tp&TbSec"),
+    (61, 0, 40, "BrssSec2"),
+    (61, 0, 41, "HiBrass"),
+    (61, 0, 42, "MelloBrs"),
+    (62, 0, 0, "SynBras1"),
+    (62, 0, 12, "QuackBr"),
+    (62, 0, 20, "RezSynBr"),
+    (62, 0, 24, "PolyBrss"),
+    (62, 0, 27, "SynBras3"),
+    (62, 0, 32, "JumpBrss"),
+    (62, 0, 45, "AnaVelBr"),
+    (62, 0, 64, "AnaBrss1"),
+    (63, 0, 0, "SynBras2"),
+    (63, 0, 18, "Soft Brs"),
+    (63, 0, 40, "SynBras4"),
+    (63, 0, 41, "ChorBrss"),
+    (63, 0, 45, "VelBrss2"),
+    (63, 0, 64, "AnaBrss2"),
+    (64, 0, 0, "SprnoSax"),
+    (65, 0, 0, "Alto Sax"),
+    (65, 0, 40, "Sax Sect"),
+    (65, 0, 43, "HyprAlto"),
+    (66, 0, 0, "TenorSax"),
+    (66, 0, 40, "BrthTnSx"),
+    (66, 0, 41, "SoftTenr"),
+    (66, 0, 64, "TnrSax2"),
+    (67, 0, 0, "Bari.Sax"),
+    (68, 0, 0, "Oboe"),
+    (69, 0, 0, "Eng.Horn"),
+    (70, 0, 0, "Bassoon"),
+    (71, 0, 0, "Clarinet"),
+    (72, 0, 0, "Piccolo"),
+    (73, 0, 0, "Flute"),
+    (74, 0, 0, "Recorder"),
+    (75, 0, 0, "PanFlute"),
+    (76, 0, 0, "Bottle"),
+    (77, 0, 0, "Shakhchi"),
+    (78, 0, 0, "Whistle"),
+    (79, 0, 0, "Ocarina"),
+    (80, 0, 0, "SquareLd"),
+    (80, 0, 6, "Square 2"),
+    (80, 0, 8, "LMSquare"),
+    (80, 0, 18, "Hollow"),
+    (80, 0, 19, "Shmoog"),
+    (80, 0, 64, "Mellow"),
+    (80, 0, 65, "SoloSine"),
+    (80, 0, 66, "SineLead"),
+    (81, 0, 0, "Saw.Lead"),
+    (81, 0, 6, "Saw 2"),
+    (81, 0, 8, "ThickSaw"),
+    (81, 0, 18, "DynaSaw"),
+    (81, 0, 19, "DigiSaw"),
+    (81, 0, 20, "Big Lead"),
+    (81, 0, 24, "HeavySyn"),
+    (81, 0, 25, "WaspySyn"),
+    (81, 0, 40, "PulseSaw"),
+    (81, 0, 41, "Dr. Lead"),
+    (81, 0, 45, "VeloLead"),
+    (81, 0, 96, "Seq Ana"),
+    (82, 0, 0, "CaliopLd"),
+    (82, 0, 65, "Pure Pad"),
+    (83, 0, 0, "Chiff Ld"),
+    (83, 0, 64, "Rubby"),
+    (84, 0, 0, "CharanLd"),
+    (84, 0, 64, "DistLead"),
+    (84, 0, 65, "WireLead"),
+    (85, 0, 0, "Voice Ld"),
+    (85, 0, 24, "SynthAah"),
+    (85, 0, 64, "VoxLead"),
+    (86, 0, 0, "Fifth Ld"),
+    (86, 0, 35, "Big Five"),
+    (87, 0, 0, "Bass &Ld"),
+    (87, 0, 16, "Big&Low"),
+    (87, 0, 64, "Fat&Prky"),
+    (87, 0, 65, "SoftWurl"),
+    (88, 0, 0, "NewAgePd"),
+    (88, 0, 64, "Fantasy2"),
+    (89, 0, 0, "Warm Pad"),
+    (89, 0, 16, "ThickPad"),
+    (89, 0, 17, "Soft Pad"),
+    (89, 0, 18, "SinePad"),
+    (89, 0, 64, "Horn Pad"),
+    (89, 0, 65, "RotarStr"),
+    (90, 0, 0, "PolySyPd"),
+    (90, 0, 64, "PolyPd80"),
+    (90, 0, 65, "ClickPad"),
+    (90, 0, 66, "Ana Pad"),
+    (90, 0, 67, "SquarPad"),
+    (91, 0, 0, "ChoirPad"),
+    (91, 0, 64, "Heaven2"),
+    (91, 0, 66, "Itopia"),
+    (91, 0, 67, "CC Pad"),
+    (92, 0, 0, "BowedPad"),
+    (92, 0, 64, "Glacier"),
+    (92, 0, 65, "GlassPad"),
+    (93, 0, 0, "MetalPad"),
+    (93, 0, 64, "Tine Pad"),
+    (93, 0, 65, "Pan Pad"),
+    (94, 0, 0, "Halo Pad"),
+    (95, 0, 0, "SweepPad"),
+    (95, 0, 20, "Shwimmer"),
+    (95, 0, 27, "Converge"),
+    (95, 0, 64, "PolarPad"),
+    (95, 0, 66, "Celstial"),
+    (96, 0, 0, "Rain"),
+    (96, 0, 45, "ClaviPad"),
+    (96, 0, 64, "HrmoRain"),
+    (96, 0, 65, "AfrcnWnd"),
+    (96, 0, 66, "Carib"),
+    (97, 0, 0, "SoundTrk"),
+    (97, 0, 27, "Prologue"),
+    (97, 0, 64, "Ancestrl"),
+    (98, 0, 0, "Crystal"),
+    (98, 0, 12, "SynDrCmp"),
+    (98, 0, 14, "Popcorn"),
+    (98, 0, 18, "TinyBell"),
+    (98, 0, 35, "RndGlock"),
+    (98, 0, 40, "GlockChi"),
+    (98, 0, 41, "ClearBel"),
+    (98, 0, 42, "ChorBell"),
+    (98, 0, 64, "SynMalet"),
+    (98, 0, 65, "SftCryst"),
+    (98, 0, 66, "LoudGlok"),
+    (98, 0, 67, "XmasBell"),
+    (98, 0, 68, "VibeBell"),
+    (98, 0, 69, "DigiBell"),
+    (98, 0, 70, "AirBells"),
+    (98, 0, 71, "BellHarp"),
+    (98, 0, 72, "Gamelmba"),
+    (99, 0, 0, "Atmosphr"),
+    (99, 0, 18, "WarmAtms"),
+    (99, 0, 19, "HollwRls"),
+    (99, 0, 40, "NylonEP"),
+    (99, 0, 64, "NylnHarp"),
+    (99, 0, 65, "Harp Vox"),
+    (99, 0, 66, "AtmosPad"),
+    (99, 0, 67, "Planet"),
+    (100, 0, 0, "Bright"),
+    (100, 0, 64, "FantaBel"),
+    (100, 0, 96, "Smokey"),
+    (101, 0, 0, "Goblins"),
+    (101, 0, 64, "GobSyn"),
+    (101, 0, 65, "50sSciFi"),
+    (101, 0, 66, "Ring Pad"),
+    (101, 0, 67, "Ritual"),
+    (101, 0, 68, "ToHeaven"),
+    (101, 0, 70, "Night"),
+    (101, 0, 71, "Glisten"),
+    (101, 0, 96, "BelChoir"),
+    (102, 0, 0, "Echoes"),
+    (102, 0, 8, "EchoPad2"),
+    (102, 0, 14, "Echo Pan"),
+    (102, 0, 64, "EchoBell"),
+    (102, 0, 65, "Big Pan"),
+    (102, 0, 66, "SynPiano"),
+    (102, 0, 67, "Creation"),
+    (102, 0, 68, "Stardust"),
+    (102, 0, 69, "Reso&Pan"),
+    (103, 0, 0, "Sci-Fi"),
+    (103, 0, 64, "Starz"),
+    (104, 0, 0, "Sitar"),
+    (104, 0, 32, "DetSitar"),
+    (104, 0, 35, "Sitar 2"),
+    (104, 0, 96, "Tambra"),
+    (104, 0, 97, "Tamboura"),
+    (105, 0, 0, "Banjo"),
+    (105, 0, 28, "MuteBnjo"),
+    (105, 0, 96, "Rabab"),
+    (105, 0, 97, "Gopichnt"),
+    (105, 0, 98, "Oud"),
+    (106, 0, 0, "Shamisen"),
+    (107, 0, 0, "Koto"),
+    (107, 0, 96, "Taisho-k"),
+    (107, 0, 97, "Kanoon"),
+    (108, 0, 0, "Kalimba"),
+    (109, 0, 0, "Bagpipe"),
+    (110, 0, 0, "Fiddle"),
+    (111, 0, 0, "Shanai"),
+    (111, 0, 64, "Shanai2"),
+    (111, 0, 96, "Pungi"),
+    (111, 0, 97, "Hichriki"),
+    (112, 0, 0, "TnklBell"),
+    (112, 0, 96, "Bonang"),
+    (112, 0, 97, "Altair"),
+    (112, 0, 98, "Gamelan"),
+    (112, 0, 99, "S.Gamlan"),
+    (112, 0, 100, "Rama Cym"),
+    (113, 0, 0, "Agogo"),
+    (114, 0, 0, "SteelDrm"),
+    (114, 0, 97, "GlasPerc"),
+    (114, 0, 98, "ThaiBell"),
+    (115, 0, 0, "WoodBlok"),
+    (115, 0, 96, "Castanet"),
+    (116, 0, 0, "TaikoDrm"),
+    (116, 0, 96, "Gr.Cassa"),
+    (117, 0, 0, "MelodTom"),
+    (117, 0, 64, "Mel Tom2"),
+    (117, 0, 65, "Real Tom"),
+    (117, 0, 66, "Rock Tom"),
+    (118, 0, 0, "Syn.Drum"),
+    (118, 0, 64, "Ana Tom"),
+    (118, 0, 65, "ElecPerc"),
+    (119, 0, 0, "RevCymbl"),
+    (120, 0, 0, "FretNoiz"),
+    (121, 0, 0, "BrthNoiz"),
+    (122, 0, 0, "Seashore"),
+    (123, 0, 0, "Tweet"),
+    (124, 0, 0, "Telphone"),
+    (125, 0, 0, "Helicptr"),
+    (126, 0, 0, "Applause"),
+    (127, 0, 0, "Gunshot"),
+    (0, 64, 0, "CuttngNz"),
+    (1, 64, 0, "CttngNz2"),
+    (3, 64, 0, "Str Slap"),
+    (16, 64, 0, "Fl.KClik"),
+    (32, 64, 0, "Rain"),
+    (33, 64, 0, "Thunder"),
+    (34, 64, 0, "Wind"),
+    (35, 64, 0, "Stream"),
+    (36, 64, 0, "Bubble"),
+    (37, 64, 0, "Feed"),
+    (48, 64, 0, "Dog"),
+    (49, 64, 0, "Horse"),
+    (50, 64, 0, "Bird 2"),
+    (54, 64, 0, "Ghost"),
+    (55, 64, 0, "Maou"),
+    (64, 64, 0, "Tel.Dial"),
+    (65, 64, 0, "DoorSqek"),
+    (66, 64, 0, "DoorSlam"),
+    (67, 64, 0, "Scratch"),
+    (68, 64, 0, "Scratch2"),
+    (69, 64, 0, "WindChm"),
+    (70, 64, 0, "Telphon2"),
+    (80, 64, 0, "CarEngin"),
+    (81, 64, 0, "Car Stop"),
+    (82, 64, 0, "Car Pass"),
+    (83, 64, 0, "CarCrash"),
+    (84, 64, 0, "Siren"),
+    (85, 64, 0, "Train"),
+    (86, 64, 0, "Jetplane"),
+    (87, 64, 0, "Starship"),
+    (88, 64, 0, "Burst"),
+    (89, 64, 0, "Coaster"),
+    (90, 64, 0, "SbMarine"),
+    (96, 64, 0, "Laughing"),
+    (97, 64, 0, "Scream"),
+    (98, 64, 0, "Punch"),
+    (99, 64, 0, "Heart"),
+    (100, 64, 0, "FootStep"),
+    (112, 64, 0, "MchinGun"),
+    (113, 64, 0, "LaserGun"),
+    (114, 64, 0, "Xplosion"),
+    (115, 64, 0, "FireWork"),
+];
+
+// (program, bank select LSB, name) for General MIDI Level 2 variations (bank MSB 0x79).
+const GM2_VARIATIONS: &[(u8, u8, &str)] = &[
+    (0, 1, "Acoustic Grand Piano (wide)"),
+    (0, 2, "Acoustic Grand Piano (dark)"),
+    (1, 1, "Bright Acoustic Piano (wide)"),
+    (2, 1, "Electric Grand Piano (wide)"),
+    (3, 1, "Honky-tonk Piano (wide)"),
+    (4, 1, "Detuned Electric Piano 1"),
+    (4, 2, "Electric Piano 1 (velocity mix)"),
+    (4, 3, "60's Electric Piano"),
+    (5, 1, "Detuned Electric Piano 2"),
+    (5, 2, "Electric Piano 2 (velocity mix)"),
+    (5, 3, "EP Legend"),
+    (5, 4, "EP Phase"),
+    (6, 1, "Harpsichord (octave mix)"),
+    (6, 2, "Harpsichord (wide)"),
+    (6, 3, "Harpsichord (with key off)"),
+    (7, 1, "Pulse Clavi"),
+    (11, 1, "Vibraphone (wide)"),
+    (12, 1, "Marimba (wide)"),
+    (14, 1, "Church Bell"),
+    (14, 2, "Carillon"),
+    (16, 1, "Detuned Drawbar Organ"),
+    (16, 2, "Italian 60's Organ"),
+    (16, 3, "Drawbar Organ 2"),
+    (17, 1, "Detuned Percussive Organ"),
+    (17, 2, "Percussive Organ 2"),
+    (19, 1, "Church Organ (octave mix)"),
+    (19, 2, "Detuned Church Organ"),
+    (20, 1, "Puff Organ"),
+    (21, 1, "Accordion 2"),
+    (24, 1, "Ukulele"),
+    (24, 2, "Acoustic Guitar (nylon + key off)"),
+    (24, 3, "Acoustic Guitar (nylon 2)"),
+    (25, 1, "12-Strings Guitar"),
+    (25, 2, "Mandolin"),
+    (25, 3, "Steel Guitar with Body Sound"),
+    (26, 1, "Electric Guitar (pedal steel)"),
+    (27, 1, "Electric Guitar (detuned clean)"),
+    (27, 2, "Mid Tone Guitar"),
+    (28, 1, "Electric Guitar (funky cutting)"),
+    (28, 2, "Electric Guitar (muted velo-sw)"),
+    (28, 3, "Jazz Man"),
+    (29, 1, "Guitar Pinch"),
+    (30, 1, "Distortion Guitar (with feedback)"),
+    (30, 2, "Distorted Rhythm Guitar"),
+    (31, 1, "Guitar Feedback"),
+    (33, 1, "Finger Slap Bass"),
+    (38, 1, "Synth Bass (warm)"),
+    (38, 2, "Synth Bass 3 (resonance)"),
+    (38, 3, "Clavi Bass"),
+    (38, 4, "Hammer"),
+    (39, 1, "Synth Bass 4 (attack)"),
+    (39, 2, "Synth Bass (rubber)"),
+    (39, 3, "Attack Pulse"),
+    (40, 1, "Violin (slow attack)"),
+    (46, 1, "Yang Chin"),
+    (48, 1, "Strings and Brass"),
+    (48, 2, "60s Strings"),
+    (50, 1, "Synth Strings 3"),
+    (52, 1, "Choir Aahs 2"),
+    (53, 1, "Humming"),
+    (54, 1, "Analog Voice"),
+    (55, 1, "Bass Hit Plus"),
+    (55, 2, "6th Hit"),
+    (55, 3, "Euro Hit"),
+    (56, 1, "Dark Trumpet Soft"),
+    (57, 1, "Trombone 2"),
+    (57, 2, "Bright Trombone"),
+    (59, 1, "Muted Trumpet 2"),
+    (60, 1, "French Horn 2 (warm)"),
+    (61, 1, "Brass Section 2 (octave mix)"),
+    (62, 1, "Synth Brass 3"),
+    (62, 2, "Analog Synth Brass 1"),
+    (62, 3, "Jump Brass"),
+    (63, 1, "Synth Brass 4"),
+    (63, 2, "Analog Synth Brass 2"),
+    (80, 1, "Lead 1a (square 2)"),
+    (80, 2, "Lead 1b (sine)"),
+    (81, 1, "Lead 2a (sawtooth 2)"),
+    (81, 2, "Lead 2b (saw + pulse)"),
+    (81, 3, "Lead 2c (double sawtooth)"),
+    (81, 4, "Lead 2d (sequenced analog)"),
+    (84, 1, "Lead 5a (wire lead)"),
+    (87, 1, "Lead 8a (soft wrl)"),
+    (89, 1, "Pad 2a (sine pad)"),
+    (91, 1, "Pad 4a (itopia)"),
+    (98, 1, "FX 3a (synth mallet)"),
+    (102, 1, "FX 7a (echo bell)"),
+    (102, 2, "FX 7b (echo pan)"),
+    (104, 1, "Sitar 2 (bend)"),
+    (107, 1, "Taisho Koto"),
+    (115, 1, "Castanets"),
+    (116, 1, "Concert Bass Drum"),
+    (117, 1, "Melodic Tom 2"),
+    (118, 1, "Rhythm Box Tom"),
+    (118, 2, "Electric Drum"),
+    (120, 1, "Guitar Cutting Noise"),
+    (120, 2, "Acoustic Bass String Slap"),
+    (121, 1, "Flute Key Click"),
+    (122, 1, "Rain"),
+    (122, 2, "Thunder"),
+    (122, 3, "Wind"),
+    (122, 4, "Stream"),
+    (122, 5, "Bubble"),
+    (123, 1, "Dog"),
+    (123, 2, "Horse Gallop"),
+    (123, 3, "Bird Tweet 2"),
+    (124, 1, "Telephone Ring 2"),
+    (124, 2, "Door Creaking"),
+    (124, 3, "Door"),
+    (124, 4, "Scratch"),
+    (124, 5, "Wind Chime"),
+    (125, 1, "Car Engine"),
+    (125, 2, "Car Stop"),
+    (125, 3, "Car Pass"),
+    (125, 4, "Car Crash"),
+    (125, 5, "Siren"),
+    (125, 6, "Train"),
+    (125, 7, "Jetplane"),
+    (125, 8, "Starship"),
+    (125, 9, "Burst Noise"),
+    (126, 1, "Laughing"),
+    (126, 2, "Screaming"),
+    (126, 3, "Punch"),
+    (126, 4, "Heart Beat"),
+    (126, 5, "Footsteps"),
+    (127, 1, "Machine Gun"),
+    (127, 2, "Lasergun"),
+    (127, 3, "Explosion"),
+];
+
+pub fn instrument_name(program: u8) -> &'static str {
+    INSTRUMENTS[(program & 0x7f) as usize]
+}
+
+pub fn instrument_family(program: u8) -> &'static str {
+    FAMILIES[((program & 0x7f) >> 3) as usize]
+}
+
+pub fn percussion_name(note: u8) -> Option<&'static str> {
+    let index = note.checked_sub(PERCUSSION_FIRST_NOTE)?;
+    PERCUSSION.get(index as usize).copied()
+}
+
+pub fn is_gm1_percussion(note: u8) -> bool {
+    GM1_PERCUSSION.contains(&note)
+}
+
+pub fn drum_kit_name(program: u8) -> Option<&'static str> {
+    DRUM_KITS
+        .iter()
+        .find(|(kit, _)| *kit == program)
+        .map(|(_, name)| *name)
+}
+
+pub fn controller_name(controller: u8) -> Option<&'static str> {
+    CONTROLLERS
+        .iter()
+        .find(|(number, _)| *number == controller)
+        .map(|(_, name)| *name)
+}
+
+fn find_variation(table: &[(u8, u8, &'static str)], program: u8, bank: u8) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(variation_program, variation_bank, _)| {
+            *variation_program == program && *variation_bank == bank
+        })
+        .map(|(_, _, name)| *name)
+}
+
+pub fn gs_variation_name(program: u8, bank_msb: u8) -> Option<&'static str> {
+    find_variation(GS_VARIATIONS, program, bank_msb)
+}
+
+pub fn xg_variation_name(program: u8, bank_msb: u8, bank_lsb: u8) -> Option<&'static str> {
+    XG_VOICES
+        .iter()
+        .find(|(voice_program, voice_msb, voice_lsb, _)| {
+            (*voice_program, *voice_msb, *voice_lsb) == (program, bank_msb, bank_lsb)
+        })
+        .map(|(_, _, _, name)| *name)
+}
+
+pub fn gm2_variation_name(program: u8, bank_lsb: u8) -> Option<&'static str> {
+    if bank_lsb == 0 {
+        return Some(instrument_name(program));
+    }
+    find_variation(GM2_VARIATIONS, program, bank_lsb)
+}
+
+impl ChannelVoiceMessage {
+    pub fn instrument_name(&self) -> Option<&'static str> {
+        match self {
+            Self::ProgramChange {
+                channel: PERCUSSION_CHANNEL,
+                program,
+            } => drum_kit_name(*program),
+            Self::ProgramChange { program, .. } => Some(instrument_name(*program)),
+            _ => None,
+        }
+    }
+
+    pub fn instrument_family(&self) -> Option<&'static str> {
+        match self {
+            Self::ProgramChange {
+                channel: PERCUSSION_CHANNEL,
+                ..
+            } => None,
+            Self::ProgramChange { program, .. } => Some(instrument_family(*program)),
+            _ => None,
+        }
+    }
+
+    pub fn controller_name(&self) -> Option<&'static str> {
+        match self {
+            Self::ControlChange { controller, .. } => controller_name(*controller),
+            _ => None,
+        }
+    }
+
+    pub fn percussion_name(&self) -> Option<&'static str> {
+        match self {
+            Self::NoteOn {
+                channel: PERCUSSION_CHANNEL,
+                note,
+                ..
+            }
+            | Self::NoteOff {
+                channel: PERCUSSION_CHANNEL,
+                note,
+                ..
+            } => percussion_name(*note),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::midi_event::ChannelVoiceMessage;
+
+    use super::{gm2_variation_name, gs_variation_name, percussion_name, xg_variation_name};
+
+    #[test]
+    fn test_names() {
+        let message = ChannelVoiceMessage::ProgramChange {
+            channel: 0,
+            program: 0,
+        };
+        assert_eq!(message.instrument_name(), Some("Acoustic Grand Piano"));
+        assert_eq!(message.instrument_family(), Some("Piano"));
+        let message = ChannelVoiceMessage::ProgramChange {
+            channel: 0,
+            program: 127,
+        };
+        assert_eq!(message.instrument_name(), Some("Gunshot"));
+        assert_eq!(message.instrument_family(), Some("Sound Effects"));
+        let message = ChannelVoiceMessage::ControlChange {
+            channel: 0,
+            controller: 64,
+            value: 127,
+        };
+        assert_eq!(message.controller_name(), Some("Sustain Pedal"));
+        let message = ChannelVoiceMessage::NoteOn {
+            channel: 9,
+            note: 38,
+            velocity: 100,
+        };
+        assert_eq!(message.percussion_name(), Some("Acoustic Snare"));
+        assert_eq!(percussion_name(35), Some("Acoustic Bass Drum"));
+        assert_eq!(percussion_name(81), Some("Open Triangle"));
+        assert_eq!(percussion_name(87), Some("Open Surdo"));
+        assert_eq!(percussion_name(88), None);
+        assert_eq!(gs_variation_name(25, 8), Some("12-str.Gt"));
+        assert_eq!(gs_variation_name(0, 0), Some("Piano 1"));
+        assert_eq!(gs_variation_name(127, 3), Some("Explosion"));
+        assert_eq!(gs_variation_name(48, 127), Some("Str Sect 1"));
+        assert_eq!(gs_variation_name(25, 9), None);
+        assert_eq!(xg_variation_name(0, 0, 0), Some("GrandPno"));
+        assert_eq!(xg_variation_name(5, 0, 34), Some("DXLegend"));
+        assert_eq!(xg_variation_name(98, 0, 72), Some("Gamelmba"));
+        assert_eq!(xg_variation_name(0, 64, 0), Some("CuttngNz"));
+        assert_eq!(xg_variation_name(114, 64, 0), Some("Xplosion"));
+        assert_eq!(xg_variation_name(5, 0, 2), None);
+        assert_eq!(gm2_variation_name(0, 0), Some("Acoustic Grand Piano"));
+        assert_eq!(gm2_variation_name(80, 2), Some("Lead 1b (sine)"));
+        assert_eq!(gm2_variation_name(125, 1), Some("Car Engine"));
+        assert_eq!(gm2_variation_name(125, 9), Some("Burst Noise"));
+        assert_eq!(gm2_variation_name(125, 10), None);
+        assert_eq!(gm2_variation_name(127, 3), Some("Explosion"));
+    }
+}
@@ -4,7 +4,8 @@ use nom::{
     IResult,
 };
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Division {
     TicksPerQuarterNote { ticks: u16 },
     FramesPerSecond { frames: u8, ticks: u8 },
@@ -25,14 +26,15 @@ impl Division {
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Division::TicksPerQuarterNote { ticks } => *ticks,
-            Division::FramesPerSecond { frames, ticks } => (frames << 8) as u16 | *ticks as u16,
+            Division::FramesPerSecond { frames, ticks } => ((*frames as u16) << 8) | *ticks as u16,
         }
         .to_be_bytes()
         .to_vec()
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HeaderData {
     pub format: u16,
     pub ntrks: u16,
@@ -65,7 +67,8 @@ impl HeaderData {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HeaderChunk {
     pub chunk_type: String,
     pub length: u32,
@@ -73,6 +76,19 @@ pub struct HeaderChunk {
 }
 
 impl HeaderChunk {
+    pub fn new(format: u16, ntrks: u16, division: Division) -> Self {
+        assert!(format <= 2, "Invalid format: {}", format);
+        Self {
+            chunk_type: "MThd".to_string(),
+            length: 6,
+            data: HeaderData {
+                format,
+                ntrks,
+                division,
+            },
+        }
+    }
+
     pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
         let (input, chunk_type) = tag("MThd")(input)?;
         let (input, length) = be_u32(input)?;
@@ -89,11 +105,43 @@ impl HeaderChunk {
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
+        // As with tracks, the written length always matches the data
+        let data = self.data.to_bytes();
         [
             self.chunk_type.as_bytes(),
-            &self.length.to_be_bytes(),
-            &self.data.to_bytes(),
+            &(data.len() as u32).to_be_bytes(),
+            &data,
         ]
         .concat()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Division;
+
+    #[test]
+    fn test_division() {
+        let divisions = [
+            (Division::TicksPerQuarterNote { ticks: 96 }, [0x00, 0x60]),
+            (
+                Division::FramesPerSecond {
+                    frames: -25i8 as u8,
+                    ticks: 40,
+                },
+                [0xe7, 0x28],
+            ),
+            (
+                Division::FramesPerSecond {
+                    frames: -29i8 as u8,
+                    ticks: 80,
+                },
+                [0xe3, 0x50],
+            ),
+        ];
+        for (division, bytes) in divisions {
+            assert_eq!(division.to_bytes(), bytes);
+            assert_eq!(Division::parse(&bytes), Ok((&[][..], division)));
+        }
+    }
+}
@@ -1,8 +1,28 @@
+pub mod channel;
+pub mod channel_state;
+pub mod controller;
+pub mod disassembly;
+pub mod edit;
 pub mod event;
+pub mod fingerprint;
+pub mod general_midi;
 pub mod header;
+pub mod lyrics;
 pub mod midi_file;
+pub mod midicsv;
+pub mod notation;
+pub mod note;
+mod numpy;
+pub mod piano_roll;
+pub mod scala;
+pub mod scan;
+pub mod slice;
+pub mod sysex;
 #[cfg(test)]
 mod test;
+pub mod timing;
+pub mod tokenize;
 pub mod track;
+pub mod ump;
 mod utils;
 pub mod variable_length_quantity;
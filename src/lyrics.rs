@@ -0,0 +1,346 @@
+use crate::{
+    event::{meta_event::MetaEvent, Event},
+    midi_file::MidiFile,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LyricsFormat {
+    // Text events with @ header tags and / or \ line breaks, as in .kar files
+    SoftKaraoke,
+    // Lyric events with carriage return and line feed breaks
+    Lyric,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Syllable {
+    pub tick: u64,
+    pub seconds: f64,
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LyricLine {
+    pub paragraph: bool,
+    pub start: f64,
+    pub end: f64,
+    pub syllables: Vec<Syllable>,
+}
+
+impl LyricLine {
+    pub fn text(&self) -> String {
+        self.syllables
+            .iter()
+            .map(|syllable| syllable.text.as_str())
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Lyrics {
+    pub format: LyricsFormat,
+    pub tags: Vec<(char, String)>,
+    pub lines: Vec<LyricLine>,
+}
+
+// Returns the break in front of the text, true for a new paragraph
+fn leading_break(text: &str, line: char, paragraph: char) -> (Option<bool>, &str) {
+    let rest = text.trim_start_matches([line, paragraph]);
+    let marks = &text[..text.len() - rest.len()];
+    ((!marks.is_empty()).then(|| marks.contains(paragraph)), rest)
+}
+
+fn trailing_break(text: &str) -> (&str, Option<bool>) {
+    let rest = text.trim_end_matches(['\r', '\n']);
+    let marks = &text[rest.len()..];
+    (rest, (!marks.is_empty()).then(|| marks.contains('\n')))
+}
+
+fn lrc_time(seconds: f64) -> String {
+    let centiseconds = (seconds * 100.0).round() as u64;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centiseconds / 6000,
+        centiseconds / 100 % 60,
+        centiseconds % 100
+    )
+}
+
+fn webvtt_time(seconds: f64) -> String {
+    let milliseconds = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        milliseconds / 3600000,
+        milliseconds / 60000 % 60,
+        milliseconds / 1000 % 60,
+        milliseconds % 1000
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl Lyrics {
+    fn tag(&self, name: char, index: usize) -> Option<&str> {
+        self.tags
+            .iter()
+            .filter(|(tag, _)| *tag == name)
+            .nth(index)
+            .map(|(_, value)| value.trim())
+    }
+
+    pub fn to_lrc(&self) -> String {
+        let mut output = String::new();
+        // Soft Karaoke gives the title first and the artist second
+        if let Some(title) = self.tag('T', 0) {
+            output.push_str(&format!("[ti:{}]\n", title));
+        }
+        if let Some(artist) = self.tag('T', 1) {
+            output.push_str(&format!("[ar:{}]\n", artist));
+        }
+        for line in self.lines.iter() {
+            output.push_str(&format!("[{}]{}\n", lrc_time(line.start), line.text()));
+        }
+        output
+    }
+
+    // Syllables after the first get inline timestamps for karaoke style highlighting
+    pub fn to_webvtt(&self) -> String {
+        let mut cues = vec!["WEBVTT".to_string()];
+        for (index, line) in self.lines.iter().enumerate() {
+            let mut text = String::new();
+            for (position, syllable) in line.syllables.iter().enumerate() {
+                let syllable_text = match position {
+                    0 => syllable.text.trim_start(),
+                    _ => {
+                        text.push_str(&format!("<{}>", webvtt_time(syllable.seconds)));
+                        syllable.text.as_str()
+                    }
+                };
+                text.push_str(&escape(syllable_text));
+            }
+            cues.push(format!(
+                "{}\n{} --> {}\n{}",
+                index + 1,
+                webvtt_time(line.start),
+                webvtt_time(line.end),
+                text.trim_end()
+            ));
+        }
+        cues.join("\n\n") + "\n"
+    }
+}
+
+impl MidiFile {
+    pub fn lyrics(&self) -> Lyrics {
+        let mut tags = Vec::new();
+        let mut text_events = Vec::new();
+        let mut lyric_events = Vec::new();
+        for track in self.tracks.iter() {
+            let mut texts = Vec::new();
+            let mut lyrics = Vec::new();
+            for (tick, event) in track.to_absolute() {
+                match event {
+                    Event::MetaEvent(MetaEvent::TextEvent { text, .. }) => {
+                        match text.strip_prefix('@') {
+                            Some(tag) => {
+                                if let Some(name) = tag.chars().next() {
+                                    tags.push((name, tag[name.len_utf8()..].to_string()));
+                                }
+                            }
+                            None => texts.push((tick, text)),
+                        }
+                    }
+                    Event::MetaEvent(MetaEvent::Lyric { text, .. }) => lyrics.push((tick, text)),
+                    _ => {}
+                }
+            }
+            text_events.push(texts);
+            lyric_events.push(lyrics);
+        }
+
+        let karaoke_breaks = text_events
+            .iter()
+            .flatten()
+            .any(|(_, text)| text.starts_with(['/', '\\']));
+        let format = match tags.iter().any(|(name, _)| *name == 'K')
+            || (lyric_events.iter().all(|events| events.is_empty()) && karaoke_breaks)
+        {
+            true => LyricsFormat::SoftKaraoke,
+            false => LyricsFormat::Lyric,
+        };
+        // The words live in a single track, other text is usually credits or markers
+        let events = match format {
+            LyricsFormat::SoftKaraoke => text_events,
+            LyricsFormat::Lyric => lyric_events,
+        }
+        .into_iter()
+        .max_by_key(|events| events.len())
+        .unwrap_or_default();
+
+        let tempo_map = self.tempo_map();
+        let mut lines = Vec::new();
+        let mut syllables = Vec::new();
+        let mut paragraph = true;
+        let mut line_break = |syllables: &mut Vec<Syllable>, new_paragraph: bool| {
+            if !syllables.is_empty() {
+                lines.push((paragraph, std::mem::take(syllables)));
+                paragraph = false;
+            }
+            paragraph |= new_paragraph;
+        };
+        for (tick, text) in events {
+            let (before, text, after) = match format {
+                LyricsFormat::SoftKaraoke => {
+                    let (before, text) = leading_break(&text, '/', '\\');
+                    (before, text, None)
+                }
+                LyricsFormat::Lyric => {
+                    let (before, text) = leading_break(&text, '\r', '\n');
+                    let (text, after) = trailing_break(text);
+                    (before, text, after)
+                }
+            };
+            if let Some(new_paragraph) = before {
+                line_break(&mut syllables, new_paragraph);
+            }
+            if !text.is_empty() {
+                syllables.push(Syllable {
+                    tick,
+                    seconds: tempo_map.tick_to_seconds(tick),
+                    text: text.to_string(),
+                });
+            }
+            if let Some(new_paragraph) = after {
+                line_break(&mut syllables, new_paragraph);
+            }
+        }
+        line_break(&mut syllables, false);
+
+        let end_tick = self
+            .tracks
+            .iter()
+            .map(|track| track.end_tick())
+            .max()
+            .unwrap_or(0);
+        let end = tempo_map.tick_to_seconds(end_tick);
+        let starts = lines
+            .iter()
+            .map(|(_, syllables)| syllables[0].seconds)
+            .collect::<Vec<_>>();
+        let lines = lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, (paragraph, syllables))| LyricLine {
+                paragraph,
+                start: starts[index],
+                end: starts
+                    .get(index + 1)
+                    .copied()
+                    .unwrap_or(end)
+                    .max(starts[index]),
+                syllables,
+            })
+            .collect();
+        Lyrics {
+            format,
+            tags,
+            lines,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, Event},
+        header::Division,
+        midi_file::MidiFile,
+        track::TrackChunk,
+    };
+
+    use super::LyricsFormat;
+
+    #[test]
+    fn test_soft_karaoke() {
+        let text = |tick, text| (tick, Event::MetaEvent(MetaEvent::text(0x01, text)));
+        let conductor = TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 500000 })),
+            text(0, "Sequenced by somebody"),
+            (0, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let words = TrackChunk::from_absolute(vec![
+            text(0, "@KMIDI KARAOKE FILE"),
+            text(0, "@TSong"),
+            text(0, "@TArtist"),
+            text(192, "\\Hel"),
+            text(288, "lo"),
+            text(384, " world & more"),
+            text(576, "/Sec"),
+            text(672, "ond"),
+            (960, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let midi_file = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![conductor, words],
+        );
+        let lyrics = midi_file.lyrics();
+        assert_eq!(lyrics.format, LyricsFormat::SoftKaraoke);
+        assert_eq!(lyrics.lines.len(), 2);
+        assert_eq!(lyrics.lines[0].text(), "Hello world & more");
+        assert!(lyrics.lines[0].paragraph && !lyrics.lines[1].paragraph);
+        assert_eq!(lyrics.lines[1].syllables[1].seconds, 3.5);
+        assert_eq!(
+            lyrics.to_lrc(),
+            "[ti:Song]\n\
+             [ar:Artist]\n\
+             [00:01.00]Hello world & more\n\
+             [00:03.00]Second\n"
+        );
+        assert_eq!(
+            lyrics.to_webvtt(),
+            "WEBVTT\n\
+             \n\
+             1\n\
+             00:00:01.000 --> 00:00:03.000\n\
+             Hel<00:00:01.500>lo<00:00:02.000> world &amp; more\n\
+             \n\
+             2\n\
+             00:00:03.000 --> 00:00:05.000\n\
+             Sec<00:00:03.500>ond\n"
+        );
+    }
+
+    #[test]
+    fn test_lyric_events() {
+        let lyric = |tick, text| (tick, Event::MetaEvent(MetaEvent::text(0x05, text)));
+        let track = TrackChunk::from_absolute(vec![
+            lyric(0, "One "),
+            lyric(96, "two\r"),
+            lyric(192, "three\n"),
+            lyric(288, "four"),
+            (384, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let midi_file = MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 96 }, vec![track]);
+        let lyrics = midi_file.lyrics();
+        assert_eq!(lyrics.format, LyricsFormat::Lyric);
+        let lines = lyrics
+            .lines
+            .iter()
+            .map(|line| (line.paragraph, line.text(), line.start, line.end))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                (true, "One two".to_string(), 0.0, 1.0),
+                (false, "three".to_string(), 1.0, 1.5),
+                (true, "four".to_string(), 1.5, 2.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,604 @@
+use encoding_rs::WINDOWS_1252;
+
+use crate::{
+    disassembly::SyntaxError,
+    event::{
+        meta_event::MetaEvent,
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        sysex_event::SysExEvent,
+        Event,
+    },
+    header::Division,
+    midi_file::MidiFile,
+    track::TrackChunk,
+    ump::translate::midi1_message,
+    variable_length_quantity::VariableLengthQuantity,
+};
+
+const TEXT_RECORDS: [(u8, &str); 7] = [
+    (0x01, "Text_t"),
+    (0x02, "Copyright_t"),
+    (0x03, "Title_t"),
+    (0x04, "Instrument_name_t"),
+    (0x05, "Lyric_t"),
+    (0x06, "Marker_t"),
+    (0x07, "Cue_point_t"),
+];
+const MIDI_PORT: u8 = 0x21;
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for byte in WINDOWS_1252.encode(text).0.iter() {
+        match byte {
+            b'"' => quoted.push_str("\"\""),
+            b'\\' => quoted.push_str("\\\\"),
+            0x20..=0x7e => quoted.push(*byte as char),
+            _ => quoted.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn unquote(field: &str) -> Option<String> {
+    let field = field.trim();
+    let inner = field.strip_prefix('"')?.strip_suffix('"')?.as_bytes();
+    let mut bytes = Vec::new();
+    let mut index = 0;
+    while index < inner.len() {
+        match inner[index] {
+            b'"' => {
+                if inner.get(index + 1) != Some(&b'"') {
+                    return None;
+                }
+                bytes.push(b'"');
+                index += 2;
+            }
+            b'\\' if inner.get(index + 1) == Some(&b'\\') => {
+                bytes.push(b'\\');
+                index += 2;
+            }
+            b'\\' => {
+                let octal = std::str::from_utf8(inner.get(index + 1..index + 4)?).ok()?;
+                bytes.push(u8::from_str_radix(octal, 8).ok()?);
+                index += 4;
+            }
+            byte => {
+                bytes.push(byte);
+                index += 1;
+            }
+        }
+    }
+    let (text, _, replacement_used) = WINDOWS_1252.decode(&bytes);
+    if replacement_used {
+        return None;
+    }
+    Some(text.into_owned())
+}
+
+fn data_fields(length: usize, data: &[u8]) -> String {
+    let mut fields = length.to_string();
+    for byte in data {
+        fields.push_str(&format!(", {}", byte));
+    }
+    fields
+}
+
+fn event_record(event: &Event) -> Option<String> {
+    let record = match event {
+        Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelVoiceMessage(
+            message,
+        ))) => match *message {
+            ChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => format!("Note_off_c, {}, {}, {}", channel, note, velocity),
+            ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => format!("Note_on_c, {}, {}, {}", channel, note, velocity),
+            ChannelVoiceMessage::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            } => format!("Poly_aftertouch_c, {}, {}, {}", channel, note, pressure),
+            ChannelVoiceMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => format!("Control_c, {}, {}, {}", channel, controller, value),
+            ChannelVoiceMessage::ProgramChange { channel, program } => {
+                format!("Program_c, {}, {}", channel, program)
+            }
+            ChannelVoiceMessage::ChannelPressure { channel, pressure } => {
+                format!("Channel_aftertouch_c, {}, {}", channel, pressure)
+            }
+            ChannelVoiceMessage::PitchBendChange { channel, value } => {
+                format!("Pitch_bend_c, {}, {}", channel, value)
+            }
+        },
+        Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelModeMessage(
+            message,
+        ))) => {
+            let bytes = message.to_bytes();
+            format!(
+                "Control_c, {}, {}, {}",
+                message.get_channel(),
+                bytes[1],
+                bytes[2]
+            )
+        }
+        Event::MidiEvent(MidiMessage::SystemMessage(_)) => return None,
+        Event::SysExEvent(event) => {
+            let data = event.get_data();
+            match event.get_status() {
+                0xf0 => format!("System_exclusive, {}", data_fields(data.len(), data)),
+                _ => format!("System_exclusive_packet, {}", data_fields(data.len(), data)),
+            }
+        }
+        Event::MetaEvent(event) => match event {
+            MetaEvent::SequenceNumber { number } => format!("Sequence_number, {}", number),
+            MetaEvent::TextEvent { text, .. }
+            | MetaEvent::CopyrightNotice { text, .. }
+            | MetaEvent::SequenceOrTrackName { text, .. }
+            | MetaEvent::InstrumentName { text, .. }
+            | MetaEvent::Lyric { text, .. }
+            | MetaEvent::Marker { text, .. }
+            | MetaEvent::CuePoint { text, .. } => {
+                let meta_type = event.to_bytes()[1];
+                let (_, name) = TEXT_RECORDS
+                    .iter()
+                    .find(|(record_type, _)| *record_type == meta_type)
+                    .unwrap();
+                format!("{}, {}", name, quote(text))
+            }
+            MetaEvent::MidiChannelPrefix { channel } => format!("Channel_prefix, {}", channel),
+            MetaEvent::EndOfTrack => "End_track".to_string(),
+            MetaEvent::SetTempo { tempo } => format!("Tempo, {}", tempo),
+            MetaEvent::SmpteOffset {
+                hour,
+                minute,
+                second,
+                frame,
+                subframe,
+            } => format!(
+                "SMPTE_offset, {}, {}, {}, {}, {}",
+                hour, minute, second, frame, subframe
+            ),
+            MetaEvent::TimeSignature {
+                numerator,
+                denominator,
+                clocks_per_metronome_click,
+                thirty_seconds_per_quarter_note,
+            } => format!(
+                "Time_signature, {}, {}, {}, {}",
+                numerator, denominator, clocks_per_metronome_click, thirty_seconds_per_quarter_note
+            ),
+            MetaEvent::KeySignature { key, scale } => format!(
+                "Key_signature, {}, {}",
+                *key as i8,
+                match scale {
+                    0 => "\"major\"",
+                    _ => "\"minor\"",
+                }
+            ),
+            MetaEvent::SequencerSpecificEvent { data, .. } => {
+                format!("Sequencer_specific, {}", data_fields(data.len(), data))
+            }
+            MetaEvent::UnknownMetaEvent {
+                meta_type: MIDI_PORT,
+                data,
+                ..
+            } if data.len() == 1 => format!("MIDI_port, {}", data[0]),
+            MetaEvent::UnknownMetaEvent {
+                meta_type, data, ..
+            } => format!(
+                "Unknown_meta_event, {}, {}",
+                meta_type,
+                data_fields(data.len(), data)
+            ),
+        },
+    };
+    Some(record)
+}
+
+fn numbers<'a, T: std::str::FromStr>(fields: &[&'a str]) -> Result<Vec<T>, &'a str> {
+    fields
+        .iter()
+        .map(|field| field.trim().parse().map_err(|_| field.trim()))
+        .collect()
+}
+
+// Length-prefixed byte lists, as used by System_exclusive and the meta data records
+fn byte_data<'a>(record: &'a str, fields: &[&'a str]) -> Result<Vec<u8>, &'a str> {
+    let (length, data) = fields.split_first().ok_or(record)?;
+    let data = numbers::<u8>(data)?;
+    match length.trim().parse::<usize>() {
+        Ok(length) if length == data.len() => Ok(data),
+        _ => Err(length.trim()),
+    }
+}
+
+fn data_event(meta_type: u8, data: Vec<u8>) -> MetaEvent {
+    let length = VariableLengthQuantity {
+        value: data.len() as u32,
+    };
+    match meta_type {
+        0x7f => MetaEvent::SequencerSpecificEvent { length, data },
+        _ => MetaEvent::UnknownMetaEvent {
+            meta_type,
+            length,
+            data,
+        },
+    }
+}
+
+fn channel_event<'a>(record: &'a str, status: u8, fields: &[&'a str]) -> Result<Event, &'a str> {
+    let values = numbers::<u16>(fields)?;
+    let (channel, data) = values.split_first().ok_or(record)?;
+    if *channel > 0x0f {
+        return Err(fields[0].trim());
+    }
+    let status = status | *channel as u8;
+    let (count, limit) = match status >> 4 {
+        0xe => (1, 0x3fff),
+        0xc | 0xd => (1, 0x7f),
+        _ => (2, 0x7f),
+    };
+    if data.len() != count {
+        return Err(fields.get(count + 1).map_or(record, |field| field.trim()));
+    }
+    if let Some(index) = data.iter().position(|value| *value > limit) {
+        return Err(fields[index + 1].trim());
+    }
+    let (data1, data2) = match data {
+        [value] if status >> 4 == 0xe => ((value & 0x7f) as u8, (value >> 7) as u8),
+        [value] => (*value as u8, 0),
+        [data1, data2] => (*data1 as u8, *data2 as u8),
+        _ => return Err(record),
+    };
+    midi1_message(status, data1, data2)
+        .map(Event::MidiEvent)
+        .ok_or(record)
+}
+
+// Errors carry the field that could not be read
+fn parse_record<'a>(record: &'a str, fields: &[&'a str], rest: &'a str) -> Result<Event, &'a str> {
+    let event = match record {
+        "Note_off_c" => channel_event(record, 0x80, fields)?,
+        "Note_on_c" => channel_event(record, 0x90, fields)?,
+        "Poly_aftertouch_c" => channel_event(record, 0xa0, fields)?,
+        "Control_c" => channel_event(record, 0xb0, fields)?,
+        "Program_c" => channel_event(record, 0xc0, fields)?,
+        "Channel_aftertouch_c" => channel_event(record, 0xd0, fields)?,
+        "Pitch_bend_c" => channel_event(record, 0xe0, fields)?,
+        "System_exclusive" => {
+            Event::SysExEvent(SysExEvent::try_new(0xf0, byte_data(record, fields)?).ok_or(record)?)
+        }
+        "System_exclusive_packet" => {
+            Event::SysExEvent(SysExEvent::new(0xf7, byte_data(record, fields)?))
+        }
+        "Sequence_number" => match numbers::<u16>(fields)?[..] {
+            [number] => Event::MetaEvent(MetaEvent::SequenceNumber { number }),
+            _ => return Err(record),
+        },
+        "Channel_prefix" => match numbers::<u8>(fields)?[..] {
+            [channel] if channel < 16 => Event::MetaEvent(MetaEvent::MidiChannelPrefix { channel }),
+            [_] => return Err(fields[0].trim()),
+            _ => return Err(record),
+        },
+        "MIDI_port" => match numbers::<u8>(fields)?[..] {
+            [port] => Event::MetaEvent(data_event(MIDI_PORT, vec![port])),
+            _ => return Err(record),
+        },
+        "End_track" => Event::MetaEvent(MetaEvent::EndOfTrack),
+        "Tempo" => match numbers::<u32>(fields)?[..] {
+            [tempo] if tempo <= 0xffffff => Event::MetaEvent(MetaEvent::SetTempo { tempo }),
+            [_] => return Err(fields[0].trim()),
+            _ => return Err(record),
+        },
+        "SMPTE_offset" => match numbers::<u8>(fields)?[..] {
+            [hour, minute, second, frame, subframe] => Event::MetaEvent(MetaEvent::SmpteOffset {
+                hour,
+                minute,
+                second,
+                frame,
+                subframe,
+            }),
+            _ => return Err(record),
+        },
+        "Time_signature" => match numbers::<u8>(fields)?[..] {
+            [numerator, denominator, clocks_per_metronome_click, thirty_seconds_per_quarter_note] => {
+                Event::MetaEvent(MetaEvent::TimeSignature {
+                    numerator,
+                    denominator,
+                    clocks_per_metronome_click,
+                    thirty_seconds_per_quarter_note,
+                })
+            }
+            _ => return Err(record),
+        },
+        "Key_signature" => match fields {
+            [key, scale] => Event::MetaEvent(MetaEvent::KeySignature {
+                key: key.trim().parse::<i8>().map_err(|_| key.trim())? as u8,
+                scale: match scale.trim().trim_matches('"') {
+                    "major" => 0,
+                    "minor" => 1,
+                    _ => return Err(scale.trim()),
+                },
+            }),
+            _ => return Err(record),
+        },
+        "Sequencer_specific" => Event::MetaEvent(data_event(0x7f, byte_data(record, fields)?)),
+        "Unknown_meta_event" => {
+            let (meta_type, data) = fields.split_first().ok_or(record)?;
+            let meta_type = meta_type
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|meta_type| *meta_type <= 0x7f)
+                .ok_or(meta_type.trim())?;
+            Event::MetaEvent(data_event(meta_type, byte_data(record, data)?))
+        }
+        record => {
+            let (meta_type, _) = TEXT_RECORDS
+                .iter()
+                .find(|(_, name)| *name == record)
+                .ok_or(record)?;
+            Event::MetaEvent(MetaEvent::text(
+                *meta_type,
+                &unquote(rest).ok_or(rest.trim())?,
+            ))
+        }
+    };
+    Ok(event)
+}
+
+impl MidiFile {
+    // None when a track holds system messages, which midicsv has no records for
+    pub fn to_csv(&self) -> Option<String> {
+        let division = self.header.data.division.to_bytes();
+        let mut lines = vec![format!(
+            "0, 0, Header, {}, {}, {}",
+            self.header.data.format,
+            self.tracks.len(),
+            u16::from_be_bytes([division[0], division[1]])
+        )];
+        for (index, track) in self.tracks.iter().enumerate() {
+            let track_number = index + 1;
+            lines.push(format!("{}, 0, Start_track", track_number));
+            for (tick, event) in track.to_absolute() {
+                lines.push(format!(
+                    "{}, {}, {}",
+                    track_number,
+                    tick,
+                    event_record(&event)?
+                ));
+            }
+        }
+        lines.push("0, 0, End_of_file".to_string());
+        Some(lines.join("\n") + "\n")
+    }
+
+    pub fn from_csv(input: &str) -> Result<Self, SyntaxError> {
+        let mut header = None;
+        let mut tracks = Vec::new();
+        for (index, line) in input.lines().enumerate() {
+            let error = |token| SyntaxError::new(index + 1, token);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let mut columns = line.splitn(4, ',').map(|column| column.trim());
+            let (track_field, tick_field, record) =
+                match (columns.next(), columns.next(), columns.next()) {
+                    (Some(track), Some(tick), Some(record)) => (track, tick, record),
+                    _ => return Err(error(line)),
+                };
+            let track = track_field
+                .parse::<usize>()
+                .map_err(|_| error(track_field))?;
+            let tick = tick_field.parse::<u64>().map_err(|_| error(tick_field))?;
+            let rest = columns.next().unwrap_or("");
+            let fields = match rest {
+                "" => Vec::new(),
+                rest => rest.split(',').collect::<Vec<_>>(),
+            };
+            match record {
+                "Header" => match numbers::<i32>(&fields).map_err(error)?[..] {
+                    [format, _, division] if (0..=2).contains(&format) => {
+                        let (_, division) = Division::parse(&(division as u16).to_be_bytes())
+                            .map_err(|_| error(fields[2].trim()))?;
+                        header = Some((format as u16, division));
+                    }
+                    [_, _, _] => return Err(error(fields[0].trim())),
+                    _ => return Err(error(record)),
+                },
+                "Start_track" => {
+                    if track != tracks.len() + 1 {
+                        return Err(error(track_field));
+                    }
+                    tracks.push((index + 1, record, Vec::new()));
+                }
+                "End_of_file" => break,
+                record => {
+                    let (last_line, last_record, events) = track
+                        .checked_sub(1)
+                        .and_then(|track| tracks.get_mut(track))
+                        .ok_or(error(track_field))?;
+                    let last_tick = events.last().map_or(0, |(last, _)| *last);
+                    if tick
+                        .checked_sub(last_tick)
+                        .is_none_or(|delta| delta > 0x0fff_ffff)
+                    {
+                        return Err(error(tick_field));
+                    }
+                    events.push((tick, parse_record(record, &fields, rest).map_err(error)?));
+                    (*last_line, *last_record) = (index + 1, record);
+                }
+            }
+        }
+        let (format, division) = header.ok_or(SyntaxError::new(1, ""))?;
+        // A track that does not end with End_track could not be parsed again
+        let tracks = tracks
+            .into_iter()
+            .map(|(line, record, events)| match events.last() {
+                Some((_, Event::MetaEvent(MetaEvent::EndOfTrack))) => {
+                    Ok(TrackChunk::from_absolute(events))
+                }
+                _ => Err(SyntaxError::new(line, record)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::new(format, division, tracks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        disassembly::SyntaxError,
+        event::{
+            meta_event::MetaEvent,
+            midi_event::{
+                ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiMessage, ModeMessage,
+                SystemMessage, SystemRealTimeMessage,
+            },
+            sysex_event::SysExEvent,
+            Event,
+        },
+        header::Division,
+        midi_file::MidiFile,
+        track::TrackChunk,
+        variable_length_quantity::VariableLengthQuantity,
+    };
+
+    #[test]
+    fn test_csv() {
+        let track = TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::SequenceNumber { number: 3 })),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::text(0x03, "Caf\u{e9}, \"Le\" \\ Bar")),
+            ),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::KeySignature {
+                    key: (-3i8) as u8,
+                    scale: 1,
+                }),
+            ),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::UnknownMetaEvent {
+                    meta_type: 0x21,
+                    length: VariableLengthQuantity { value: 1 },
+                    data: vec![2],
+                }),
+            ),
+            (
+                0,
+                Event::SysExEvent(SysExEvent::from_payload(&[0x7e, 0x7f, 0x09, 0x01])),
+            ),
+            (
+                0,
+                Event::MidiEvent(MidiMessage::ChannelMessage(
+                    ChannelMessage::ChannelModeMessage(ChannelModeMessage::new(
+                        2,
+                        ModeMessage::LocalControlOff,
+                    )),
+                )),
+            ),
+            (
+                0,
+                Event::from(ChannelVoiceMessage::PitchBendChange {
+                    channel: 2,
+                    value: 0x2345,
+                }),
+            ),
+            (
+                96,
+                Event::from(ChannelVoiceMessage::NoteOn {
+                    channel: 9,
+                    note: 36,
+                    velocity: 0,
+                }),
+            ),
+            (96, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let midi_file = MidiFile::new(1, Division::TicksPerQuarterNote { ticks: 96 }, vec![track]);
+        let csv = midi_file.to_csv().unwrap();
+        assert_eq!(
+            csv,
+            "0, 0, Header, 1, 1, 96\n\
+             1, 0, Start_track\n\
+             1, 0, Sequence_number, 3\n\
+             1, 0, Title_t, \"Caf\\351, \"\"Le\"\" \\\\ Bar\"\n\
+             1, 0, Key_signature, -3, \"minor\"\n\
+             1, 0, MIDI_port, 2\n\
+             1, 0, System_exclusive, 5, 126, 127, 9, 1, 247\n\
+             1, 0, Control_c, 2, 122, 0\n\
+             1, 0, Pitch_bend_c, 2, 9029\n\
+             1, 96, Note_on_c, 9, 36, 0\n\
+             1, 96, End_track\n\
+             0, 0, End_of_file\n"
+        );
+        let parsed = MidiFile::from_csv(&csv).unwrap();
+        assert_eq!(parsed, midi_file);
+        assert_eq!(parsed.to_bytes(), midi_file.to_bytes());
+
+        assert_eq!(
+            MidiFile::from_csv("0, 0, Header, 0, 1, 96\n1, 0, Note_on_c, 0, 60, 100\n")
+                .unwrap_err(),
+            SyntaxError::new(2, "1")
+        );
+        assert_eq!(
+            MidiFile::from_csv(
+                "0, 0, Header, 0, 1, 96\n1, 0, Start_track\n1, 0, Note_on_c, 16, 60, 100\n"
+            )
+            .unwrap_err(),
+            SyntaxError::new(3, "16")
+        );
+        assert_eq!(
+            MidiFile::from_csv("0, 0, Header, 0, 1, 96\n1, 0, Start_track\n1, 0, Tempo, x\n")
+                .unwrap_err(),
+            SyntaxError::new(3, "x")
+        );
+        assert_eq!(
+            MidiFile::from_csv(
+                "0, 0, Header, 0, 1, 96\n1, 0, Start_track\n1, 300000000, End_track\n"
+            )
+            .unwrap_err(),
+            SyntaxError::new(3, "300000000")
+        );
+        assert_eq!(
+            MidiFile::from_csv(
+                "0, 0, Header, 1, 2, 96\n1, 0, Start_track\n1, 0, Tempo, 500000\n\
+                 2, 0, Start_track\n2, 0, End_track\n0, 0, End_of_file\n"
+            )
+            .unwrap_err(),
+            SyntaxError::new(3, "Tempo")
+        );
+        assert_eq!(
+            MidiFile::from_csv("0, 0, Header, 0, 1, 96\n1, 0, Start_track\n0, 0, End_of_file\n")
+                .unwrap_err(),
+            SyntaxError::new(2, "Start_track")
+        );
+
+        let clock = MidiFile::new(
+            0,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![TrackChunk::from_absolute(vec![
+                (
+                    0,
+                    Event::MidiEvent(MidiMessage::SystemMessage(
+                        SystemMessage::SystemRealTimeMessage(SystemRealTimeMessage::TimingClock),
+                    )),
+                ),
+                (0, Event::MetaEvent(MetaEvent::EndOfTrack)),
+            ])],
+        );
+        assert!(clock.to_csv().is_none());
+    }
+}
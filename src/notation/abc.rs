@@ -0,0 +1,1014 @@
+use std::collections::HashMap;
+
+use crate::{
+    event::{meta_event::MetaEvent, Event},
+    header::Division,
+    midi_file::MidiFile,
+    note::Note,
+    track::TrackChunk,
+    utils::gcd,
+};
+
+use super::{tuplet_marks, Clef, Element, Key, Measure, NoteValue, Pitch, Score};
+
+// Lengths are written relative to L:1/8, twelve half units
+const UNIT_NOTE_LENGTH: u64 = 12;
+
+fn length(half_units: u64) -> String {
+    let divisor = gcd(half_units, UNIT_NOTE_LENGTH);
+    match (half_units / divisor, UNIT_NOTE_LENGTH / divisor) {
+        (1, 1) => String::new(),
+        (numerator, 1) => numerator.to_string(),
+        (1, denominator) => format!("/{}", denominator),
+        (numerator, denominator) => format!("{}/{}", numerator, denominator),
+    }
+}
+
+// Triplets are written with their untupleted length
+fn value_length(value: &NoteValue) -> String {
+    match value.triplet {
+        true => length(value.units * 3),
+        false => length(value.units * 2),
+    }
+}
+
+fn key_name(key: &Key) -> String {
+    let tonic = key.tonic();
+    let accidental = match tonic.alter {
+        1 => "#",
+        -1 => "b",
+        _ => "",
+    };
+    format!(
+        "{}{}{}",
+        tonic.step,
+        accidental,
+        if key.minor { "m" } else { "" }
+    )
+}
+
+fn note_name(pitch: &Pitch) -> String {
+    match pitch.octave {
+        octave if octave >= 5 => format!(
+            "{}{}",
+            pitch.step.to_ascii_lowercase(),
+            "'".repeat((octave - 5) as usize)
+        ),
+        octave => format!("{}{}", pitch.step, ",".repeat((4 - octave).max(0) as usize)),
+    }
+}
+
+// Accidentals hold until the end of the bar for the same step and octave
+fn accidental(pitch: &Pitch, key: &Key, bar: &mut HashMap<(char, i8), i8>) -> &'static str {
+    let current = *bar
+        .get(&(pitch.step, pitch.octave))
+        .unwrap_or(&key.alter(pitch.step));
+    if current == pitch.alter {
+        return "";
+    }
+    bar.insert((pitch.step, pitch.octave), pitch.alter);
+    match pitch.alter {
+        2 => "^^",
+        1 => "^",
+        0 => "=",
+        -1 => "_",
+        _ => "__",
+    }
+}
+
+fn voice(elements: &[Element], measure: &Measure) -> String {
+    if elements.is_empty() {
+        return format!("z{}", length(measure.length * 2));
+    }
+    let marks = tuplet_marks(elements);
+    let mut bar = HashMap::new();
+    let mut tokens = Vec::new();
+    for (index, element) in elements.iter().enumerate() {
+        let mut token = String::new();
+        if marks[index].0 {
+            let count = marks[index..].iter().position(|(_, stop)| *stop).unwrap() + 1;
+            token.push_str(&match count {
+                3 => "(3".to_string(),
+                count => format!("(3:2:{}", count),
+            });
+        }
+        let notes = element
+            .keys
+            .iter()
+            .map(|key| {
+                let pitch = Pitch::spell(*key, measure.key.fifths);
+                format!(
+                    "{}{}",
+                    accidental(&pitch, &measure.key, &mut bar),
+                    note_name(&pitch)
+                )
+            })
+            .collect::<Vec<_>>();
+        match notes.len() {
+            0 => token.push('z'),
+            1 => token.push_str(&notes[0]),
+            _ => token.push_str(&format!("[{}]", notes.concat())),
+        }
+        token.push_str(&value_length(&element.value));
+        if element.tie_start {
+            token.push('-');
+        }
+        tokens.push(token);
+    }
+    tokens.join(" ")
+}
+
+impl Score {
+    pub fn to_abc(&self, track: usize) -> Option<String> {
+        let part = &self.parts[self.track_part(track)?];
+        let first = &self.measures[0];
+        let mut lines = vec![
+            "X:1".to_string(),
+            format!(
+                "T:{}",
+                self.title.as_ref().unwrap_or(&part.name).replace('\n', " ")
+            ),
+            format!("M:{}/{}", first.time_signature.0, first.time_signature.1),
+            "L:1/8".to_string(),
+        ];
+        if let Some((0, bpm)) = first.tempos.first() {
+            lines.push(format!("Q:1/4={}", bpm.round()));
+        }
+        lines.push(format!(
+            "K:{}{}",
+            key_name(&first.key),
+            match part.clef {
+                Clef::Treble => "",
+                Clef::Bass => " clef=bass",
+                Clef::Percussion => " clef=perc",
+            }
+        ));
+
+        let mut bars = Vec::new();
+        for (index, (measure, voices)) in self.measures.iter().zip(part.measures.iter()).enumerate()
+        {
+            let mut bar = String::new();
+            if index > 0 {
+                let previous = &self.measures[index - 1];
+                if previous.time_signature != measure.time_signature {
+                    bar.push_str(&format!(
+                        "[M:{}/{}] ",
+                        measure.time_signature.0, measure.time_signature.1
+                    ));
+                }
+                if previous.key != measure.key {
+                    bar.push_str(&format!("[K:{}] ", key_name(&measure.key)));
+                }
+                if let Some((_, bpm)) = measure.tempos.first() {
+                    bar.push_str(&format!("[Q:1/4={}] ", bpm.round()));
+                }
+            }
+            let voices = voices
+                .iter()
+                .enumerate()
+                .filter(|(index, elements)| *index == 0 || !elements.is_empty())
+                .map(|(_, elements)| voice(elements, measure))
+                .collect::<Vec<_>>();
+            bar.push_str(&voices.join(" & "));
+            bars.push(bar);
+        }
+        for (index, line) in bars.chunks(4).enumerate() {
+            let last = index == (bars.len() - 1) / 4;
+            lines.push(format!(
+                "{} {}",
+                line.join(" | "),
+                if last { "|]" } else { "|" }
+            ));
+        }
+        Some(lines.join("\n") + "\n")
+    }
+}
+
+const TICKS_PER_QUARTER_NOTE: u16 = 480;
+const WHOLE_NOTE: f64 = TICKS_PER_QUARTER_NOTE as f64 * 4.0;
+const VELOCITY: u8 = 80;
+const MODES: [(&str, i8); 9] = [
+    ("maj", 0),
+    ("ion", 0),
+    ("mix", -1),
+    ("dor", -2),
+    ("aeo", -3),
+    ("min", -3),
+    ("phr", -4),
+    ("lyd", 1),
+    ("loc", -5),
+];
+
+enum Item {
+    // Keys with their tie flags, no keys is a rest
+    Notes(Vec<(u8, bool)>, f64),
+    Meta(MetaEvent),
+    Bar {
+        repeat_start: bool,
+        repeat_end: bool,
+        double: bool,
+    },
+    Ending(Vec<u32>),
+}
+
+fn digits(input: &str) -> (&str, &str) {
+    input.split_at(
+        input
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(input.len()),
+    )
+}
+
+fn fraction(value: &str) -> Option<f64> {
+    let value = match value.split_once('/') {
+        Some((numerator, denominator)) => {
+            numerator.trim().parse::<f64>().ok()? / denominator.trim().parse::<f64>().ok()?
+        }
+        None => value.trim().parse::<f64>().ok()?,
+    };
+    (value > 0.0 && value.is_finite()).then_some(value)
+}
+
+// Length multipliers such as 3, /2, // or 3/2
+fn multiplier(input: &str) -> Option<(&str, f64)> {
+    let (numerator, mut input) = digits(input);
+    let mut value = numerator.parse().unwrap_or(1.0);
+    while let Some(rest) = input.strip_prefix('/') {
+        let (denominator, rest) = digits(rest);
+        let denominator: f64 = denominator.parse().unwrap_or(2.0);
+        if denominator == 0.0 {
+            return None;
+        }
+        value /= denominator;
+        input = rest;
+    }
+    Some((input, value))
+}
+
+fn tie(input: &str) -> (&str, bool) {
+    match input.strip_prefix('-') {
+        Some(rest) => (rest, true),
+        None => (input, false),
+    }
+}
+
+fn explicit_accidental(input: &str) -> (&str, Option<i8>) {
+    for (prefix, alter) in [("^^", 2), ("__", -2), ("^", 1), ("_", -1), ("=", 0)] {
+        if let Some(rest) = input.strip_prefix(prefix) {
+            return (rest, Some(alter));
+        }
+    }
+    (input, None)
+}
+
+fn meter(value: &str) -> Option<Option<(u8, u8)>> {
+    match value.trim() {
+        "" | "none" => Some(None),
+        "C" => Some(Some((4, 4))),
+        "C|" => Some(Some((2, 2))),
+        value => {
+            let (numerator, denominator) = value.split_once('/')?;
+            // Additive meters such as (2+2+3)/8
+            let numerator = numerator
+                .trim()
+                .trim_matches(|c| c == '(' || c == ')')
+                .split('+')
+                .map(|beats| beats.trim().parse::<u32>().ok())
+                .sum::<Option<u32>>()?;
+            let numerator = u8::try_from(numerator).ok()?;
+            let denominator = denominator.trim().parse::<u8>().ok()?;
+            (numerator > 0 && denominator.is_power_of_two())
+                .then_some(Some((numerator, denominator)))
+        }
+    }
+}
+
+fn time_signature((numerator, denominator): (u8, u8)) -> MetaEvent {
+    let beat = match numerator > 3 && numerator.is_multiple_of(3) {
+        true => 3,
+        false => 1,
+    };
+    MetaEvent::TimeSignature {
+        numerator,
+        denominator: denominator.trailing_zeros() as u8,
+        clocks_per_metronome_click: (96 * beat / denominator as u32).clamp(1, 255) as u8,
+        thirty_seconds_per_quarter_note: 8,
+    }
+}
+
+fn tempo(value: &str, unit: f64) -> Option<u32> {
+    // Quoted text such as "Allegro" only describes the tempo
+    let value = value.split('"').step_by(2).collect::<String>();
+    let (beats, bpm) = value.split_once('=').unwrap_or(("", &value));
+    let bpm = bpm.trim().parse::<f64>().ok()?;
+    let beat = match beats.trim() {
+        // A bare number or the obsolete Q:C=120 counts unit note lengths
+        "" => unit,
+        beats if beats.starts_with(|c: char| c.is_ascii_alphabetic()) => unit,
+        beats => beats
+            .split_whitespace()
+            .map(fraction)
+            .sum::<Option<f64>>()?,
+    };
+    let tempo = (60_000_000.0 / (bpm * beat * 4.0)).round();
+    (bpm > 0.0 && (1.0..=0xffffff as f64).contains(&tempo)).then_some(tempo as u32)
+}
+
+fn mode(name: &str) -> Option<i8> {
+    let name = name.to_ascii_lowercase();
+    if name == "m" {
+        return Some(-3);
+    }
+    MODES
+        .iter()
+        .find(|(mode, _)| name.get(..3) == Some(mode))
+        .map(|(_, offset)| *offset)
+}
+
+fn key(value: &str) -> Option<(Key, HashMap<char, i8>)> {
+    // Clef and other name=value settings are not part of the key
+    let mut tokens = value
+        .split_whitespace()
+        .filter(|token| !token.contains('=') || token.starts_with('='))
+        .peekable();
+    let mut fifths = 0;
+    let mut minor = false;
+    if let Some(token) = tokens.next_if(|token| token.starts_with(|c| ('A'..='G').contains(&c))) {
+        fifths = "FCGDAEB".find(&token[..1]).unwrap() as i8 - 1;
+        let mut rest = &token[1..];
+        if let Some(sharp) = rest.strip_prefix('#') {
+            fifths += 7;
+            rest = sharp;
+        } else if let Some(flat) = rest.strip_prefix('b') {
+            fifths -= 7;
+            rest = flat;
+        }
+        let offset = match rest {
+            "" => tokens
+                .next_if(|token| mode(token).is_some())
+                .map_or(0, |token| mode(token).unwrap()),
+            rest => mode(rest)?,
+        };
+        fifths += offset;
+        minor = offset == -3;
+    } else {
+        tokens.next_if(|token| ["none", "HP", "Hp"].contains(token));
+    }
+    if !(-7..=7).contains(&fifths) {
+        return None;
+    }
+    let key = Key { fifths, minor };
+    let mut alters = "CDEFGAB"
+        .chars()
+        .map(|step| (step, key.alter(step)))
+        .collect::<HashMap<_, _>>();
+    for token in tokens {
+        if token == "exp" {
+            alters.values_mut().for_each(|alter| *alter = 0);
+        }
+        if let (rest, Some(alter)) = explicit_accidental(token) {
+            let step = rest.chars().next()?.to_ascii_uppercase();
+            if !alters.contains_key(&step) {
+                return None;
+            }
+            alters.insert(step, alter);
+        }
+    }
+    Some((key, alters))
+}
+
+#[derive(Default)]
+struct Tune {
+    title: Option<String>,
+    unit: Option<f64>,
+    meter: Option<(u8, u8)>,
+    alters: HashMap<char, i8>,
+    bar: HashMap<(char, i8), i8>,
+    tuplet: Option<(u32, f64)>,
+    broken: Option<f64>,
+    items: Vec<Item>,
+}
+
+impl Tune {
+    // Without an L: field the unit note length follows the meter
+    fn unit(&self) -> f64 {
+        self.unit.unwrap_or(match self.meter {
+            Some((numerator, denominator)) if (numerator as f64) < 0.75 * denominator as f64 => {
+                1.0 / 16.0
+            }
+            _ => 1.0 / 8.0,
+        })
+    }
+
+    fn field(&mut self, name: char, value: &str) -> Option<()> {
+        match name {
+            'T' if self.title.is_none() => {
+                let title = value.trim().to_string();
+                self.items.push(Item::Meta(MetaEvent::text(0x03, &title)));
+                self.title = Some(title);
+            }
+            'M' => {
+                self.meter = meter(value)?;
+                if let Some(meter) = self.meter {
+                    self.items.push(Item::Meta(time_signature(meter)));
+                }
+            }
+            'L' => self.unit = Some(fraction(value)?),
+            // Tempo fields that are only text are left out
+            'Q' => {
+                if let Some(tempo) = tempo(value, self.unit()) {
+                    self.items.push(Item::Meta(MetaEvent::SetTempo { tempo }));
+                }
+            }
+            'K' => {
+                let (key, alters) = key(value)?;
+                self.alters = alters;
+                self.bar.clear();
+                self.items.push(Item::Meta(MetaEvent::KeySignature {
+                    key: key.fifths as u8,
+                    scale: key.minor as u8,
+                }));
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn notes(&mut self, keys: Vec<(u8, bool)>, length: f64) {
+        let mut duration = length * WHOLE_NOTE;
+        if let Some(factor) = self.broken.take() {
+            duration *= factor;
+        }
+        if let Some((remaining, factor)) = &mut self.tuplet {
+            duration *= *factor;
+            *remaining -= 1;
+            if *remaining == 0 {
+                self.tuplet = None;
+            }
+        }
+        self.items.push(Item::Notes(keys, duration));
+    }
+
+    // Accidentals hold until the end of the bar for the same step and octave
+    fn pitch<'a>(&mut self, input: &'a str) -> Option<(&'a str, u8)> {
+        let (input, explicit) = explicit_accidental(input);
+        let letter = input
+            .chars()
+            .next()
+            .filter(|c| "ABCDEFGabcdefg".contains(*c))?;
+        let step = letter.to_ascii_uppercase();
+        let mut octave = if letter.is_ascii_lowercase() { 5 } else { 4 };
+        let mut input = &input[1..];
+        loop {
+            match input.chars().next() {
+                Some('\'') => octave += 1,
+                Some(',') => octave -= 1,
+                _ => break,
+            }
+            input = &input[1..];
+        }
+        let alter = match explicit {
+            Some(alter) => {
+                self.bar.insert((step, octave), alter);
+                alter
+            }
+            None => *self.bar.get(&(step, octave)).unwrap_or(&self.alters[&step]),
+        };
+        let semitone = [0, 2, 4, 5, 7, 9, 11]["CDEFGAB".find(step).unwrap()];
+        let key = (octave as i32 + 1) * 12 + semitone + alter as i32;
+        Some((input, u8::try_from(key).ok().filter(|key| *key < 128)?))
+    }
+
+    fn chord<'a>(&mut self, input: &'a str) -> Option<&'a str> {
+        let mut input = input[1..].trim_start();
+        let mut keys = Vec::new();
+        let mut length = None;
+        while !input.starts_with(']') {
+            // Decorations of single notes in the chord do not sound either
+            if let Some(c) = input.chars().next().filter(|c| "\"!+.~".contains(*c)) {
+                input = match c {
+                    '.' | '~' => &input[1..],
+                    _ => skip_marked(input, c),
+                }
+                .trim_start();
+                continue;
+            }
+            let (rest, key) = self.pitch(input)?;
+            let (rest, multiplier) = multiplier(rest)?;
+            let (rest, tie) = tie(rest);
+            // The first note gives the length of the chord
+            length.get_or_insert(multiplier);
+            keys.push((key, tie));
+            input = rest.trim_start();
+        }
+        let (input, multiplier) = multiplier(&input[1..])?;
+        let (input, tie) = tie(input);
+        for (_, tied) in keys.iter_mut() {
+            *tied |= tie;
+        }
+        self.notes(keys, length.unwrap_or(1.0) * multiplier * self.unit());
+        Some(input)
+    }
+
+    fn bar<'a>(&mut self, input: &'a str) -> &'a str {
+        let bytes = input.as_bytes();
+        let mut end = 0;
+        while let Some(byte) = bytes.get(end) {
+            match byte {
+                b'|' | b':' => {}
+                b'[' if end == 0 && bytes.get(1) == Some(&b'|') => {}
+                b']' if end > 0 && bytes[end - 1] == b'|' => {}
+                _ => break,
+            }
+            end += 1;
+        }
+        let token = &input[..end];
+        self.bar.clear();
+        self.items.push(Item::Bar {
+            repeat_start: token.ends_with(':'),
+            repeat_end: token.starts_with(':'),
+            double: token.matches('|').count() > 1 || token.contains(['[', ']']),
+        });
+        let input = &input[end..];
+        match input.starts_with(|c: char| c.is_ascii_digit()) {
+            true => self.ending(input),
+            false => input,
+        }
+    }
+
+    // Ending numbers such as 1, 2 or 1,3 or 1-3
+    fn ending<'a>(&mut self, input: &'a str) -> &'a str {
+        let end = input
+            .find(|c: char| !c.is_ascii_digit() && c != ',' && c != '-')
+            .unwrap_or(input.len());
+        let mut numbers = Vec::new();
+        for part in input[..end].split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    numbers.extend(first.parse().unwrap_or(0)..=last.parse().unwrap_or(0))
+                }
+                None => numbers.extend(part.parse::<u32>()),
+            }
+        }
+        self.items.push(Item::Ending(numbers));
+        &input[end..]
+    }
+
+    fn tuplet<'a>(&mut self, input: &'a str) -> &'a str {
+        let (count, mut input) = digits(input);
+        let count = count.parse::<u32>().unwrap_or(0);
+        let mut values = [None, None];
+        for value in values.iter_mut() {
+            match input.strip_prefix(':') {
+                Some(rest) => {
+                    let (number, rest) = digits(rest);
+                    *value = number.parse::<u32>().ok();
+                    input = rest;
+                }
+                None => break,
+            }
+        }
+        let compound = self
+            .meter
+            .is_some_and(|(numerator, _)| numerator > 3 && numerator.is_multiple_of(3));
+        let time = values[0].unwrap_or(match count {
+            2 | 4 | 8 => 3,
+            3 | 6 => 2,
+            _ if compound => 3,
+            _ => 2,
+        });
+        let notes = values[1].unwrap_or(count);
+        if count > 0 && notes > 0 {
+            self.tuplet = Some((notes, time as f64 / count as f64));
+        }
+        input
+    }
+
+    // Dotted pairs such as a>b, with >> and >>> for double and triple dots
+    fn broken_rhythm(&mut self, count: usize, lengthen: bool) {
+        let short = 0.5f64.powi(count as i32);
+        let (previous, next) = match lengthen {
+            true => (2.0 - short, short),
+            false => (short, 2.0 - short),
+        };
+        if let Some(Item::Notes(_, duration)) = self
+            .items
+            .iter_mut()
+            .rev()
+            .find(|item| matches!(item, Item::Notes(..)))
+        {
+            *duration *= previous;
+        }
+        self.broken = Some(next);
+    }
+
+    fn body(&mut self, line: &str) -> Option<()> {
+        let mut input = line;
+        while let Some(c) = input.chars().next() {
+            input = match c {
+                '^' | '_' | '=' | 'A'..='G' | 'a'..='g' => {
+                    let (rest, key) = self.pitch(input)?;
+                    let (rest, length) = multiplier(rest)?;
+                    let (rest, tie) = tie(rest);
+                    self.notes(vec![(key, tie)], length * self.unit());
+                    rest
+                }
+                'z' | 'x' => {
+                    let (rest, length) = multiplier(&input[1..])?;
+                    self.notes(Vec::new(), length * self.unit());
+                    rest
+                }
+                // Whole measure rests
+                'Z' | 'X' => {
+                    let (count, rest) = digits(&input[1..]);
+                    let measure = self.meter.map_or(1.0, |(numerator, denominator)| {
+                        numerator as f64 / denominator as f64
+                    });
+                    self.notes(Vec::new(), count.parse().unwrap_or(1.0) * measure);
+                    rest
+                }
+                '[' => {
+                    let mut chars = input[1..].chars();
+                    match (chars.next(), chars.next()) {
+                        (Some('|'), _) => self.bar(input),
+                        (Some(digit), _) if digit.is_ascii_digit() => self.ending(&input[1..]),
+                        (Some(name), Some(':')) if name.is_ascii_alphabetic() => {
+                            let end = input.find(']')?;
+                            self.field(name, &input[3..end])?;
+                            &input[end + 1..]
+                        }
+                        _ => self.chord(input)?,
+                    }
+                }
+                '|' | ':' => self.bar(input),
+                '(' if input[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                    self.tuplet(&input[1..])
+                }
+                '>' | '<' => {
+                    let rest = input.trim_start_matches(c);
+                    self.broken_rhythm(input.len() - rest.len(), c == '>');
+                    rest
+                }
+                // Chord symbols, annotations, decorations and grace notes do not sound
+                '"' | '!' | '+' | '{' => skip_marked(input, c),
+                c => &input[c.len_utf8()..],
+            }
+        }
+        Some(())
+    }
+}
+
+// Skips past the mark that closes the one at the start, or to the end of the line
+fn skip_marked(input: &str, c: char) -> &str {
+    let close = if c == '{' { '}' } else { c };
+    input[1..]
+        .find(close)
+        .map_or("", |index| &input[index + 2..])
+}
+
+// A repeated section is played once for each ending number, and at least twice
+fn passes(items: &[Item], section: usize) -> u32 {
+    let mut passes = 2;
+    let mut ending = false;
+    for item in items[section..].iter() {
+        match item {
+            Item::Ending(numbers) => {
+                ending = true;
+                passes = numbers.iter().copied().fold(passes, u32::max);
+            }
+            Item::Bar {
+                repeat_start: true, ..
+            } => break,
+            Item::Bar { double: true, .. } if ending => break,
+            _ => {}
+        }
+    }
+    passes
+}
+
+// Repeated sections are played for each pass, taking the ending numbered for it
+fn unfold(items: &[Item], body: usize) -> Vec<&Item> {
+    let mut output = Vec::new();
+    let mut section = body;
+    let mut pass = 1;
+    let mut ending = false;
+    let mut skipping = false;
+    let mut index = 0;
+    while index < items.len() {
+        match &items[index] {
+            Item::Bar {
+                repeat_end: true, ..
+            } if !skipping && pass < passes(items, section) => {
+                pass += 1;
+                ending = false;
+                index = section;
+                continue;
+            }
+            Item::Bar {
+                repeat_start,
+                repeat_end,
+                double,
+            } => {
+                if *repeat_end && skipping {
+                    // An ending left out on this pass
+                    ending = false;
+                    skipping = false;
+                } else if *repeat_start || *repeat_end || (*double && ending) {
+                    section = index + 1;
+                    pass = 1;
+                    ending = false;
+                    skipping = false;
+                }
+            }
+            Item::Ending(numbers) => {
+                ending = true;
+                skipping = !numbers.contains(&pass);
+            }
+            item if !skipping => output.push(item),
+            _ => {}
+        }
+        index += 1;
+    }
+    output
+}
+
+// Tunes in a file start with their X: reference number field
+pub fn split_tunes(input: &str) -> Vec<&str> {
+    let starts = input
+        .match_indices("X:")
+        .map(|(index, _)| index)
+        .filter(|index| *index == 0 || input[..*index].ends_with('\n'))
+        .collect::<Vec<_>>();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, start)| &input[*start..*starts.get(index + 1).unwrap_or(&input.len())])
+        .collect()
+}
+
+impl MidiFile {
+    pub fn track_to_abc(&self, track: usize) -> Option<String> {
+        Score::new(self)?.to_abc(track)
+    }
+
+    // Reads the first tune, which runs from its X: field to the next blank line
+    pub fn from_abc(input: &str) -> Option<Self> {
+        let lines = input
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .skip_while(|line| !line.starts_with("X:"))
+            .skip(1);
+        let mut tune = Tune::default();
+        let mut body = None;
+        for line in lines {
+            if line.trim().is_empty() || line.starts_with("X:") {
+                break;
+            }
+            let line = line.split('%').next().unwrap();
+            let mut chars = line.chars();
+            match (chars.next(), chars.next()) {
+                (None, _) => {}
+                (Some(name), Some(':')) if name.is_ascii_alphabetic() => {
+                    tune.field(name, &line[2..])?;
+                    // K: closes the header and fixes the unit note length
+                    if name == 'K' && body.is_none() {
+                        tune.unit = Some(tune.unit());
+                        body = Some(tune.items.len());
+                    }
+                }
+                _ if body.is_none() => return None,
+                _ => tune.body(line)?,
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut notes: Vec<Note> = Vec::new();
+        let mut ties = HashMap::new();
+        let mut position = 0.0f64;
+        for item in unfold(&tune.items, body?) {
+            match item {
+                Item::Notes(keys, duration) => {
+                    let start = position.round() as u64;
+                    position += duration;
+                    let end = position.round() as u64;
+                    let mut tied = HashMap::new();
+                    for &(key, tie) in keys {
+                        let index = match ties.get(&key) {
+                            Some(&index) => index,
+                            None => {
+                                notes.push(Note {
+                                    channel: 0,
+                                    key,
+                                    velocity: VELOCITY,
+                                    start,
+                                    end,
+                                });
+                                notes.len() - 1
+                            }
+                        };
+                        notes[index].end = end;
+                        if tie {
+                            tied.insert(key, index);
+                        }
+                    }
+                    ties = tied;
+                }
+                Item::Meta(event) => {
+                    events.push((position.round() as u64, Event::MetaEvent(event.clone())))
+                }
+                _ => {}
+            }
+        }
+        // Events are sorted below, so no delta can be larger than the end of the tune
+        if position.is_nan() || position > 0x0fff_ffff as f64 {
+            return None;
+        }
+        events.extend(
+            TrackChunk::from_notes(&notes)
+                .to_absolute()
+                .into_iter()
+                .filter(|(_, event)| !matches!(event, Event::MetaEvent(MetaEvent::EndOfTrack))),
+        );
+        events.sort_by_key(|(tick, _)| *tick);
+        events.push((
+            position.round() as u64,
+            Event::MetaEvent(MetaEvent::EndOfTrack),
+        ));
+        Some(Self::new(
+            0,
+            Division::TicksPerQuarterNote {
+                ticks: TICKS_PER_QUARTER_NOTE,
+            },
+            vec![TrackChunk::from_absolute(events)],
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, Event, MTrkEvent},
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+        variable_length_quantity::VariableLengthQuantity,
+    };
+
+    use super::split_tunes;
+
+    #[test]
+    fn test_abc() {
+        let note = |key, start, end| Note {
+            channel: 0,
+            key,
+            velocity: 100,
+            start,
+            end,
+        };
+        let mut track = TrackChunk::from_notes(&[
+            note(66, 0, 48),
+            note(67, 48, 96),
+            note(66, 96, 144),
+            note(65, 144, 192),
+            note(64, 192, 224),
+            note(66, 224, 256),
+            note(67, 256, 288),
+            note(72, 288, 384),
+            note(76, 288, 384),
+            note(74, 384, 576),
+        ]);
+        track.data.insert(
+            0,
+            MTrkEvent {
+                delta_time: VariableLengthQuantity { value: 0 },
+                event: Event::MetaEvent(MetaEvent::TimeSignature {
+                    numerator: 3,
+                    denominator: 2,
+                    clocks_per_metronome_click: 24,
+                    thirty_seconds_per_quarter_note: 8,
+                }),
+            },
+        );
+        track.data.insert(
+            0,
+            MTrkEvent {
+                delta_time: VariableLengthQuantity { value: 0 },
+                event: Event::MetaEvent(MetaEvent::KeySignature { key: 1, scale: 1 }),
+            },
+        );
+        let midi_file = MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 96 }, vec![track]);
+        assert_eq!(
+            midi_file.track_to_abc(0).unwrap(),
+            "X:1\n\
+             T:Track 1\n\
+             M:3/4\n\
+             L:1/8\n\
+             K:Em\n\
+             F G F =F (3E ^F G | [ce]2 d4 |]\n"
+        );
+        assert!(midi_file.track_to_abc(1).is_none());
+    }
+
+    #[test]
+    fn test_from_abc() {
+        let book = "%abc-2.1\n\
+                    X:1\n\
+                    T:Test Tune\n\
+                    M:6/8\n\
+                    L:1/8\n\
+                    Q:3/8=60\n\
+                    K:Dmix\n\
+                    |: d2^c B>AG | [DF]3- [DF]2 c |1 (3FGA f4 :|2 % second time\n\
+                    _B,3 =f3 |]\n\
+                    \n\
+                    X:2\n\
+                    T:Second\n\
+                    K:C\n\
+                    CDEF|\n";
+        let tunes = split_tunes(book);
+        assert_eq!(tunes.len(), 2);
+        assert!(tunes[1].starts_with("X:2"));
+
+        let midi_file = MidiFile::from_abc(book).unwrap();
+        let events = midi_file.tracks[0].to_absolute();
+        assert_eq!(
+            events[..4],
+            [
+                (0, Event::MetaEvent(MetaEvent::text(0x03, "Test Tune"))),
+                (
+                    0,
+                    Event::MetaEvent(MetaEvent::TimeSignature {
+                        numerator: 6,
+                        denominator: 3,
+                        clocks_per_metronome_click: 36,
+                        thirty_seconds_per_quarter_note: 8,
+                    })
+                ),
+                (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 666667 })),
+                (
+                    0,
+                    Event::MetaEvent(MetaEvent::KeySignature { key: 1, scale: 0 })
+                ),
+            ]
+        );
+        let notes = midi_file.notes()[0]
+            .iter()
+            .map(|note| (note.key, note.start, note.end))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            notes,
+            [
+                (74, 0, 480),
+                (73, 480, 720),
+                (71, 720, 1080),
+                (69, 1080, 1200),
+                (67, 1200, 1440),
+                (62, 1440, 2640),
+                (66, 1440, 2640),
+                (72, 2640, 2880),
+                (66, 2880, 3040),
+                (67, 3040, 3200),
+                (69, 3200, 3360),
+                (78, 3360, 4320),
+                (74, 4320, 4800),
+                (73, 4800, 5040),
+                (71, 5040, 5400),
+                (69, 5400, 5520),
+                (67, 5520, 5760),
+                (62, 5760, 6960),
+                (66, 5760, 6960),
+                (72, 6960, 7200),
+                (58, 7200, 7920),
+                (77, 7920, 8640),
+            ]
+        );
+        assert_eq!(midi_file.tracks[0].end_tick(), 8640);
+
+        assert_eq!(MidiFile::from_abc(tunes[1]).unwrap().notes()[0].len(), 4);
+        assert!(MidiFile::from_abc("X:1\nT:No key\nCDEF|\n").is_none());
+        assert!(MidiFile::from_abc("X:1\nK:C\nC/0|").is_none());
+        assert!(MidiFile::from_abc("X:1\nK:C\nZ1000000|").is_none());
+        assert!(MidiFile::from_abc("X:1\nK:C\nZ1000|").is_some());
+
+        let keys = |abc: &str| {
+            MidiFile::from_abc(abc).unwrap().notes()[0]
+                .iter()
+                .map(|note| note.key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys("X:1\nK:C\n|: C |1,2 D :|3 E |] F |\n"),
+            [60, 62, 60, 62, 60, 64, 65]
+        );
+        assert_eq!(
+            keys("X:1\nK:C\n|: C |1 D :|2 E :|3 F |]\n"),
+            [60, 62, 60, 64, 60, 65]
+        );
+        assert_eq!(keys("X:1\nK:C\nC D :| E |\n"), [60, 62, 60, 62, 64]);
+        assert_eq!(
+            keys("X:1\nK:C\n[!fermata!CE] [\"Am\".A!>!c] |\n"),
+            [60, 64, 69, 72]
+        );
+    }
+}
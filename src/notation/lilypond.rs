@@ -0,0 +1,239 @@
+use crate::{midi_file::MidiFile, utils::gcd};
+
+use super::{tuplet_marks, Clef, Element, Key, Measure, Pitch, Score, UNITS_PER_QUARTER};
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn pitch_name(pitch: &Pitch) -> String {
+    let accidental = match pitch.alter {
+        alter if alter > 0 => "is".repeat(alter as usize),
+        alter => "es".repeat(alter.unsigned_abs() as usize),
+    };
+    format!("{}{}", pitch.step.to_ascii_lowercase(), accidental)
+}
+
+fn note_name(pitch: &Pitch) -> String {
+    let octave = match pitch.octave {
+        octave if octave > 3 => "'".repeat((octave - 3) as usize),
+        octave => ",".repeat((3 - octave) as usize),
+    };
+    format!("{}{}", pitch_name(pitch), octave)
+}
+
+fn key_command(key: &Key) -> String {
+    format!(
+        "\\key {} {}",
+        pitch_name(&key.tonic()),
+        if key.minor { "\\minor" } else { "\\major" }
+    )
+}
+
+fn fraction(units: u64) -> String {
+    let whole = UNITS_PER_QUARTER * 4;
+    let divisor = gcd(units, whole);
+    format!("1*{}/{}", units / divisor, whole / divisor)
+}
+
+fn tempo(bpm: f64) -> String {
+    format!("\\tempo 4 = {}", bpm.round())
+}
+
+// Tempo changes go before the element they start with, or on a spacer beside the voice
+fn voice(elements: &[Element], measure: &Measure, tempos: &[(u64, f64)]) -> String {
+    let mut tokens = Vec::new();
+    let mut starts = Vec::new();
+    let mut position = 0;
+    let placed = |position: u64| {
+        tempos
+            .iter()
+            .filter(move |(offset, _)| *offset == position)
+            .map(|(_, bpm)| tempo(*bpm))
+    };
+    if elements.is_empty() {
+        starts.push(0);
+        tokens.extend(placed(0));
+        tokens.push(format!("R{}", fraction(measure.length)));
+    }
+    let marks = tuplet_marks(elements);
+    for (element, (tuplet_start, tuplet_stop)) in elements.iter().zip(marks) {
+        starts.push(position);
+        tokens.extend(placed(position));
+        position += element.value.units;
+        if tuplet_start {
+            tokens.push("\\tuplet 3/2 {".to_string());
+        }
+        let notes = element
+            .keys
+            .iter()
+            .map(|key| note_name(&Pitch::spell(*key, measure.key.fifths)))
+            .collect::<Vec<_>>();
+        let mut token = match notes.len() {
+            0 => "r".to_string(),
+            1 => notes[0].clone(),
+            _ => format!("<{}>", notes.join(" ")),
+        };
+        token.push_str(&(1 << element.value.base).to_string());
+        token.push_str(&".".repeat(element.value.dots as usize));
+        if element.tie_start {
+            token.push('~');
+        }
+        tokens.push(token);
+        if tuplet_stop {
+            tokens.push("}".to_string());
+        }
+    }
+    let mut spacer = Vec::new();
+    let mut position = 0;
+    for (offset, bpm) in tempos {
+        if starts.contains(offset) {
+            continue;
+        }
+        if *offset > position {
+            spacer.push(format!("s{}", fraction(offset - position)));
+            position = *offset;
+        }
+        spacer.push(tempo(*bpm));
+    }
+    match spacer.is_empty() {
+        true => tokens.join(" "),
+        false => format!(
+            "<< {{ {} }} {{ {} }} >>",
+            tokens.join(" "),
+            spacer.join(" ")
+        ),
+    }
+}
+
+impl Score {
+    pub fn to_lilypond(&self, track: usize) -> Option<String> {
+        let part = &self.parts[self.track_part(track)?];
+        let mut lines = vec!["\\version \"2.24.0\"".to_string()];
+        if let Some(title) = &self.title {
+            lines.push(format!("\\header {{ title = {} }}", quote(title)));
+        }
+        lines.push("\\score {".to_string());
+        lines.push(format!(
+            "  \\new Staff \\with {{ instrumentName = {} }} {{",
+            quote(&part.name)
+        ));
+        lines.push(format!(
+            "    \\clef {}",
+            match part.clef {
+                Clef::Treble => "treble",
+                Clef::Bass => "bass",
+                Clef::Percussion => "percussion",
+            }
+        ));
+        for (index, (measure, voices)) in self.measures.iter().zip(part.measures.iter()).enumerate()
+        {
+            let previous = index.checked_sub(1).map(|index| &self.measures[index]);
+            if previous.is_none_or(|previous| previous.key != measure.key) {
+                lines.push(format!("    {}", key_command(&measure.key)));
+            }
+            if previous.is_none_or(|previous| previous.time_signature != measure.time_signature) {
+                lines.push(format!(
+                    "    \\time {}/{}",
+                    measure.time_signature.0, measure.time_signature.1
+                ));
+            }
+            let voices = voices
+                .iter()
+                .enumerate()
+                .filter(|(index, elements)| *index == 0 || !elements.is_empty())
+                .map(|(index, elements)| match index {
+                    0 => voice(elements, measure, &measure.tempos),
+                    _ => voice(elements, measure, &[]),
+                })
+                .collect::<Vec<_>>();
+            let bar = match voices.len() {
+                1 => voices[0].clone(),
+                _ => format!("<< {{ {} }} >>", voices.join(" } \\\\ { ")),
+            };
+            lines.push(format!("    {} |", bar));
+        }
+        lines.push("    \\bar \"|.\"".to_string());
+        lines.push("  }".to_string());
+        lines.push("  \\layout { }".to_string());
+        lines.push("}".to_string());
+        Some(lines.join("\n") + "\n")
+    }
+}
+
+impl MidiFile {
+    pub fn track_to_lilypond(&self, track: usize) -> Option<String> {
+        Score::new(self)?.to_lilypond(track)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, Event},
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+    };
+
+    #[test]
+    fn test_lilypond() {
+        let note = |key, start, end| Note {
+            channel: 0,
+            key,
+            velocity: 100,
+            start,
+            end,
+        };
+        let track = TrackChunk::from_notes(&[
+            note(48, 0, 32),
+            note(51, 32, 64),
+            note(55, 64, 96),
+            note(46, 96, 288),
+            note(43, 96, 192),
+            note(41, 192, 480),
+        ]);
+        let midi_file = MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 96 }, vec![track]);
+        assert_eq!(
+            midi_file.track_to_lilypond(0).unwrap(),
+            "\\version \"2.24.0\"\n\
+             \\score {\n  \
+               \\new Staff \\with { instrumentName = \"Track 1\" } {\n    \
+                 \\clef bass\n    \
+                 \\key c \\major\n    \
+                 \\time 4/4\n    \
+                 << { \\tuplet 3/2 { c8 ees8 g8 } g,4 f,2~ } \\\\ { r4 bes,2 r4 } >> |\n    \
+                 f,4 r2. |\n    \
+                 \\bar \"|.\"\n  \
+               }\n  \
+               \\layout { }\n\
+             }\n"
+        );
+        assert!(midi_file.track_to_lilypond(1).is_none());
+
+        let conductor = TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 500000 })),
+            (192, Event::MetaEvent(MetaEvent::SetTempo { tempo: 600000 })),
+            (576, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+            (768, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let track = TrackChunk::from_notes(&[
+            note(60, 0, 96),
+            note(62, 96, 192),
+            note(64, 192, 288),
+            note(65, 288, 384),
+            note(67, 384, 768),
+        ]);
+        let midi_file = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![conductor, track],
+        );
+        let lilypond = midi_file.track_to_lilypond(1).unwrap();
+        assert!(lilypond.contains(
+            "\\tempo 4 = 120 c'4 d'4 \\tempo 4 = 100 e'4 f'4 |\n    \
+             << { g'1 } { s1*1/2 \\tempo 4 = 150 } >> |"
+        ));
+    }
+}
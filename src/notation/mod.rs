@@ -0,0 +1,598 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    event::{meta_event::MetaEvent, Event},
+    general_midi::PERCUSSION_CHANNEL,
+    header::Division,
+    midi_file::MidiFile,
+    note::Note,
+};
+
+pub mod abc;
+pub mod lilypond;
+pub mod musicxml;
+
+pub const UNITS_PER_QUARTER: u64 = 12;
+const NOTE_TYPES: [&str; 6] = ["whole", "half", "quarter", "eighth", "16th", "32nd"];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Pitch {
+    pub step: char,
+    pub alter: i8,
+    pub octave: i8,
+}
+
+impl Pitch {
+    pub fn spell(key: u8, fifths: i8) -> Self {
+        let pitch_class = (key % 12) as i32;
+        // Twelve consecutive positions on the line of fifths, leaning to the sharp side of the key
+        let fifths = fifths as i32;
+        let position = (fifths - 3..fifths + 9)
+            .find(|position| (position * 7).rem_euclid(12) == pitch_class)
+            .unwrap();
+        let alter = (position + 1).div_euclid(7);
+        Self {
+            step: b"FCGDAEB"[(position + 1).rem_euclid(7) as usize] as char,
+            alter: alter as i8,
+            octave: ((key as i32 - alter).div_euclid(12) - 1) as i8,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Key {
+    pub fifths: i8,
+    pub minor: bool,
+}
+
+impl Key {
+    pub fn tonic(&self) -> Pitch {
+        let pitch_class = (self.fifths as i32 * 7 + if self.minor { 9 } else { 0 }).rem_euclid(12);
+        Pitch::spell(60 + pitch_class as u8, self.fifths)
+    }
+
+    // The alteration the key signature gives to a step
+    pub fn alter(&self, step: char) -> i8 {
+        let order = match self.fifths >= 0 {
+            true => "FCGDAEB",
+            false => "BEADGCF",
+        };
+        match order.find(step) {
+            Some(index) if index < self.fifths.unsigned_abs() as usize => self.fifths.signum(),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NoteValue {
+    pub units: u64,
+    pub base: u8,
+    pub dots: u8,
+    pub triplet: bool,
+}
+
+const fn value(units: u64, base: u8, dots: u8, triplet: bool) -> NoteValue {
+    NoteValue {
+        units,
+        base,
+        dots,
+        triplet,
+    }
+}
+
+const NOTE_VALUES: [NoteValue; 14] = [
+    value(72, 0, 1, false),
+    value(48, 0, 0, false),
+    value(36, 1, 1, false),
+    value(24, 1, 0, false),
+    value(18, 2, 1, false),
+    value(16, 1, 0, true),
+    value(12, 2, 0, false),
+    value(9, 3, 1, false),
+    value(8, 2, 0, true),
+    value(6, 3, 0, false),
+    value(4, 3, 0, true),
+    value(3, 4, 0, false),
+    value(2, 4, 0, true),
+    value(1, 5, 0, true),
+];
+
+impl NoteValue {
+    pub fn type_name(&self) -> &'static str {
+        NOTE_TYPES[self.base as usize]
+    }
+
+    // Plain values start on a multiple of their undotted length, up to a beat
+    fn fits(&self, offset: u64) -> bool {
+        let alignment = match self.triplet {
+            true => self.units.min(4),
+            false => (48 >> self.base).min(UNITS_PER_QUARTER),
+        };
+        offset.is_multiple_of(alignment)
+    }
+}
+
+// The fewest tied values covering the span, preferring plain values over triplets
+pub fn split_duration(offset: u64, units: u64) -> Vec<NoteValue> {
+    let length = units as usize;
+    let mut best: Vec<Option<(usize, usize, NoteValue)>> = vec![None; length + 1];
+    for position in (0..length).rev() {
+        for value in NOTE_VALUES.iter() {
+            let next = position + value.units as usize;
+            if next > length || !value.fits(offset + position as u64) {
+                continue;
+            }
+            let (pieces, triplets) = match next {
+                next if next == length => (0, 0),
+                next => match best[next] {
+                    Some((pieces, triplets, _)) => (pieces, triplets),
+                    None => continue,
+                },
+            };
+            let cost = (pieces + 1, triplets + value.triplet as usize);
+            if best[position].is_none_or(|(pieces, triplets, _)| cost < (pieces, triplets)) {
+                best[position] = Some((cost.0, cost.1, *value));
+            }
+        }
+    }
+    let mut values = Vec::new();
+    let mut position = 0;
+    while position < length {
+        let (_, _, value) = best[position].unwrap();
+        values.push(value);
+        position += value.units as usize;
+    }
+    values
+}
+
+// Each beat is snapped to either a sixteenth or an eighth triplet grid, whichever fits
+// the events inside it better
+struct Quantizer {
+    ticks_per_quarter: u64,
+    triplet_beats: HashSet<u64>,
+}
+
+impl Quantizer {
+    fn new(ticks_per_quarter: u64, ticks: impl Iterator<Item = u64>) -> Self {
+        let error = |offset: u64, divisions: f64| {
+            let step = ticks_per_quarter as f64 / divisions;
+            let steps = offset as f64 / step;
+            (steps - steps.round()).abs() * step
+        };
+        let mut errors: HashMap<u64, (f64, f64)> = HashMap::new();
+        for tick in ticks {
+            let offset = tick % ticks_per_quarter;
+            let entry = errors.entry(tick / ticks_per_quarter).or_default();
+            entry.0 += error(offset, 4.0);
+            entry.1 += error(offset, 3.0);
+        }
+        Self {
+            ticks_per_quarter,
+            triplet_beats: errors
+                .into_iter()
+                .filter(|(_, (duple, triplet))| triplet < duple)
+                .map(|(beat, _)| beat)
+                .collect(),
+        }
+    }
+
+    fn divisions(&self, tick: u64) -> u64 {
+        match self
+            .triplet_beats
+            .contains(&(tick / self.ticks_per_quarter))
+        {
+            true => 3,
+            false => 4,
+        }
+    }
+
+    fn quantize_changes<T>(&self, changes: &mut [(u64, T)]) {
+        changes.sort_by_key(|(tick, _)| *tick);
+        for (tick, _) in changes.iter_mut() {
+            *tick = self.quantize(*tick);
+        }
+    }
+
+    fn quantize(&self, tick: u64) -> u64 {
+        let divisions = self.divisions(tick);
+        let offset = tick % self.ticks_per_quarter;
+        let steps = (offset as f64 * divisions as f64 / self.ticks_per_quarter as f64).round();
+        (tick / self.ticks_per_quarter) * UNITS_PER_QUARTER
+            + steps as u64 * (UNITS_PER_QUARTER / divisions)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Clef {
+    Treble,
+    Bass,
+    Percussion,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Element {
+    pub value: NoteValue,
+    pub keys: Vec<u8>,
+    pub tie_start: bool,
+    pub tie_stop: bool,
+}
+
+impl Element {
+    pub fn is_rest(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Measure {
+    pub start: u64,
+    pub length: u64,
+    pub time_signature: (u8, u8),
+    pub key: Key,
+    pub tempos: Vec<(u64, f64)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Part {
+    pub track: usize,
+    pub name: String,
+    pub clef: Clef,
+    // Elements of every voice in every measure, an empty first voice being a measure rest
+    pub measures: Vec<Vec<Vec<Element>>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Score {
+    pub title: Option<String>,
+    pub measures: Vec<Measure>,
+    pub parts: Vec<Part>,
+}
+
+// Triplet brackets close on every beat and wherever the run of triplets ends
+pub(crate) fn tuplet_marks(elements: &[Element]) -> Vec<(bool, bool)> {
+    let mut marks = vec![(false, false); elements.len()];
+    let mut units = 0;
+    for (index, element) in elements.iter().enumerate() {
+        if !element.value.triplet {
+            continue;
+        }
+        marks[index].0 = units == 0;
+        units += element.value.units;
+        let next_triplet = elements
+            .get(index + 1)
+            .is_some_and(|next| next.value.triplet);
+        if units % UNITS_PER_QUARTER == 0 || !next_triplet {
+            marks[index].1 = true;
+            units = 0;
+        }
+    }
+    marks
+}
+
+type Chord = (u64, u64, Vec<u8>);
+
+fn span(offset: u64, units: u64, keys: &[u8], tie_stop: bool, tie_start: bool) -> Vec<Element> {
+    let values = split_duration(offset, units);
+    let last = values.len() - 1;
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| Element {
+            value,
+            keys: keys.to_vec(),
+            tie_start: !keys.is_empty() && (index < last || tie_start),
+            tie_stop: !keys.is_empty() && (index > 0 || tie_stop),
+        })
+        .collect()
+}
+
+fn voice_elements(chords: &[Chord], measure: &Measure) -> Vec<Element> {
+    let end = measure.start + measure.length;
+    let mut elements = Vec::new();
+    let mut position = measure.start;
+    for (chord_start, chord_end, keys) in chords
+        .iter()
+        .filter(|(chord_start, chord_end, _)| *chord_start < end && *chord_end > measure.start)
+    {
+        if *chord_start > position {
+            elements.extend(span(
+                position - measure.start,
+                chord_start - position,
+                &[],
+                false,
+                false,
+            ));
+        }
+        let from = (*chord_start).max(measure.start);
+        let to = (*chord_end).min(end);
+        elements.extend(span(
+            from - measure.start,
+            to - from,
+            keys,
+            *chord_start < measure.start,
+            *chord_end > end,
+        ));
+        position = to;
+    }
+    if !elements.is_empty() && position < end {
+        elements.extend(span(
+            position - measure.start,
+            end - position,
+            &[],
+            false,
+            false,
+        ));
+    }
+    elements
+}
+
+fn active<T: Copy>(changes: &[(u64, T)], position: u64, default: T) -> T {
+    changes
+        .iter()
+        .take_while(|(start, _)| *start <= position)
+        .last()
+        .map_or(default, |(_, value)| *value)
+}
+
+impl Score {
+    pub fn new(midi_file: &MidiFile) -> Option<Self> {
+        let ticks_per_quarter = match midi_file.header.data.division {
+            Division::TicksPerQuarterNote { ticks } if ticks > 0 => ticks as u64,
+            _ => return None,
+        };
+        let tracks = midi_file.notes();
+        let mut time_signatures = Vec::new();
+        let mut keys = Vec::new();
+        let mut tempos = Vec::new();
+        let mut names = Vec::new();
+        for track in midi_file.tracks.iter() {
+            let mut name = None;
+            for (tick, event) in track.to_absolute() {
+                match event {
+                    // A measure in units needs a denominator that divides 48, the units of a whole
+                    Event::MetaEvent(MetaEvent::TimeSignature {
+                        numerator,
+                        denominator,
+                        ..
+                    }) if numerator > 0 && denominator <= 4 => {
+                        time_signatures.push((tick, (numerator, 1 << denominator)))
+                    }
+                    Event::MetaEvent(MetaEvent::KeySignature { key, scale }) => keys.push((
+                        tick,
+                        Key {
+                            fifths: (key as i8).clamp(-7, 7),
+                            minor: scale == 1,
+                        },
+                    )),
+                    Event::MetaEvent(MetaEvent::SetTempo { tempo }) if tempo > 0 => {
+                        tempos.push((tick, 60_000_000.0 / tempo as f64))
+                    }
+                    Event::MetaEvent(MetaEvent::SequenceOrTrackName { text, .. })
+                        if name.is_none() =>
+                    {
+                        name = Some(text)
+                    }
+                    _ => {}
+                }
+            }
+            names.push(name);
+        }
+
+        let quantizer = Quantizer::new(
+            ticks_per_quarter,
+            tracks
+                .iter()
+                .flatten()
+                .flat_map(|note| [note.start, note.end]),
+        );
+        quantizer.quantize_changes(&mut time_signatures);
+        quantizer.quantize_changes(&mut keys);
+        quantizer.quantize_changes(&mut tempos);
+
+        let chords = tracks
+            .iter()
+            .map(|notes| Self::chords(notes, &quantizer))
+            .collect::<Vec<_>>();
+        let end = chords
+            .iter()
+            .flatten()
+            .map(|(_, end, _)| *end)
+            .max()
+            .unwrap_or(0);
+        let mut measures = Vec::new();
+        let mut start = 0;
+        while start < end || measures.is_empty() {
+            let (numerator, denominator) = active(&time_signatures, start, (4, 4));
+            let mut length = (numerator as u64 * UNITS_PER_QUARTER * 4 / denominator as u64).max(1);
+            if let Some((next, _)) = time_signatures.iter().find(|(change, _)| *change > start) {
+                length = length.min(next - start);
+            }
+            measures.push(Measure {
+                start,
+                length,
+                time_signature: (numerator, denominator),
+                key: active(&keys, start, Key::default()),
+                tempos: tempos
+                    .iter()
+                    .filter(|(tick, _)| *tick >= start && *tick < start + length)
+                    .map(|(tick, bpm)| (tick - start, *bpm))
+                    .collect(),
+            });
+            start += length;
+        }
+
+        let mut title = None;
+        let mut parts = Vec::new();
+        for (index, (notes, chords)) in tracks.iter().zip(chords.iter()).enumerate() {
+            if notes.is_empty() {
+                if title.is_none() {
+                    title = names[index].clone();
+                }
+                continue;
+            }
+            let mut voices: Vec<Vec<Chord>> = Vec::new();
+            for chord in chords.iter() {
+                match voices
+                    .iter_mut()
+                    .find(|voice| voice.last().unwrap().1 <= chord.0)
+                {
+                    Some(voice) => voice.push(chord.clone()),
+                    None => voices.push(vec![chord.clone()]),
+                }
+            }
+            let average =
+                notes.iter().map(|note| note.key as u64).sum::<u64>() / notes.len() as u64;
+            parts.push(Part {
+                track: index,
+                name: names[index]
+                    .clone()
+                    .unwrap_or_else(|| format!("Track {}", index + 1)),
+                clef: if notes.iter().all(|note| note.channel == PERCUSSION_CHANNEL) {
+                    Clef::Percussion
+                } else if average < 60 {
+                    Clef::Bass
+                } else {
+                    Clef::Treble
+                },
+                measures: measures
+                    .iter()
+                    .map(|measure| {
+                        voices
+                            .iter()
+                            .map(|voice| voice_elements(voice, measure))
+                            .collect()
+                    })
+                    .collect(),
+            });
+        }
+        Some(Self {
+            title,
+            measures,
+            parts,
+        })
+    }
+
+    pub fn track_part(&self, track: usize) -> Option<usize> {
+        self.parts.iter().position(|part| part.track == track)
+    }
+
+    // Notes sharing a quantized start and end are merged into chords
+    fn chords(notes: &[Note], quantizer: &Quantizer) -> Vec<Chord> {
+        let mut chords: Vec<Chord> = Vec::new();
+        for note in notes {
+            let start = quantizer.quantize(note.start);
+            let end = quantizer
+                .quantize(note.end)
+                .max(start + UNITS_PER_QUARTER / quantizer.divisions(note.start));
+            match chords
+                .iter_mut()
+                .rev()
+                .take_while(|(chord_start, _, _)| *chord_start == start)
+                .find(|(_, chord_end, _)| *chord_end == end)
+            {
+                Some((_, _, keys)) => {
+                    if !keys.contains(&note.key) {
+                        keys.push(note.key);
+                        keys.sort();
+                    }
+                }
+                None => chords.push((start, end, vec![note.key])),
+            }
+        }
+        chords.sort_by_key(|(start, end, _)| (*start, *end));
+        chords
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, Event},
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+    };
+
+    use super::{split_duration, Pitch, Score};
+
+    #[test]
+    fn test_spell() {
+        let spell = |key, fifths| {
+            let pitch = Pitch::spell(key, fifths);
+            (pitch.step, pitch.alter, pitch.octave)
+        };
+        assert_eq!(spell(60, 0), ('C', 0, 4));
+        assert_eq!(spell(61, 0), ('C', 1, 4));
+        assert_eq!(spell(70, 0), ('B', -1, 4));
+        assert_eq!(spell(61, -4), ('D', -1, 4));
+        assert_eq!(spell(60, 7), ('B', 1, 3));
+        assert_eq!(spell(71, -7), ('C', -1, 5));
+    }
+
+    #[test]
+    fn test_score() {
+        let durations = |offset, units| {
+            split_duration(offset, units)
+                .iter()
+                .map(|value| value.units)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(durations(0, 48), vec![48]);
+        assert_eq!(durations(6, 18), vec![6, 12]);
+        assert_eq!(durations(4, 8), vec![8]);
+        assert_eq!(durations(0, 21), vec![18, 3]);
+
+        let note = |key, start, end| Note {
+            channel: 0,
+            key,
+            velocity: 100,
+            start,
+            end,
+        };
+        let track = TrackChunk::from_notes(&[
+            note(60, 0, 32),
+            note(64, 32, 64),
+            note(67, 64, 96),
+            note(72, 336, 480),
+            note(76, 336, 480),
+        ]);
+        let midi_file = MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 96 }, vec![track]);
+        let score = Score::new(&midi_file).unwrap();
+        assert_eq!(score.measures.len(), 2);
+        let measures = &score.parts[0].measures;
+        let first = &measures[0][0];
+        assert!(first[..3].iter().all(|element| element.value.triplet));
+        assert_eq!(first[3].value.units, 24);
+        assert!(first[3].is_rest());
+        assert_eq!(first[5].keys, vec![72, 76]);
+        assert!(first[5].tie_start);
+        assert!(measures[1][0][0].tie_stop);
+        assert_eq!(measures[1][0][0].value.units, 12);
+        assert_eq!(measures[1][0][1].value.units, 36);
+
+        // 3/32 cannot be written in units, so it is left out
+        let mut events = midi_file.tracks[0].to_absolute();
+        events.insert(
+            0,
+            (
+                0,
+                Event::MetaEvent(MetaEvent::TimeSignature {
+                    numerator: 3,
+                    denominator: 5,
+                    clocks_per_metronome_click: 3,
+                    thirty_seconds_per_quarter_note: 8,
+                }),
+            ),
+        );
+        let midi_file = MidiFile::new(
+            0,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![TrackChunk::from_absolute(events)],
+        );
+        let score = Score::new(&midi_file).unwrap();
+        assert_eq!(score.measures[0].time_signature, (4, 4));
+        assert_eq!(score.measures[0].length, 48);
+    }
+}
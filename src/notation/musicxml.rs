@@ -0,0 +1,336 @@
+use crate::midi_file::MidiFile;
+
+use super::{tuplet_marks, Clef, Element, Pitch, Score, UNITS_PER_QUARTER};
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+struct Writer {
+    lines: Vec<String>,
+    depth: usize,
+}
+
+impl Writer {
+    fn line(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{}", "  ".repeat(self.depth), line));
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.line(format!("<{}>", tag));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.line(format!("</{}>", tag));
+    }
+
+    fn element(&mut self, tag: &str, text: &str) {
+        self.line(format!("<{}>{}</{}>", tag, escape(text), tag));
+    }
+}
+
+fn format_tempo(bpm: f64) -> String {
+    format!("{}", (bpm * 100.0).round() / 100.0)
+}
+
+fn write_element(
+    writer: &mut Writer,
+    element: &Element,
+    voice: usize,
+    fifths: i8,
+    clef: Clef,
+    tuplet: (bool, bool),
+) {
+    let keys = match element.is_rest() {
+        true => vec![None],
+        false => element.keys.iter().map(|key| Some(*key)).collect(),
+    };
+    for (index, key) in keys.into_iter().enumerate() {
+        writer.open("note");
+        if index > 0 {
+            writer.line("<chord/>".to_string());
+        }
+        match key {
+            // Drums are placed on the staff by the key they would have as a pitch
+            Some(key) if clef == Clef::Percussion => {
+                let pitch = Pitch::spell(key, 0);
+                writer.open("unpitched");
+                writer.element("display-step", &pitch.step.to_string());
+                writer.element("display-octave", &pitch.octave.to_string());
+                writer.close("unpitched");
+            }
+            Some(key) => {
+                let pitch = Pitch::spell(key, fifths);
+                writer.open("pitch");
+                writer.element("step", &pitch.step.to_string());
+                if pitch.alter != 0 {
+                    writer.element("alter", &pitch.alter.to_string());
+                }
+                writer.element("octave", &pitch.octave.to_string());
+                writer.close("pitch");
+            }
+            None => writer.line("<rest/>".to_string()),
+        }
+        writer.element("duration", &element.value.units.to_string());
+        if element.tie_stop {
+            writer.line("<tie type=\"stop\"/>".to_string());
+        }
+        if element.tie_start {
+            writer.line("<tie type=\"start\"/>".to_string());
+        }
+        writer.element("voice", &voice.to_string());
+        writer.element("type", element.value.type_name());
+        for _ in 0..element.value.dots {
+            writer.line("<dot/>".to_string());
+        }
+        if element.value.triplet {
+            writer.open("time-modification");
+            writer.element("actual-notes", "3");
+            writer.element("normal-notes", "2");
+            writer.close("time-modification");
+        }
+        let tuplet = match index {
+            0 => tuplet,
+            _ => (false, false),
+        };
+        if element.tie_start || element.tie_stop || tuplet.0 || tuplet.1 {
+            writer.open("notations");
+            if element.tie_stop {
+                writer.line("<tied type=\"stop\"/>".to_string());
+            }
+            if element.tie_start {
+                writer.line("<tied type=\"start\"/>".to_string());
+            }
+            if tuplet.0 {
+                writer.line("<tuplet type=\"start\" bracket=\"yes\"/>".to_string());
+            }
+            if tuplet.1 {
+                writer.line("<tuplet type=\"stop\"/>".to_string());
+            }
+            writer.close("notations");
+        }
+        writer.close("note");
+    }
+}
+
+impl Score {
+    pub fn to_musicxml(&self) -> String {
+        let mut writer = Writer {
+            lines: vec![
+                "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>".to_string(),
+                "<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">".to_string(),
+            ],
+            depth: 0,
+        };
+        writer.line("<score-partwise version=\"4.0\">".to_string());
+        writer.depth += 1;
+        if let Some(title) = &self.title {
+            writer.open("work");
+            writer.element("work-title", title);
+            writer.close("work");
+        }
+        writer.open("part-list");
+        for (index, part) in self.parts.iter().enumerate() {
+            writer.line(format!("<score-part id=\"P{}\">", index + 1));
+            writer.depth += 1;
+            writer.element("part-name", &part.name);
+            writer.close("score-part");
+        }
+        writer.close("part-list");
+
+        for (part_index, part) in self.parts.iter().enumerate() {
+            writer.line(format!("<part id=\"P{}\">", part_index + 1));
+            writer.depth += 1;
+            for (index, (measure, voices)) in
+                self.measures.iter().zip(part.measures.iter()).enumerate()
+            {
+                writer.line(format!("<measure number=\"{}\">", index + 1));
+                writer.depth += 1;
+                let previous = index.checked_sub(1).map(|index| &self.measures[index]);
+                let key_changed = previous.is_none_or(|previous| previous.key != measure.key);
+                let time_changed = previous
+                    .is_none_or(|previous| previous.time_signature != measure.time_signature);
+                if key_changed || time_changed {
+                    writer.open("attributes");
+                    if index == 0 {
+                        writer.element("divisions", &UNITS_PER_QUARTER.to_string());
+                    }
+                    if key_changed {
+                        writer.open("key");
+                        writer.element("fifths", &measure.key.fifths.to_string());
+                        writer.element(
+                            "mode",
+                            match measure.key.minor {
+                                true => "minor",
+                                false => "major",
+                            },
+                        );
+                        writer.close("key");
+                    }
+                    if time_changed {
+                        writer.open("time");
+                        writer.element("beats", &measure.time_signature.0.to_string());
+                        writer.element("beat-type", &measure.time_signature.1.to_string());
+                        writer.close("time");
+                    }
+                    if index == 0 {
+                        writer.open("clef");
+                        let (sign, line) = match part.clef {
+                            Clef::Treble => ("G", Some(2)),
+                            Clef::Bass => ("F", Some(4)),
+                            Clef::Percussion => ("percussion", None),
+                        };
+                        writer.element("sign", sign);
+                        if let Some(line) = line {
+                            writer.element("line", &line.to_string());
+                        }
+                        writer.close("clef");
+                    }
+                    writer.close("attributes");
+                }
+                if part_index == 0 {
+                    for (offset, bpm) in measure.tempos.iter() {
+                        writer.line("<direction placement=\"above\">".to_string());
+                        writer.depth += 1;
+                        writer.open("direction-type");
+                        writer.open("metronome");
+                        writer.element("beat-unit", "quarter");
+                        writer.element("per-minute", &format_tempo(*bpm));
+                        writer.close("metronome");
+                        writer.close("direction-type");
+                        if *offset > 0 {
+                            writer.element("offset", &offset.to_string());
+                        }
+                        writer.line(format!("<sound tempo=\"{}\"/>", format_tempo(*bpm)));
+                        writer.close("direction");
+                    }
+                }
+                for (voice, elements) in voices.iter().enumerate() {
+                    if voice > 0 {
+                        if elements.is_empty() {
+                            continue;
+                        }
+                        writer.open("backup");
+                        writer.element("duration", &measure.length.to_string());
+                        writer.close("backup");
+                    }
+                    if elements.is_empty() {
+                        writer.open("note");
+                        writer.line("<rest measure=\"yes\"/>".to_string());
+                        writer.element("duration", &measure.length.to_string());
+                        writer.element("voice", "1");
+                        writer.close("note");
+                    }
+                    for (element, tuplet) in elements.iter().zip(tuplet_marks(elements)) {
+                        write_element(
+                            &mut writer,
+                            element,
+                            voice + 1,
+                            measure.key.fifths,
+                            part.clef,
+                            tuplet,
+                        );
+                    }
+                }
+                writer.close("measure");
+            }
+            writer.close("part");
+        }
+        writer.close("score-partwise");
+        writer.lines.join("\n") + "\n"
+    }
+}
+
+impl MidiFile {
+    pub fn to_musicxml(&self) -> Option<String> {
+        Score::new(self).map(|score| score.to_musicxml())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, Event},
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+    };
+
+    #[test]
+    fn test_musicxml() {
+        let note = |key, start, end| Note {
+            channel: 0,
+            key,
+            velocity: 100,
+            start,
+            end,
+        };
+        let conductor = TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::text(0x03, "Songs & Dances"))),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::KeySignature {
+                    key: (-1i8) as u8,
+                    scale: 0,
+                }),
+            ),
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+            (0, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let track = TrackChunk::from_notes(&[
+            note(70, 0, 32),
+            note(72, 32, 64),
+            note(74, 64, 96),
+            note(65, 288, 480),
+        ]);
+        let midi_file = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![conductor, track],
+        );
+        let xml = midi_file.to_musicxml().unwrap();
+        assert!(xml.contains("<work-title>Songs &amp; Dances</work-title>"));
+        assert!(xml.contains("<part-name>Track 2</part-name>"));
+        assert!(xml.contains("<fifths>-1</fifths>"));
+        assert!(xml.contains("<per-minute>150</per-minute>"));
+        assert!(xml.contains("<step>B</step>\n          <alter>-1</alter>"));
+        assert_eq!(xml.matches("<actual-notes>3</actual-notes>").count(), 3);
+        assert_eq!(xml.matches("<tuplet type=\"start\"").count(), 1);
+        assert_eq!(xml.matches("<tuplet type=\"stop\"/>").count(), 1);
+        assert_eq!(xml.matches("<tie type=\"start\"/>").count(), 1);
+        assert_eq!(xml.matches("<tie type=\"stop\"/>").count(), 1);
+        assert_eq!(xml.matches("<measure number=").count(), 2);
+        assert_eq!(xml.matches("<part id=").count(), 1);
+        assert!(!xml.contains("<unpitched>"));
+
+        let drums = TrackChunk::from_notes(&[Note {
+            channel: 9,
+            ..note(38, 0, 24)
+        }]);
+        let midi_file = MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 96 }, vec![drums]);
+        let xml = midi_file.to_musicxml().unwrap();
+        assert!(xml.contains("<sign>percussion</sign>"));
+        assert!(xml.contains(
+            "<unpitched>\n          <display-step>D</display-step>\n          <display-octave>2</display-octave>"
+        ));
+        assert!(!xml.contains("<pitch>"));
+
+        let smpte = MidiFile::new(
+            0,
+            Division::FramesPerSecond {
+                frames: 0xe7,
+                ticks: 40,
+            },
+            vec![],
+        );
+        assert!(smpte.to_musicxml().is_none());
+    }
+}
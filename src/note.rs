@@ -0,0 +1,163 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    event::{
+        meta_event::MetaEvent,
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        Event,
+    },
+    midi_file::MidiFile,
+    track::TrackChunk,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Note {
+    pub channel: u8,
+    pub key: u8,
+    pub velocity: u8,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Note {
+    pub fn duration(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+impl TrackChunk {
+    // Overlapping notes on the same key are paired first in, first out, and notes
+    // still sounding at the end of the track are closed there
+    pub fn notes(&self) -> Vec<Note> {
+        let mut notes = Vec::new();
+        let mut sounding: HashMap<(u8, u8), VecDeque<(u64, u8)>> = HashMap::new();
+        let mut end_tick = 0;
+        for (tick, event) in self.to_absolute() {
+            end_tick = tick;
+            let message = match event {
+                Event::MidiEvent(MidiMessage::ChannelMessage(
+                    ChannelMessage::ChannelVoiceMessage(message),
+                )) => message,
+                _ => continue,
+            };
+            match message {
+                ChannelVoiceMessage::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                } if velocity > 0 => sounding
+                    .entry((channel, note))
+                    .or_default()
+                    .push_back((tick, velocity)),
+                ChannelVoiceMessage::NoteOn { channel, note, .. }
+                | ChannelVoiceMessage::NoteOff { channel, note, .. } => {
+                    if let Some((start, velocity)) = sounding
+                        .get_mut(&(channel, note))
+                        .and_then(|starts| starts.pop_front())
+                    {
+                        notes.push(Note {
+                            channel,
+                            key: note,
+                            velocity,
+                            start,
+                            end: tick,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        for ((channel, key), starts) in sounding {
+            notes.extend(starts.into_iter().map(|(start, velocity)| Note {
+                channel,
+                key,
+                velocity,
+                start,
+                end: end_tick,
+            }));
+        }
+        notes.sort_by_key(|note| (note.start, note.key, note.channel));
+        notes
+    }
+
+    pub fn from_notes(notes: &[Note]) -> TrackChunk {
+        let mut events = Vec::with_capacity(notes.len() * 2);
+        for note in notes {
+            assert!(note.channel < 16 && note.key < 128 && note.velocity < 128);
+            assert!(note.velocity > 0 && note.end >= note.start);
+            events.push((
+                note.end,
+                if note.end == note.start { 2 } else { 0 },
+                ChannelVoiceMessage::NoteOff {
+                    channel: note.channel,
+                    note: note.key,
+                    velocity: 64,
+                },
+            ));
+            events.push((
+                note.start,
+                1,
+                ChannelVoiceMessage::NoteOn {
+                    channel: note.channel,
+                    note: note.key,
+                    velocity: note.velocity,
+                },
+            ));
+        }
+        // Note offs go first so repeated notes do not swallow each other, except for
+        // zero length notes which have to start before they end
+        events.sort_by_key(|(tick, order, _)| (*tick, *order));
+        let end_tick = events.last().map(|(tick, _, _)| *tick).unwrap_or(0);
+        let mut events = events
+            .into_iter()
+            .map(|(tick, _, message)| (tick, Event::from(message)))
+            .collect::<Vec<_>>();
+        events.push((end_tick, Event::MetaEvent(MetaEvent::EndOfTrack)));
+        TrackChunk::from_absolute(events)
+    }
+}
+
+impl MidiFile {
+    pub fn notes(&self) -> Vec<Vec<Note>> {
+        self.tracks.iter().map(|track| track.notes()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::track::TrackChunk;
+
+    use super::Note;
+
+    #[test]
+    fn test_notes() {
+        let notes = vec![
+            Note {
+                channel: 0,
+                key: 60,
+                velocity: 100,
+                start: 0,
+                end: 96,
+            },
+            Note {
+                channel: 0,
+                key: 60,
+                velocity: 90,
+                start: 96,
+                end: 192,
+            },
+            Note {
+                channel: 1,
+                key: 64,
+                velocity: 80,
+                start: 48,
+                end: 48,
+            },
+        ];
+        let track = TrackChunk::from_notes(&notes);
+        let mut expected = notes.clone();
+        expected.sort_by_key(|note| (note.start, note.key));
+        assert_eq!(track.notes(), expected);
+        assert_eq!(track.end_tick(), 192);
+    }
+}
@@ -0,0 +1,165 @@
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => 0xedb88320 ^ (crc >> 1),
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+pub(crate) trait NpyElement: Copy {
+    const DESCR: &'static str;
+
+    fn write(self, output: &mut Vec<u8>);
+}
+
+impl NpyElement for u8 {
+    const DESCR: &'static str = "|u1";
+
+    fn write(self, output: &mut Vec<u8>) {
+        output.push(self);
+    }
+}
+
+impl NpyElement for u16 {
+    const DESCR: &'static str = "<u2";
+
+    fn write(self, output: &mut Vec<u8>) {
+        output.extend(self.to_le_bytes());
+    }
+}
+
+impl NpyElement for u32 {
+    const DESCR: &'static str = "<u4";
+
+    fn write(self, output: &mut Vec<u8>) {
+        output.extend(self.to_le_bytes());
+    }
+}
+
+impl NpyElement for u64 {
+    const DESCR: &'static str = "<u8";
+
+    fn write(self, output: &mut Vec<u8>) {
+        output.extend(self.to_le_bytes());
+    }
+}
+
+pub(crate) fn npy<T: NpyElement>(shape: &[usize], data: &[T]) -> Vec<u8> {
+    assert_eq!(shape.iter().product::<usize>(), data.len());
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        shape => format!(
+            "({})",
+            shape
+                .iter()
+                .map(|length| length.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR,
+        shape
+    );
+    // Padded so the data starts on a 64 byte boundary
+    let length = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - length % 64) % 64));
+    header.push('\n');
+    let mut output = b"\x93NUMPY\x01\x00".to_vec();
+    output.extend((header.len() as u16).to_le_bytes());
+    output.extend(header.as_bytes());
+    output.reserve(std::mem::size_of_val(data));
+    for value in data {
+        value.write(&mut output);
+    }
+    output
+}
+
+// An uncompressed zip archive of .npy files, as numpy.savez writes them
+pub(crate) fn npz(arrays: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut directory = Vec::new();
+    for (name, data) in arrays {
+        assert!(data.len() <= u32::MAX as usize && output.len() <= u32::MAX as usize);
+        let name = format!("{}.npy", name);
+        let offset = output.len() as u32;
+        let mut fields = Vec::new();
+        // Version 2.0, no flags, stored, and a timestamp of 1980-01-01
+        for value in [20u16, 0, 0, 0, 0x21] {
+            fields.extend(value.to_le_bytes());
+        }
+        fields.extend(crc32(data).to_le_bytes());
+        fields.extend((data.len() as u32).to_le_bytes());
+        fields.extend((data.len() as u32).to_le_bytes());
+        fields.extend((name.len() as u16).to_le_bytes());
+        fields.extend(0u16.to_le_bytes());
+
+        output.extend(0x04034b50u32.to_le_bytes());
+        output.extend(&fields);
+        output.extend(name.as_bytes());
+        output.extend(data);
+
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(20u16.to_le_bytes());
+        directory.extend(&fields);
+        // No comment, disk zero and no attributes
+        directory.extend([0; 6]);
+        directory.extend(0u32.to_le_bytes());
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+    }
+    let offset = output.len() as u32;
+    output.extend(&directory);
+    output.extend(0x06054b50u32.to_le_bytes());
+    output.extend([0; 4]);
+    output.extend((arrays.len() as u16).to_le_bytes());
+    output.extend((arrays.len() as u16).to_le_bytes());
+    output.extend((directory.len() as u32).to_le_bytes());
+    output.extend(offset.to_le_bytes());
+    output.extend(0u16.to_le_bytes());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, npy, npz};
+
+    #[test]
+    fn test_npy() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        let array = npy(&[2, 2], &[1u16, 2, 3, 4]);
+        assert!(array.starts_with(
+            b"\x93NUMPY\x01\x00\x76\x00{'descr': '<u2', 'fortran_order': False, 'shape': (2, 2), }"
+        ));
+        assert_eq!(array.len(), 128 + 8);
+        assert_eq!(array[127], b'\n');
+        assert_eq!(array[128..], [1, 0, 2, 0, 3, 0, 4, 0]);
+        assert!(npy(&[3], &[0u8; 3])
+            .windows(6)
+            .any(|window| window == b"(3,), "));
+
+        let archive = npz(&[("a", array.clone())]);
+        assert_eq!(archive[..4], [0x50, 0x4b, 0x03, 0x04]);
+        assert_eq!(archive[30..35], *b"a.npy");
+        assert_eq!(archive[35..35 + array.len()], array);
+        assert_eq!(archive.len(), 30 + 5 + array.len() + 46 + 5 + 22);
+    }
+}
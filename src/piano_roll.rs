@@ -0,0 +1,374 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+};
+
+use crate::{
+    channel_state::value_at,
+    event::midi_event::ChannelVoiceMessage,
+    general_midi::{DRUMS, PERCUSSION_CHANNEL},
+    midi_file::MidiFile,
+    note::Note,
+    numpy::{npy, npz},
+};
+
+const SUSTAIN: u8 = 0x40;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Resolution {
+    Ticks(u64),
+    Seconds(f64),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stacking {
+    Merged,
+    Track,
+    Channel,
+    Program,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PianoRollOptions {
+    pub resolution: Resolution,
+    pub stacking: Stacking,
+    pub binary: bool,
+    pub onsets_only: bool,
+    pub sustain: bool,
+}
+
+impl Default for PianoRollOptions {
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::Seconds(0.01),
+            stacking: Stacking::Merged,
+            binary: false,
+            onsets_only: false,
+            sustain: false,
+        }
+    }
+}
+
+// Layers of frames of 128 keys, with the nonzero cells as (layer, frame, key, value) in that order
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PianoRoll {
+    pub labels: Vec<u32>,
+    pub frames: usize,
+    pub cells: Vec<(usize, usize, u8, u8)>,
+}
+
+impl PianoRoll {
+    pub fn layers(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn get(&self, layer: usize, frame: usize, key: u8) -> u8 {
+        self.cells
+            .binary_search_by_key(&(layer, frame, key), |cell| (cell.0, cell.1, cell.2))
+            .map_or(0, |index| self.cells[index].3)
+    }
+
+    pub fn dense(&self) -> Vec<u8> {
+        let mut data = vec![0; self.layers() * self.frames * 128];
+        for (layer, frame, key, value) in self.cells.iter() {
+            data[(layer * self.frames + frame) * 128 + *key as usize] = *value;
+        }
+        data
+    }
+
+    pub fn to_npy(&self) -> Vec<u8> {
+        npy(&[self.layers(), self.frames, 128], &self.dense())
+    }
+
+    pub fn to_npz(&self) -> Vec<u8> {
+        npz(&[
+            ("roll", self.to_npy()),
+            ("labels", npy(&[self.layers()], &self.labels)),
+        ])
+    }
+
+    // Coordinate lists that scipy.sparse can take per layer
+    pub fn to_sparse_npz(&self) -> Vec<u8> {
+        let column = |index: fn(&(usize, usize, u8, u8)) -> u64| {
+            npy(
+                &[self.cells.len()],
+                &self.cells.iter().map(index).collect::<Vec<_>>(),
+            )
+        };
+        npz(&[
+            ("layer", column(|cell| cell.0 as u64)),
+            ("frame", column(|cell| cell.1 as u64)),
+            ("pitch", column(|cell| cell.2 as u64)),
+            ("value", column(|cell| cell.3 as u64)),
+            (
+                "shape",
+                npy(&[3], &[self.layers() as u64, self.frames as u64, 128]),
+            ),
+            ("labels", npy(&[self.layers()], &self.labels)),
+        ])
+    }
+}
+
+impl MidiFile {
+    pub fn piano_roll(&self, options: &PianoRollOptions) -> Option<PianoRoll> {
+        let valid = match options.resolution {
+            Resolution::Ticks(ticks) => ticks > 0,
+            Resolution::Seconds(seconds) => seconds > 0.0 && seconds.is_finite(),
+        };
+        if !valid {
+            return None;
+        }
+
+        let pedals = self.channel_changes(|message| match *message {
+            ChannelVoiceMessage::ControlChange {
+                controller: SUSTAIN,
+                value,
+                ..
+            } => Some(value >= 64),
+            _ => None,
+        });
+        let programs = self.program_changes();
+
+        let notes = self
+            .tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, track)| track.notes().into_iter().map(move |note| (index, note)))
+            .collect::<Vec<(usize, Note)>>();
+        let mut onsets: HashMap<(u8, u8), Vec<u64>> = HashMap::new();
+        for (_, note) in notes.iter() {
+            onsets
+                .entry((note.channel, note.key))
+                .or_default()
+                .push(note.start);
+        }
+        onsets.values_mut().for_each(|starts| starts.sort());
+
+        let label = |track: usize, note: &Note| match options.stacking {
+            Stacking::Merged => 0,
+            Stacking::Track => track as u32,
+            Stacking::Channel => note.channel as u32,
+            Stacking::Program if note.channel == PERCUSSION_CHANNEL => DRUMS as u32,
+            Stacking::Program => {
+                value_at(&programs[note.channel as usize], note.start).unwrap_or(0) as u32
+            }
+        };
+        let labels = match options.stacking {
+            Stacking::Merged => vec![0],
+            Stacking::Track => (0..self.tracks.len() as u32).collect(),
+            Stacking::Channel => (0..16).collect(),
+            Stacking::Program => notes
+                .iter()
+                .map(|(track, note)| label(*track, note))
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+
+        let end_tick = self
+            .tracks
+            .iter()
+            .map(|track| track.end_tick())
+            .max()
+            .unwrap_or(0);
+        let tempo_map = self.tempo_map();
+        let frame = |tick: u64| -> usize {
+            let frame = match options.resolution {
+                Resolution::Ticks(ticks) => tick as f64 / ticks as f64,
+                Resolution::Seconds(seconds) => tempo_map.tick_to_seconds(tick) / seconds,
+            };
+            frame.round() as usize
+        };
+        let mut spans = Vec::with_capacity(notes.len());
+        for (track, note) in notes.iter() {
+            let mut end = note.end;
+            // A held pedal keeps the note sounding until it is released or the key is struck again
+            if options.sustain && value_at(&pedals[note.channel as usize], end) == Some(true) {
+                let changes = &pedals[note.channel as usize];
+                let release = changes[changes.partition_point(|(tick, _)| *tick <= end)..]
+                    .iter()
+                    .find(|(_, down)| !down)
+                    .map_or(end_tick, |(tick, _)| *tick);
+                let starts = &onsets[&(note.channel, note.key)];
+                let next = starts[starts.partition_point(|start| *start <= note.start)..]
+                    .first()
+                    .copied()
+                    .unwrap_or(u64::MAX);
+                end = end.max(release.min(next));
+            }
+            let start = frame(note.start);
+            let end = match options.onsets_only {
+                true => start + 1,
+                false => frame(end).max(start + 1),
+            };
+            let layer = labels.binary_search(&label(*track, note)).unwrap();
+            spans.push((layer, start, end, note.key, note.velocity));
+        }
+
+        let frames = spans
+            .iter()
+            .map(|(_, _, end, _, _)| *end)
+            .max()
+            .unwrap_or(0)
+            .max(frame(end_tick));
+        // Overlapping notes on a key keep the loudest value
+        let mut cells = spans
+            .into_iter()
+            .flat_map(|(layer, start, end, key, velocity)| {
+                let value = if options.binary { 1 } else { velocity };
+                (start..end).map(move |frame| (layer, frame, key, value))
+            })
+            .collect::<Vec<_>>();
+        cells.sort_unstable_by_key(|cell| (cell.0, cell.1, cell.2, Reverse(cell.3)));
+        cells.dedup_by_key(|cell| (cell.0, cell.1, cell.2));
+        Some(PianoRoll {
+            labels,
+            frames,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{midi_event::ChannelVoiceMessage, Event},
+        general_midi::DRUMS,
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+    };
+
+    use super::{PianoRollOptions, Resolution, Stacking};
+
+    #[test]
+    fn test_piano_roll() {
+        let note = |channel, key, velocity, start, end| Note {
+            channel,
+            key,
+            velocity,
+            start,
+            end,
+        };
+        let mut piano = TrackChunk::from_notes(&[
+            note(0, 60, 100, 0, 24),
+            note(0, 64, 90, 24, 48),
+            note(0, 60, 80, 96, 120),
+        ])
+        .to_absolute();
+        piano.insert(
+            0,
+            (
+                0,
+                Event::from(ChannelVoiceMessage::ProgramChange {
+                    channel: 0,
+                    program: 4,
+                }),
+            ),
+        );
+        piano.insert(
+            1,
+            (
+                0,
+                Event::from(ChannelVoiceMessage::ControlChange {
+                    channel: 0,
+                    controller: 0x40,
+                    value: 127,
+                }),
+            ),
+        );
+        piano.insert(
+            piano.len() - 3,
+            (
+                72,
+                Event::from(ChannelVoiceMessage::ControlChange {
+                    channel: 0,
+                    controller: 0x40,
+                    value: 0,
+                }),
+            ),
+        );
+        let drums = TrackChunk::from_notes(&[note(9, 36, 127, 48, 60)]);
+        let midi_file = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![TrackChunk::from_absolute(piano), drums],
+        );
+
+        let options = PianoRollOptions {
+            resolution: Resolution::Ticks(12),
+            ..PianoRollOptions::default()
+        };
+        let roll = midi_file.piano_roll(&options).unwrap();
+        assert_eq!((roll.layers(), roll.frames), (1, 10));
+        assert_eq!(
+            (0..10)
+                .map(|frame| roll.get(0, frame, 60))
+                .collect::<Vec<_>>(),
+            [100, 100, 0, 0, 0, 0, 0, 0, 80, 80]
+        );
+        assert_eq!(roll.get(0, 4, 36), 127);
+
+        let sustained = midi_file
+            .piano_roll(&PianoRollOptions {
+                sustain: true,
+                binary: true,
+                ..options
+            })
+            .unwrap();
+        assert_eq!(
+            (0..10)
+                .map(|frame| sustained.get(0, frame, 64))
+                .collect::<Vec<_>>(),
+            [0, 0, 1, 1, 1, 1, 0, 0, 0, 0]
+        );
+
+        let stacked = midi_file
+            .piano_roll(&PianoRollOptions {
+                stacking: Stacking::Program,
+                onsets_only: true,
+                ..options
+            })
+            .unwrap();
+        assert_eq!(stacked.labels, [4, DRUMS as u32]);
+        assert_eq!(
+            stacked.cells,
+            [
+                (0, 0, 60, 100),
+                (0, 2, 64, 90),
+                (0, 8, 60, 80),
+                (1, 4, 36, 127)
+            ]
+        );
+
+        let seconds = midi_file
+            .piano_roll(&PianoRollOptions {
+                resolution: Resolution::Seconds(0.125),
+                stacking: Stacking::Track,
+                ..PianoRollOptions::default()
+            })
+            .unwrap();
+        assert_eq!((seconds.layers(), seconds.frames), (2, 5));
+        assert_eq!(seconds.get(1, 2, 36), 127);
+        assert_eq!(seconds.to_npy().len(), 128 + 2 * 5 * 128);
+        assert_eq!(
+            seconds.dense().iter().filter(|value| **value > 0).count(),
+            seconds.cells.len()
+        );
+        assert!(seconds
+            .to_npz()
+            .windows(8)
+            .any(|window| window == b"roll.npy"));
+
+        for resolution in [Resolution::Ticks(0), Resolution::Seconds(0.0)] {
+            assert_eq!(
+                midi_file.piano_roll(&PianoRollOptions {
+                    resolution,
+                    ..options
+                }),
+                None
+            );
+        }
+    }
+}
@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+
+use crate::{
+    channel_state::{Channel, ChannelState},
+    controller::ControllerEvent,
+    event::{
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        sysex_event::SysExEvent,
+        Event,
+    },
+    general_midi::PERCUSSION_CHANNEL,
+    sysex::mts::{NoteTuning, TuningMessage},
+    track::TrackChunk,
+};
+
+fn data_lines(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.starts_with('!'))
+}
+
+fn parse_pitch(value: &str) -> Option<f64> {
+    if value.contains('.') {
+        return value.parse().ok();
+    }
+    let ratio = match value.split_once('/') {
+        Some((numerator, denominator)) => {
+            numerator.parse::<f64>().ok()? / denominator.parse::<f64>().ok()?
+        }
+        None => value.parse::<f64>().ok()?,
+    };
+    if ratio <= 0.0 || !ratio.is_finite() {
+        return None;
+    }
+    Some(1200.0 * ratio.log2())
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scale {
+    pub description: String,
+    pub pitches: Vec<f64>,
+}
+
+impl Scale {
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut lines = data_lines(input);
+        let description = lines.next()?.trim().to_string();
+        let length = lines
+            .next()?
+            .split_whitespace()
+            .next()?
+            .parse::<usize>()
+            .ok()?;
+        let pitches = lines
+            .filter(|line| !line.trim().is_empty())
+            .take(length)
+            .map(|line| parse_pitch(line.split_whitespace().next()?))
+            .collect::<Option<Vec<_>>>()?;
+        if pitches.len() != length || length == 0 {
+            return None;
+        }
+        Some(Self {
+            description,
+            pitches,
+        })
+    }
+
+    pub fn equal_temperament(notes: usize) -> Self {
+        assert!(notes > 0);
+        Self {
+            description: format!("{} tone equal temperament", notes),
+            pitches: (1..=notes)
+                .map(|note| 1200.0 * note as f64 / notes as f64)
+                .collect(),
+        }
+    }
+
+    pub fn period(&self) -> f64 {
+        *self.pitches.last().unwrap()
+    }
+
+    pub fn degree_cents(&self, degree: i64) -> f64 {
+        let length = self.pitches.len() as i64;
+        let octave = degree.div_euclid(length);
+        let step = degree.rem_euclid(length);
+        let cents = match step {
+            0 => 0.0,
+            step => self.pitches[step as usize - 1],
+        };
+        octave as f64 * self.period() + cents
+    }
+
+    pub fn frequencies(&self, mapping: &KeyboardMapping) -> [Option<f64>; 128] {
+        let mut frequencies = [None; 128];
+        let reference = self.degree_cents(
+            mapping
+                .degree(mapping.reference_note, self)
+                .unwrap_or(mapping.reference_note as i64 - mapping.middle_note as i64),
+        );
+        for key in mapping.first_note..=mapping.last_note.min(127) {
+            frequencies[key as usize] = mapping.degree(key, self).map(|degree| {
+                mapping.reference_frequency
+                    * 2f64.powf((self.degree_cents(degree) - reference) / 1200.0)
+            });
+        }
+        frequencies
+    }
+
+    pub fn tuning_message(
+        &self,
+        mapping: &KeyboardMapping,
+        device_id: u8,
+        program: u8,
+    ) -> TuningMessage {
+        TuningMessage::BulkDump {
+            device_id,
+            program,
+            name: self.description.chars().take(16).collect(),
+            tunings: self
+                .frequencies(mapping)
+                .iter()
+                .map(|frequency| frequency.map(NoteTuning::from_frequency))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeyboardMapping {
+    pub first_note: u8,
+    pub last_note: u8,
+    pub middle_note: u8,
+    pub reference_note: u8,
+    pub reference_frequency: f64,
+    pub octave_degree: usize,
+    pub mapping: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn linear(middle_note: u8, reference_note: u8, reference_frequency: f64) -> Self {
+        Self {
+            middle_note,
+            reference_note,
+            reference_frequency,
+            ..Self::default()
+        }
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut lines = data_lines(input)
+            .map(|line| line.split_whitespace().next().unwrap_or(""))
+            .filter(|value| !value.is_empty());
+        let size = lines.next()?.parse::<usize>().ok()?;
+        let mut note = || lines.next()?.parse::<u8>().ok().filter(|note| *note < 128);
+        let first_note = note()?;
+        let last_note = note()?;
+        let middle_note = note()?;
+        let reference_note = note()?;
+        let reference_frequency = lines.next()?.parse::<f64>().ok()?;
+        let octave_degree = lines.next()?.parse::<usize>().ok()?;
+        let mut mapping = Vec::with_capacity(size);
+        for _ in 0..size {
+            // Trailing entries may be omitted and are then unmapped
+            let entry = match lines.next() {
+                None | Some("x") => None,
+                Some(value) => Some(value.parse::<usize>().ok()?),
+            };
+            mapping.push(entry);
+        }
+        Some(Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    pub fn degree(&self, key: u8, scale: &Scale) -> Option<i64> {
+        let offset = key as i64 - self.middle_note as i64;
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+        let size = self.mapping.len() as i64;
+        let octave_degree = match self.octave_degree {
+            0 => scale.pitches.len(),
+            degree => degree,
+        } as i64;
+        let step = self.mapping[offset.rem_euclid(size) as usize]? as i64;
+        Some(offset.div_euclid(size) * octave_degree + step)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Voice {
+    channel: u8,
+    source: Option<(u8, u8)>,
+    note: u8,
+    detune: f64,
+    started: usize,
+}
+
+// Data entry and parameter numbers stay with the voice channels, they set the retuning range
+fn is_parameter_controller(controller: u8) -> bool {
+    matches!(controller, 0x06 | 0x26 | 0x60..=0x65)
+}
+
+// Messages that bring a voice channel to the program and controllers of a source channel
+fn sync_messages(source: &Channel, voice: &Channel, channel: u8) -> Vec<ChannelVoiceMessage> {
+    let mut messages = Vec::new();
+    if (source.bank(), source.program) != (voice.bank(), voice.program) {
+        messages.extend([
+            ChannelVoiceMessage::ControlChange {
+                channel,
+                controller: 0x00,
+                value: source.bank_select_msb(),
+            },
+            ChannelVoiceMessage::ControlChange {
+                channel,
+                controller: 0x20,
+                value: source.bank_select_lsb(),
+            },
+            ChannelVoiceMessage::ProgramChange {
+                channel,
+                program: source.program,
+            },
+        ]);
+    }
+    for controller in 0x01..0x78 {
+        let value = source.controllers[controller as usize];
+        if controller != 0x20
+            && !is_parameter_controller(controller)
+            && value != voice.controllers[controller as usize]
+        {
+            messages.push(ChannelVoiceMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            });
+        }
+    }
+    if source.channel_pressure != voice.channel_pressure {
+        messages.push(ChannelVoiceMessage::ChannelPressure {
+            channel,
+            pressure: source.channel_pressure,
+        });
+    }
+    messages
+}
+
+fn emit(
+    events: &mut Vec<(u64, Event)>,
+    outputs: &mut ChannelState,
+    tick: u64,
+    message: ChannelVoiceMessage,
+) {
+    outputs.process(&MidiMessage::from(message));
+    events.push((tick, Event::from(message)));
+}
+
+impl TrackChunk {
+    pub fn insert_tuning(&mut self, message: &TuningMessage) {
+        let mut events = self.to_absolute();
+        events.insert(
+            0,
+            (
+                0,
+                Event::SysExEvent(SysExEvent::from_payload(&message.to_bytes())),
+            ),
+        );
+        *self = TrackChunk::from_absolute(events);
+    }
+
+    pub fn retune_with_pitch_bend(
+        &self,
+        frequencies: &[Option<f64>; 128],
+        channels: &[u8],
+        sensitivity: u8,
+    ) -> TrackChunk {
+        assert!(!channels.is_empty(), "No channels to allocate voices to");
+        assert!(sensitivity > 0, "Invalid pitch bend sensitivity");
+        channels.iter().for_each(|channel| {
+            assert!(
+                *channel < 16 && *channel != PERCUSSION_CHANNEL,
+                "Invalid channel: {}",
+                channel
+            )
+        });
+        let mut events = Vec::new();
+        let mut sources = ChannelState::new();
+        let mut outputs = ChannelState::new();
+        for channel in channels {
+            let setup = ControllerEvent::PitchBendSensitivity {
+                channel: *channel,
+                semitones: sensitivity,
+                cents: 0,
+            };
+            for message in setup.to_messages() {
+                emit(&mut events, &mut outputs, 0, message);
+            }
+        }
+        let mut voices = channels
+            .iter()
+            .map(|channel| Voice {
+                channel: *channel,
+                source: None,
+                note: 0,
+                detune: 0.0,
+                started: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut sounding: HashMap<(u8, u8), usize> = HashMap::new();
+        for (index, (tick, event)) in self.to_absolute().into_iter().enumerate() {
+            let message = match &event {
+                Event::MidiEvent(MidiMessage::ChannelMessage(
+                    ChannelMessage::ChannelVoiceMessage(message),
+                )) if message.get_channel() != PERCUSSION_CHANNEL => *message,
+                _ => {
+                    events.push((tick, event));
+                    continue;
+                }
+            };
+            sources.process(&MidiMessage::from(message));
+            match message {
+                ChannelVoiceMessage::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                } if velocity > 0 => {
+                    let Some(frequency) = frequencies[note as usize] else {
+                        continue;
+                    };
+                    let exact = 69.0 + 12.0 * (frequency / 440.0).log2();
+                    let key = exact.round().clamp(0.0, 127.0);
+                    if let Some(previous) = sounding.remove(&(channel, note)) {
+                        let previous = &mut voices[previous];
+                        previous.source = None;
+                        emit(
+                            &mut events,
+                            &mut outputs,
+                            tick,
+                            ChannelVoiceMessage::NoteOff {
+                                channel: previous.channel,
+                                note: previous.note,
+                                velocity: 0,
+                            },
+                        );
+                    }
+                    let slot = voices
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, voice)| (voice.source.is_some(), voice.started))
+                        .map(|(slot, _)| slot)
+                        .unwrap();
+                    let stolen = voices[slot];
+                    if let Some(source) = stolen.source {
+                        sounding.remove(&source);
+                        emit(
+                            &mut events,
+                            &mut outputs,
+                            tick,
+                            ChannelVoiceMessage::NoteOff {
+                                channel: stolen.channel,
+                                note: stolen.note,
+                                velocity: 0,
+                            },
+                        );
+                    }
+                    sounding.insert((channel, note), slot);
+                    voices[slot] = Voice {
+                        channel: stolen.channel,
+                        source: Some((channel, note)),
+                        note: key as u8,
+                        detune: exact - key,
+                        started: index,
+                    };
+                    let source = sources.channel(channel);
+                    let sync =
+                        sync_messages(source, outputs.channel(stolen.channel), stolen.channel);
+                    for message in sync {
+                        emit(&mut events, &mut outputs, tick, message);
+                    }
+                    emit(
+                        &mut events,
+                        &mut outputs,
+                        tick,
+                        ChannelVoiceMessage::pitch_bend_from_semitones(
+                            stolen.channel,
+                            exact - key + source.pitch_bend_semitones(),
+                            sensitivity as f64,
+                        ),
+                    );
+                    emit(
+                        &mut events,
+                        &mut outputs,
+                        tick,
+                        ChannelVoiceMessage::NoteOn {
+                            channel: stolen.channel,
+                            note: key as u8,
+                            velocity,
+                        },
+                    );
+                }
+                ChannelVoiceMessage::NoteOn { channel, note, .. }
+                | ChannelVoiceMessage::NoteOff { channel, note, .. } => {
+                    if let Some(slot) = sounding.remove(&(channel, note)) {
+                        let voice_state = &mut voices[slot];
+                        voice_state.source = None;
+                        emit(
+                            &mut events,
+                            &mut outputs,
+                            tick,
+                            ChannelVoiceMessage::NoteOff {
+                                channel: voice_state.channel,
+                                note: voice_state.note,
+                                velocity: match message {
+                                    ChannelVoiceMessage::NoteOff { velocity, .. } => velocity,
+                                    _ => 0,
+                                },
+                            },
+                        );
+                    }
+                }
+                ChannelVoiceMessage::PolyphonicKeyPressure {
+                    channel,
+                    note,
+                    pressure,
+                } => {
+                    if let Some(slot) = sounding.get(&(channel, note)) {
+                        emit(
+                            &mut events,
+                            &mut outputs,
+                            tick,
+                            ChannelVoiceMessage::PolyphonicKeyPressure {
+                                channel: voices[*slot].channel,
+                                note: voices[*slot].note,
+                                pressure,
+                            },
+                        );
+                    }
+                }
+                // A source bend moves every voice it holds on top of their retuning
+                ChannelVoiceMessage::PitchBendChange { channel, .. } => {
+                    let bend = sources.channel(channel).pitch_bend_semitones();
+                    for voice in voices
+                        .iter()
+                        .filter(|voice| voice.source.map(|(source, _)| source) == Some(channel))
+                    {
+                        emit(
+                            &mut events,
+                            &mut outputs,
+                            tick,
+                            ChannelVoiceMessage::pitch_bend_from_semitones(
+                                voice.channel,
+                                voice.detune + bend,
+                                sensitivity as f64,
+                            ),
+                        );
+                    }
+                }
+                ChannelVoiceMessage::ControlChange { controller, .. }
+                    if is_parameter_controller(controller) => {}
+                // Other voices catch up with the source channel when they take one of its notes
+                _ => {
+                    let channel = message.get_channel();
+                    for voice in voices
+                        .iter()
+                        .filter(|voice| voice.source.map(|(source, _)| source) == Some(channel))
+                    {
+                        let mut message = message;
+                        message.set_channel(voice.channel);
+                        emit(&mut events, &mut outputs, tick, message);
+                    }
+                }
+            }
+        }
+        events.sort_by_key(|(tick, _)| *tick);
+        TrackChunk::from_absolute(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{
+            meta_event::MetaEvent,
+            midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+            Event,
+        },
+        sysex::mts::TuningMessage,
+        track::TrackChunk,
+    };
+
+    use super::{KeyboardMapping, Scale};
+
+    const SCALE: &str = "! meantone.scl
+!
+Quarter-comma meantone
+ 5
+!
+ 193.157
+ 5/4
+ 3/2
+ 1788.0 comment
+ 2/1
+";
+
+    const MAPPING: &str = "! white.kbm
+6
+0
+127
+60
+69
+440.0
+5
+! mapping
+0
+x
+1
+2
+x
+";
+
+    #[test]
+    fn test_parse() {
+        let scale = Scale::parse(SCALE).unwrap();
+        assert_eq!(scale.description, "Quarter-comma meantone");
+        assert_eq!(scale.pitches.len(), 5);
+        assert!((scale.pitches[1] - 386.3137).abs() < 1e-3);
+        assert_eq!(scale.period(), 1200.0);
+        assert!(Scale::parse("Broken\n3\n100.0\n").is_none());
+
+        let mapping = KeyboardMapping::parse(MAPPING).unwrap();
+        assert_eq!(
+            mapping.mapping,
+            [Some(0), None, Some(1), Some(2), None, None]
+        );
+        assert_eq!(mapping.degree(61, &scale), None);
+        assert_eq!(mapping.degree(66, &scale), Some(5));
+        assert_eq!(mapping.degree(59, &scale), None);
+        assert_eq!(mapping.degree(56, &scale), Some(-4));
+
+        let frequencies = Scale::equal_temperament(12).frequencies(&KeyboardMapping::default());
+        assert!((frequencies[69].unwrap() - 440.0).abs() < 1e-9);
+        assert!((frequencies[60].unwrap() - 261.6256).abs() < 1e-3);
+
+        let message = scale.tuning_message(&KeyboardMapping::default(), 0x7f, 0);
+        assert_eq!(
+            TuningMessage::parse(&message.to_bytes()).unwrap().1,
+            message
+        );
+    }
+
+    #[test]
+    fn test_retune_with_pitch_bend() {
+        let note = |tick, note, velocity| {
+            (
+                tick,
+                Event::from(ChannelVoiceMessage::NoteOn {
+                    channel: 0,
+                    note,
+                    velocity,
+                }),
+            )
+        };
+        let track = TrackChunk::from_absolute(vec![
+            (
+                0,
+                Event::from(ChannelVoiceMessage::ProgramChange {
+                    channel: 0,
+                    program: 5,
+                }),
+            ),
+            note(0, 60, 100),
+            note(0, 64, 100),
+            (
+                5,
+                Event::from(ChannelVoiceMessage::pitch_bend_from_semitones(0, 1.0, 2.0)),
+            ),
+            (
+                5,
+                Event::from(ChannelVoiceMessage::ControlChange {
+                    channel: 1,
+                    controller: 0x07,
+                    value: 90,
+                }),
+            ),
+            note(10, 60, 0),
+            note(10, 64, 0),
+            (20, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let mut frequencies = Scale::equal_temperament(12).frequencies(&KeyboardMapping::default());
+        frequencies[64] = Some(frequencies[64].unwrap() * 2f64.powf(-14.0 / 1200.0));
+        let retuned = track.retune_with_pitch_bend(&frequencies, &[0, 1], 2);
+        let messages = retuned
+            .to_absolute()
+            .into_iter()
+            .filter_map(|(tick, event)| match event {
+                Event::MidiEvent(MidiMessage::ChannelMessage(
+                    ChannelMessage::ChannelVoiceMessage(message),
+                )) if tick > 0 || !matches!(message, ChannelVoiceMessage::ControlChange { .. }) => {
+                    Some((tick, message))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // The source program follows each voice, and its bend adds to the retuning
+        assert_eq!(
+            messages,
+            [
+                (
+                    0,
+                    ChannelVoiceMessage::ProgramChange {
+                        channel: 0,
+                        program: 5
+                    }
+                ),
+                (0, ChannelVoiceMessage::pitch_bend_from_centered(0, 0)),
+                (
+                    0,
+                    ChannelVoiceMessage::NoteOn {
+                        channel: 0,
+                        note: 60,
+                        velocity: 100
+                    }
+                ),
+                (
+                    0,
+                    ChannelVoiceMessage::ProgramChange {
+                        channel: 1,
+                        program: 5
+                    }
+                ),
+                (0, ChannelVoiceMessage::pitch_bend_from_cents(1, -14.0, 2.0)),
+                (
+                    0,
+                    ChannelVoiceMessage::NoteOn {
+                        channel: 1,
+                        note: 64,
+                        velocity: 100
+                    }
+                ),
+                (5, ChannelVoiceMessage::pitch_bend_from_cents(0, 100.0, 2.0)),
+                (5, ChannelVoiceMessage::pitch_bend_from_cents(1, 86.0, 2.0)),
+                (
+                    10,
+                    ChannelVoiceMessage::NoteOff {
+                        channel: 0,
+                        note: 60,
+                        velocity: 0
+                    }
+                ),
+                (
+                    10,
+                    ChannelVoiceMessage::NoteOff {
+                        channel: 1,
+                        note: 64,
+                        velocity: 0
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::prelude::*;
+use walkdir::WalkDir;
+
+use crate::{fingerprint::Fingerprint, midi_file::MidiFile};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ScanOptions {
+    pub extensions: Vec<String>,
+    // Scan a random sample of this many files instead of the whole tree
+    pub sample: Option<usize>,
+    // Zero uses one thread per available core
+    pub threads: usize,
+    pub round_trip: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            extensions: ["mid", "midi", "kar", "smf"]
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            sample: None,
+            threads: 0,
+            round_trip: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Outcome {
+    Ok,
+    // The kind is a nom error kind, Incomplete, Io or Panic, the offset is where parsing stopped
+    Error {
+        kind: String,
+        offset: Option<usize>,
+        message: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub size: u64,
+    pub outcome: Outcome,
+    pub warnings: Vec<String>,
+    pub parse_time: Duration,
+    pub format: Option<u16>,
+    pub tracks: usize,
+    pub events: usize,
+    pub end_tick: u64,
+    pub seconds: f64,
+    pub fingerprint: Option<Fingerprint>,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScanReport {
+    pub files: Vec<FileReport>,
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or_else(String::new, |message| message.to_string()),
+    }
+}
+
+fn error(kind: &str, offset: Option<usize>, message: String) -> Outcome {
+    Outcome::Error {
+        kind: kind.to_string(),
+        offset,
+        message,
+    }
+}
+
+pub fn scan_data(path: &Path, data: &[u8], round_trip: bool) -> FileReport {
+    let mut report = FileReport {
+        path: path.to_path_buf(),
+        size: data.len() as u64,
+        outcome: Outcome::Ok,
+        warnings: Vec::new(),
+        parse_time: Duration::ZERO,
+        format: None,
+        tracks: 0,
+        events: 0,
+        end_tick: 0,
+        seconds: 0.0,
+        fingerprint: None,
+    };
+    let start = Instant::now();
+    // The parser asserts on some malformed chunks, which must not take down the whole scan
+    let parsed = panic::catch_unwind(|| MidiFile::parse(data));
+    report.parse_time = start.elapsed();
+    let (remaining, midi_file) = match parsed {
+        Ok(Ok(parsed)) => parsed,
+        Ok(Err(nom::Err::Incomplete(_))) => {
+            report.outcome = error("Incomplete", Some(data.len()), String::new());
+            return report;
+        }
+        Ok(Err(nom::Err::Error(e) | nom::Err::Failure(e))) => {
+            report.outcome = error(
+                &format!("{:?}", e.code),
+                // The error input is a slice of data, also inside a track chunk
+                Some(e.input.as_ptr() as usize - data.as_ptr() as usize),
+                String::new(),
+            );
+            return report;
+        }
+        Err(payload) => {
+            report.outcome = error("Panic", None, panic_message(payload));
+            return report;
+        }
+    };
+
+    report.format = Some(midi_file.header.data.format);
+    report.tracks = midi_file.tracks.len();
+    report.events = midi_file.tracks.iter().map(|track| track.data.len()).sum();
+    if !remaining.is_empty() {
+        report
+            .warnings
+            .push(format!("{} bytes after the last track", remaining.len()));
+    }
+    if midi_file.header.data.format == 0 && midi_file.tracks.len() != 1 {
+        report
+            .warnings
+            .push(format!("format 0 with {} tracks", midi_file.tracks.len()));
+    }
+    // A panic in one of the analyses leaves the file readable, so it is only a warning
+    let end_tick = midi_file
+        .tracks
+        .iter()
+        .map(|track| track.end_tick())
+        .max()
+        .unwrap_or(0);
+    report.end_tick = end_tick;
+    if let Some(seconds) = analyze(&mut report, "tempo map", || {
+        midi_file.tempo_map().tick_to_seconds(end_tick)
+    }) {
+        report.seconds = seconds;
+    }
+    report.fingerprint = analyze(&mut report, "fingerprint", || midi_file.fingerprint());
+    if round_trip {
+        let matches = analyze(&mut report, "round trip", || {
+            MidiFile::parse(&midi_file.to_bytes()).is_ok_and(|(_, reopened)| reopened == midi_file)
+        });
+        if matches == Some(false) {
+            report.warnings.push("round trip mismatch".to_string());
+        }
+    }
+    report
+}
+
+fn analyze<T>(report: &mut FileReport, name: &str, f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            report
+                .warnings
+                .push(format!("{} panicked: {}", name, panic_message(payload)));
+            None
+        }
+    }
+}
+
+pub fn scan_file(path: &Path, round_trip: bool) -> FileReport {
+    match std::fs::read(path) {
+        Ok(data) => scan_data(path, &data, round_trip),
+        Err(e) => {
+            let mut report = scan_data(path, &[], round_trip);
+            report.outcome = error("Io", None, e.to_string());
+            report
+        }
+    }
+}
+
+pub fn collect_files(root: &Path, options: &ScanOptions) -> Vec<PathBuf> {
+    let files = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && entry.path().extension().is_some_and(|extension| {
+                    options
+                        .extensions
+                        .iter()
+                        .any(|wanted| extension.eq_ignore_ascii_case(wanted))
+                })
+        })
+        .map(|entry| entry.into_path())
+        .collect::<Vec<_>>();
+    match options.sample {
+        Some(size) => {
+            let mut sample = files
+                .choose_multiple(&mut thread_rng(), size)
+                .cloned()
+                .collect::<Vec<_>>();
+            sample.sort();
+            sample
+        }
+        None => files,
+    }
+}
+
+// Reports come back in the order of the paths, whichever thread scanned them
+pub fn scan_files(
+    paths: &[PathBuf],
+    options: &ScanOptions,
+    progress: &(dyn Fn(&FileReport) + Sync),
+) -> ScanReport {
+    let threads = match options.threads {
+        0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+        threads => threads,
+    }
+    .clamp(1, paths.len().max(1));
+    let next = AtomicUsize::new(0);
+    let mut reports = thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut reports = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else {
+                            break;
+                        };
+                        let report = scan_file(path, options.round_trip);
+                        progress(&report);
+                        reports.push((index, report));
+                    }
+                    reports
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    reports.sort_by_key(|(index, _)| *index);
+    ScanReport {
+        files: reports.into_iter().map(|(_, report)| report).collect(),
+    }
+}
+
+pub fn scan_directory(root: &Path, options: &ScanOptions) -> ScanReport {
+    scan_files(&collect_files(root, options), options, &|_| {})
+}
+
+fn json_string(text: &str) -> String {
+    let mut output = String::from('"');
+    for character in text.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                output.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => output.push(character),
+        }
+    }
+    output.push('"');
+    output
+}
+
+fn json_number(value: f64) -> String {
+    match value.is_finite() {
+        true => value.to_string(),
+        false => "null".to_string(),
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+impl FileReport {
+    pub fn is_ok(&self) -> bool {
+        self.outcome == Outcome::Ok
+    }
+
+    fn error_fields(&self) -> (&str, Option<usize>, &str) {
+        match &self.outcome {
+            Outcome::Ok => ("", None, ""),
+            Outcome::Error {
+                kind,
+                offset,
+                message,
+            } => (kind, *offset, message),
+        }
+    }
+
+    fn hashes(&self) -> Option<(String, String)> {
+        self.fingerprint.map(|fingerprint| {
+            (
+                format!("{:016x}", fingerprint.exact),
+                format!("{:016x}", fingerprint.simhash),
+            )
+        })
+    }
+}
+
+impl ScanReport {
+    pub fn errors(&self) -> usize {
+        self.files.iter().filter(|file| !file.is_ok()).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| !file.warnings.is_empty())
+            .count()
+    }
+
+    pub fn to_json(&self) -> String {
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let (kind, offset, message) = file.error_fields();
+                let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+                format!(
+                    "    {{\"path\": {}, \"size\": {}, \"status\": {}, \"error\": {}, \"offset\": {}, \
+                     \"message\": {}, \"warnings\": [{}], \"parse_micros\": {}, \"format\": {}, \
+                     \"tracks\": {}, \"events\": {}, \"end_tick\": {}, \"seconds\": {}, \"fingerprint\": {}, \"simhash\": {}}}",
+                    json_string(&file.path.to_string_lossy()),
+                    file.size,
+                    json_string(if file.is_ok() { "ok" } else { "error" }),
+                    optional((!file.is_ok()).then(|| json_string(kind))),
+                    optional(offset.map(|offset| offset.to_string())),
+                    optional((!file.is_ok()).then(|| json_string(message))),
+                    file.warnings
+                        .iter()
+                        .map(|warning| json_string(warning))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    file.parse_time.as_micros(),
+                    optional(file.format.map(|format| format.to_string())),
+                    file.tracks,
+                    file.events,
+                    file.end_tick,
+                    json_number(file.seconds),
+                    optional(file.hashes().map(|(exact, _)| json_string(&exact))),
+                    optional(file.hashes().map(|(_, simhash)| json_string(&simhash)))
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\n  \"summary\": {{\"files\": {}, \"ok\": {}, \"errors\": {}, \"warnings\": {}}},\n  \
+             \"files\": [\n{}{}  ]\n}}\n",
+            self.files.len(),
+            self.files.len() - self.errors(),
+            self.errors(),
+            self.warnings(),
+            files.join(",\n"),
+            if files.is_empty() { "" } else { "\n" }
+        )
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut output = String::from(
+            "path,size,status,error,offset,message,warnings,parse_micros,format,tracks,events,end_tick,seconds,fingerprint,simhash\n",
+        );
+        for file in self.files.iter() {
+            let (kind, offset, message) = file.error_fields();
+            let fields = [
+                csv_field(&file.path.to_string_lossy()),
+                file.size.to_string(),
+                if file.is_ok() { "ok" } else { "error" }.to_string(),
+                csv_field(kind),
+                offset.map_or_else(String::new, |offset| offset.to_string()),
+                csv_field(message),
+                csv_field(&file.warnings.join("; ")),
+                file.parse_time.as_micros().to_string(),
+                file.format
+                    .map_or_else(String::new, |format| format.to_string()),
+                file.tracks.to_string(),
+                file.events.to_string(),
+                file.end_tick.to_string(),
+                file.seconds.to_string(),
+                file.hashes().map_or_else(String::new, |(exact, _)| exact),
+                file.hashes()
+                    .map_or_else(String::new, |(_, simhash)| simhash),
+            ];
+            output.push_str(&fields.join(","));
+            output.push('\n');
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{header::Division, midi_file::MidiFile, note::Note, track::TrackChunk};
+
+    use super::{analyze, collect_files, scan_data, scan_files, Outcome, ScanOptions};
+
+    #[test]
+    fn test_scan() {
+        let track = TrackChunk::from_notes(&[Note {
+            channel: 0,
+            key: 60,
+            velocity: 100,
+            start: 0,
+            end: 192,
+        }]);
+        let data =
+            MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 96 }, vec![track]).to_bytes();
+
+        let report = scan_data(Path::new("ok.mid"), &data, true);
+        assert!(report.is_ok() && report.warnings.is_empty());
+        assert_eq!(
+            (report.format, report.tracks, report.events, report.end_tick),
+            (Some(0), 1, 3, 192)
+        );
+        assert_eq!(report.seconds, 1.0);
+        assert_eq!(
+            report.fingerprint,
+            Some(MidiFile::parse(&data).unwrap().1.fingerprint())
+        );
+
+        let truncated = scan_data(Path::new("truncated.mid"), &data[..20], true);
+        // The track header is cut off in its length field
+        assert_eq!(
+            truncated.outcome,
+            Outcome::Error {
+                kind: "Eof".to_string(),
+                offset: Some(18),
+                message: String::new(),
+            }
+        );
+        // A text length running past the end of the first of two tracks, which ends at byte 34
+        let broken = [
+            &data[..10],
+            &[0, 2],
+            &data[12..14],
+            b"MTrk",
+            &[0, 0, 0, 12, 0x00, 0xff, 0x03, 0x10],
+            b"abcd",
+            &[0x00, 0xff, 0x2f, 0x00],
+            &data[14..],
+        ]
+        .concat();
+        assert_eq!(
+            scan_data(Path::new("broken.mid"), &broken, true).outcome,
+            Outcome::Error {
+                kind: "Eof".to_string(),
+                offset: Some(26),
+                message: String::new(),
+            }
+        );
+        let trailing = scan_data(
+            Path::new("trailing.mid"),
+            &[&data[..], b"junk"].concat(),
+            true,
+        );
+        assert_eq!(trailing.warnings, ["4 bytes after the last track"]);
+
+        let mut failed = scan_data(Path::new("failed.mid"), &data, false);
+        assert_eq!(
+            analyze(&mut failed, "fingerprint", || panic!("boom")),
+            None::<()>
+        );
+        assert!(failed.is_ok());
+        assert_eq!(failed.warnings, ["fingerprint panicked: boom"]);
+
+        let directory = std::env::temp_dir().join(format!("midi-scan-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("nested")).unwrap();
+        std::fs::write(directory.join("a.mid"), &data).unwrap();
+        std::fs::write(directory.join("nested").join("b.MIDI"), &data[..20]).unwrap();
+        std::fs::write(directory.join("notes.txt"), "not midi").unwrap();
+        let options = ScanOptions {
+            threads: 2,
+            ..ScanOptions::default()
+        };
+        let paths = collect_files(&directory, &options);
+        let report = scan_files(&paths, &options, &|_| {});
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(report.files.len(), 2);
+        assert_eq!((report.errors(), report.warnings()), (1, 0));
+        assert!(report.files[0].path.ends_with("a.mid"));
+
+        let csv = report.to_csv();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.lines().nth(2).unwrap().contains(",error,Eof,18,,"));
+        let json = report.to_json();
+        assert!(
+            json.contains("\"summary\": {\"files\": 2, \"ok\": 1, \"errors\": 1, \"warnings\": 0}")
+        );
+        assert!(json.contains("\"status\": \"error\", \"error\": \"Eof\", \"offset\": 18"));
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    event::{
+        meta_event::MetaEvent,
+        midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+        Event,
+    },
+    midi_file::MidiFile,
+    timing::TimePosition,
+    track::TrackChunk,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BoundaryPolicy {
+    #[default]
+    Truncate,
+    Drop,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum StateKey {
+    Tempo,
+    TimeSignature,
+    KeySignature,
+    TrackName,
+    InstrumentName,
+    Program(u8),
+    Controller(u8, u8),
+    PitchBend(u8),
+    ChannelPressure(u8),
+}
+
+fn voice_message(event: &Event) -> Option<&ChannelVoiceMessage> {
+    match event {
+        Event::MidiEvent(MidiMessage::ChannelMessage(ChannelMessage::ChannelVoiceMessage(
+            message,
+        ))) => Some(message),
+        _ => None,
+    }
+}
+
+fn note_off(channel: u8, note: u8) -> Event {
+    Event::from(ChannelVoiceMessage::NoteOff {
+        channel,
+        note,
+        velocity: 0,
+    })
+}
+
+fn pair_notes(events: &[(u64, Event)]) -> HashMap<usize, (usize, u64)> {
+    let mut pending: HashMap<(u8, u8), VecDeque<usize>> = HashMap::new();
+    let mut pairs = HashMap::new();
+    for (index, (tick, event)) in events.iter().enumerate() {
+        match voice_message(event) {
+            Some(ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            }) if *velocity > 0 => {
+                pending
+                    .entry((*channel, *note))
+                    .or_default()
+                    .push_back(index);
+            }
+            Some(
+                ChannelVoiceMessage::NoteOn { channel, note, .. }
+                | ChannelVoiceMessage::NoteOff { channel, note, .. },
+            ) => {
+                if let Some(on) = pending
+                    .get_mut(&(*channel, *note))
+                    .and_then(|queue| queue.pop_front())
+                {
+                    pairs.insert(on, (index, *tick));
+                }
+            }
+            _ => {}
+        }
+    }
+    pairs
+}
+
+impl TrackChunk {
+    pub fn slice(&self, start: u64, end: u64, policy: BoundaryPolicy) -> TrackChunk {
+        assert!(start <= end);
+        let events = self.to_absolute();
+        let pairs = pair_notes(&events);
+        let paired_offs = pairs
+            .values()
+            .map(|(index, _)| *index)
+            .collect::<HashSet<_>>();
+
+        let mut state: HashMap<StateKey, (usize, Event)> = HashMap::new();
+        let mut sliced = Vec::new();
+        let mut carried_notes = Vec::new();
+        for (index, (tick, event)) in events.iter().enumerate() {
+            if *tick >= end {
+                break;
+            }
+            if paired_offs.contains(&index) || event == &Event::MetaEvent(MetaEvent::EndOfTrack) {
+                continue;
+            }
+            if let Some(ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            }) = voice_message(event)
+            {
+                if *velocity > 0 {
+                    let off_tick = pairs.get(&index).map(|(_, tick)| *tick);
+                    if off_tick.is_some_and(|off_tick| off_tick <= start) {
+                        continue;
+                    }
+                    let crosses = *tick < start || off_tick.is_none_or(|off_tick| off_tick > end);
+                    if crosses && policy == BoundaryPolicy::Drop {
+                        continue;
+                    }
+                    let on_tick = (*tick).max(start) - start;
+                    let off = match pairs.get(&index) {
+                        Some((off_index, off_tick)) if *off_tick <= end => {
+                            (*off_tick - start, events[*off_index].1.clone())
+                        }
+                        _ => (end - start, note_off(*channel, *note)),
+                    };
+                    if *tick < start {
+                        carried_notes.push((on_tick, event.clone()));
+                    } else {
+                        sliced.push((on_tick, event.clone()));
+                    }
+                    sliced.push(off);
+                    continue;
+                }
+            }
+            if *tick >= start {
+                sliced.push((tick - start, event.clone()));
+                continue;
+            }
+            let key = match event {
+                Event::MetaEvent(MetaEvent::SetTempo { .. }) => StateKey::Tempo,
+                Event::MetaEvent(MetaEvent::TimeSignature { .. }) => StateKey::TimeSignature,
+                Event::MetaEvent(MetaEvent::KeySignature { .. }) => StateKey::KeySignature,
+                Event::MetaEvent(MetaEvent::SequenceOrTrackName { .. }) => StateKey::TrackName,
+                Event::MetaEvent(MetaEvent::InstrumentName { .. }) => StateKey::InstrumentName,
+                _ => match voice_message(event) {
+                    Some(ChannelVoiceMessage::ProgramChange { channel, .. }) => {
+                        StateKey::Program(*channel)
+                    }
+                    Some(ChannelVoiceMessage::ControlChange {
+                        channel,
+                        controller: 0x79,
+                        ..
+                    }) => {
+                        state.retain(|key, _| match key {
+                            StateKey::Controller(key_channel, _)
+                            | StateKey::PitchBend(key_channel)
+                            | StateKey::ChannelPressure(key_channel) => key_channel != channel,
+                            _ => true,
+                        });
+                        continue;
+                    }
+                    Some(ChannelVoiceMessage::ControlChange {
+                        channel,
+                        controller,
+                        ..
+                    }) if *controller != 0x78 => StateKey::Controller(*channel, *controller),
+                    Some(ChannelVoiceMessage::PitchBendChange { channel, .. }) => {
+                        StateKey::PitchBend(*channel)
+                    }
+                    Some(ChannelVoiceMessage::ChannelPressure { channel, .. }) => {
+                        StateKey::ChannelPressure(*channel)
+                    }
+                    _ => continue,
+                },
+            };
+            state.insert(key, (index, event.clone()));
+        }
+
+        let mut initial = state.into_values().collect::<Vec<_>>();
+        initial.sort_by_key(|(index, _)| *index);
+        let mut result = initial
+            .into_iter()
+            .map(|(_, event)| (0, event))
+            .chain(carried_notes)
+            .collect::<Vec<_>>();
+        sliced.sort_by_key(|(tick, _)| *tick);
+        result.extend(sliced);
+        result.push((end - start, Event::MetaEvent(MetaEvent::EndOfTrack)));
+        TrackChunk::from_absolute(result)
+    }
+}
+
+impl MidiFile {
+    pub fn slice(&self, start: TimePosition, end: TimePosition) -> MidiFile {
+        self.slice_with_policy(start, end, BoundaryPolicy::default())
+    }
+
+    pub fn slice_with_policy(
+        &self,
+        start: TimePosition,
+        end: TimePosition,
+        policy: BoundaryPolicy,
+    ) -> MidiFile {
+        let tempo_map = self.tempo_map();
+        let start = tempo_map.to_tick(start);
+        let end = tempo_map.to_tick(end).max(start);
+        MidiFile {
+            header: self.header.clone(),
+            tracks: self
+                .tracks
+                .iter()
+                .map(|track| track.slice(start, end, policy))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, midi_event::ChannelVoiceMessage, Event},
+        track::TrackChunk,
+    };
+
+    use super::BoundaryPolicy;
+
+    fn note(on: bool, note: u8) -> Event {
+        Event::from(ChannelVoiceMessage::NoteOn {
+            channel: 0,
+            note,
+            velocity: if on { 100 } else { 0 },
+        })
+    }
+
+    fn track() -> TrackChunk {
+        TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+            (
+                0,
+                Event::from(ChannelVoiceMessage::ProgramChange {
+                    channel: 0,
+                    program: 5,
+                }),
+            ),
+            (0, note(true, 60)),
+            (
+                10,
+                Event::from(ChannelVoiceMessage::ControlChange {
+                    channel: 0,
+                    controller: 7,
+                    value: 90,
+                }),
+            ),
+            (20, note(false, 60)),
+            (30, note(true, 62)),
+            (40, note(false, 62)),
+            (45, note(true, 64)),
+            (60, note(false, 64)),
+            (70, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ])
+    }
+
+    #[test]
+    fn test_slice_truncate() {
+        let track = track().slice(15, 50, BoundaryPolicy::Truncate);
+        assert_eq!(
+            track.to_absolute(),
+            vec![
+                (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 400000 })),
+                (
+                    0,
+                    Event::from(ChannelVoiceMessage::ProgramChange {
+                        channel: 0,
+                        program: 5,
+                    })
+                ),
+                (
+                    0,
+                    Event::from(ChannelVoiceMessage::ControlChange {
+                        channel: 0,
+                        controller: 7,
+                        value: 90,
+                    })
+                ),
+                (0, note(true, 60)),
+                (5, note(false, 60)),
+                (15, note(true, 62)),
+                (25, note(false, 62)),
+                (30, note(true, 64)),
+                (
+                    35,
+                    Event::from(ChannelVoiceMessage::NoteOff {
+                        channel: 0,
+                        note: 64,
+                        velocity: 0,
+                    })
+                ),
+                (35, Event::MetaEvent(MetaEvent::EndOfTrack)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slice_drop() {
+        let track = track().slice(15, 50, BoundaryPolicy::Drop);
+        let notes = track
+            .to_absolute()
+            .into_iter()
+            .filter(|(_, event)| matches!(event, Event::MidiEvent(_)))
+            .count();
+        assert_eq!(notes, 4);
+        assert_eq!(track.end_tick(), 35);
+    }
+}
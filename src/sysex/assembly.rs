@@ -0,0 +1,217 @@
+use crate::{
+    event::{sysex_event::SysExEvent, Event},
+    track::TrackChunk,
+};
+
+use super::SysExMessage;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SysExPacketKind {
+    Complete,
+    Start,
+    Continuation,
+    End,
+    Escape,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SysExPacket {
+    pub tick: u64,
+    pub kind: SysExPacketKind,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AssembledSysEx {
+    pub packets: Vec<SysExPacket>,
+    pub complete: bool,
+}
+
+impl AssembledSysEx {
+    pub fn start_tick(&self) -> u64 {
+        self.packets.first().map(|packet| packet.tick).unwrap_or(0)
+    }
+
+    pub fn end_tick(&self) -> u64 {
+        self.packets.last().map(|packet| packet.tick).unwrap_or(0)
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = self
+            .packets
+            .iter()
+            .flat_map(|packet| packet.data.iter().copied())
+            .collect::<Vec<_>>();
+        if self.complete {
+            payload.pop();
+        }
+        payload
+    }
+
+    pub fn decode(&self) -> Option<SysExMessage> {
+        if !self.complete {
+            return None;
+        }
+        match SysExMessage::parse(&self.payload()) {
+            Ok((_, message)) => Some(message),
+            Err(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SysExItem {
+    Message(AssembledSysEx),
+    Escape(SysExPacket),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct SysExAssembler {
+    pending: Option<AssembledSysEx>,
+}
+
+impl SysExAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, tick: u64, event: &SysExEvent) -> Vec<SysExItem> {
+        let mut items = Vec::new();
+        let terminated = event.is_terminated();
+        let data = event.get_data().to_vec();
+        match (event.get_status(), self.pending.take()) {
+            (0xf0, pending) => {
+                if let Some(pending) = pending {
+                    items.push(SysExItem::Message(pending));
+                }
+                let kind = if terminated {
+                    SysExPacketKind::Complete
+                } else {
+                    SysExPacketKind::Start
+                };
+                let message = AssembledSysEx {
+                    packets: vec![SysExPacket { tick, kind, data }],
+                    complete: terminated,
+                };
+                if terminated {
+                    items.push(SysExItem::Message(message));
+                } else {
+                    self.pending = Some(message);
+                }
+            }
+            (_, Some(mut pending)) => {
+                let kind = if terminated {
+                    SysExPacketKind::End
+                } else {
+                    SysExPacketKind::Continuation
+                };
+                pending.packets.push(SysExPacket { tick, kind, data });
+                if terminated {
+                    pending.complete = true;
+                    items.push(SysExItem::Message(pending));
+                } else {
+                    self.pending = Some(pending);
+                }
+            }
+            (_, None) => items.push(SysExItem::Escape(SysExPacket {
+                tick,
+                kind: SysExPacketKind::Escape,
+                data,
+            })),
+        }
+        items
+    }
+
+    pub fn finish(self) -> Option<SysExItem> {
+        self.pending.map(SysExItem::Message)
+    }
+}
+
+pub fn split_sysex(payload: &[u8], packet_size: usize) -> Vec<SysExEvent> {
+    assert!(packet_size > 0);
+    let data = [payload, &[0xf7]].concat();
+    data.chunks(packet_size)
+        .enumerate()
+        .map(|(index, chunk)| SysExEvent::new(if index == 0 { 0xf0 } else { 0xf7 }, chunk.to_vec()))
+        .collect()
+}
+
+impl TrackChunk {
+    pub fn sysex_items(&self) -> Vec<SysExItem> {
+        let mut assembler = SysExAssembler::new();
+        let mut items = Vec::new();
+        for (tick, event) in self.to_absolute() {
+            if let Event::SysExEvent(event) = event {
+                items.extend(assembler.push(tick, &event));
+            }
+        }
+        items.extend(assembler.finish());
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, sysex_event::SysExEvent, Event},
+        sysex::{
+            msc::{Cue, MscCommand, ShowControl, SOUND},
+            SysExMessage,
+        },
+        track::TrackChunk,
+    };
+
+    use super::{split_sysex, SysExAssembler, SysExItem, SysExPacketKind};
+
+    #[test]
+    fn test_reassembly() {
+        let payload = SysExMessage::GsReset { device_id: 0x10 }.to_bytes();
+        let packets = split_sysex(&payload, 4);
+        assert_eq!(packets.len(), 3);
+        let mut events = packets
+            .into_iter()
+            .enumerate()
+            .map(|(index, packet)| (index as u64 * 10, Event::SysExEvent(packet)))
+            .collect::<Vec<_>>();
+        events.push((25, Event::SysExEvent(SysExEvent::escape(&[0xf8]))));
+        events.insert(0, (0, Event::SysExEvent(SysExEvent::escape(&[0xfa]))));
+        events.push((30, Event::MetaEvent(MetaEvent::EndOfTrack)));
+        let items = TrackChunk::from_absolute(events).sysex_items();
+        assert_eq!(items.len(), 3);
+        assert!(matches!(&items[0], SysExItem::Escape(packet) if packet.data == [0xfa]));
+        let SysExItem::Message(message) = &items[1] else {
+            panic!("Expected a message");
+        };
+        assert!(message.complete);
+        assert_eq!(message.packets.len(), 3);
+        assert_eq!(message.packets[0].kind, SysExPacketKind::Start);
+        assert_eq!(message.packets[2].kind, SysExPacketKind::End);
+        assert_eq!(message.end_tick(), 20);
+        assert_eq!(message.payload(), payload);
+        assert_eq!(
+            message.decode(),
+            Some(SysExMessage::GsReset { device_id: 0x10 })
+        );
+        assert!(matches!(&items[2], SysExItem::Escape(packet) if packet.tick == 25));
+    }
+
+    #[test]
+    fn test_push() {
+        let message = SysExMessage::ShowControl(ShowControl {
+            device_id: 0x7f,
+            command_format: SOUND,
+            command: MscCommand::Go(Cue::new("12.5")),
+        });
+        let mut assembler = SysExAssembler::new();
+        let mut items = Vec::new();
+        for (index, packet) in split_sysex(&message.to_bytes(), 3).iter().enumerate() {
+            assert!(items.is_empty());
+            items.extend(assembler.push(index as u64, packet));
+        }
+        assert_eq!(assembler.finish(), None);
+        let [SysExItem::Message(assembled)] = &items[..] else {
+            panic!("Expected a message");
+        };
+        assert_eq!(assembled.decode(), Some(message));
+    }
+}
@@ -0,0 +1,278 @@
+use nom::{
+    bytes::complete::{tag, take},
+    combinator::eof,
+    multi::many_till,
+    IResult,
+};
+
+use crate::utils::be_u7;
+
+use super::UNIVERSAL_REAL_TIME;
+
+const MMC_COMMAND: u8 = 0x06;
+const LOCATE: u8 = 0x44;
+const LOCATE_TARGET: u8 = 0x01;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TimecodeRate {
+    Fps24,
+    Fps25,
+    Fps30Drop,
+    Fps30,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Timecode {
+    pub rate: TimecodeRate,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub frame: u8,
+    pub subframe: u8,
+}
+
+impl Timecode {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, hour) = be_u7(input)?;
+        let (input, minute) = be_u7(input)?;
+        let (input, second) = be_u7(input)?;
+        let (input, frame) = be_u7(input)?;
+        let (input, subframe) = be_u7(input)?;
+        let rate = match hour >> 5 {
+            0 => TimecodeRate::Fps24,
+            1 => TimecodeRate::Fps25,
+            2 => TimecodeRate::Fps30Drop,
+            _ => TimecodeRate::Fps30,
+        };
+        Ok((
+            input,
+            Self {
+                rate,
+                hour: hour & 0x1f,
+                minute,
+                second,
+                frame,
+                subframe,
+            },
+        ))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 5] {
+        let rate = match self.rate {
+            TimecodeRate::Fps24 => 0,
+            TimecodeRate::Fps25 => 1,
+            TimecodeRate::Fps30Drop => 2,
+            TimecodeRate::Fps30 => 3,
+        };
+        [
+            (rate << 5) | (self.hour & 0x1f),
+            self.minute,
+            self.second,
+            self.frame,
+            self.subframe,
+        ]
+    }
+}
+
+// A command without its own variant, only commands 0x40-0x77 carry data
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OtherCommand {
+    command: u8,
+    data: Vec<u8>,
+}
+
+impl OtherCommand {
+    pub fn new(command: u8, data: &[u8]) -> Option<Self> {
+        let valid = match command {
+            0x01..=0x0d => false,
+            LOCATE if data.len() == 6 && data[0] == LOCATE_TARGET => false,
+            0x40..=0x77 => data.len() <= 0x7f && data.iter().all(|byte| byte >> 7 == 0),
+            _ => command >> 7 == 0 && data.is_empty(),
+        };
+        valid.then(|| Self {
+            command,
+            data: data.to_vec(),
+        })
+    }
+
+    pub fn command(&self) -> u8 {
+        self.command
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    DeferredPlay,
+    FastForward,
+    Rewind,
+    RecordStrobe,
+    RecordExit,
+    RecordPause,
+    Pause,
+    Eject,
+    Chase,
+    CommandErrorReset,
+    MmcReset,
+    Locate(Timecode),
+    Other(OtherCommand),
+}
+
+impl MmcCommand {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, command) = be_u7(input)?;
+        let simple = match command {
+            0x01 => Some(Self::Stop),
+            0x02 => Some(Self::Play),
+            0x03 => Some(Self::DeferredPlay),
+            0x04 => Some(Self::FastForward),
+            0x05 => Some(Self::Rewind),
+            0x06 => Some(Self::RecordStrobe),
+            0x07 => Some(Self::RecordExit),
+            0x08 => Some(Self::RecordPause),
+            0x09 => Some(Self::Pause),
+            0x0a => Some(Self::Eject),
+            0x0b => Some(Self::Chase),
+            0x0c => Some(Self::CommandErrorReset),
+            0x0d => Some(Self::MmcReset),
+            _ => None,
+        };
+        if let Some(command) = simple {
+            return Ok((input, command));
+        }
+        // Commands 0x40-0x77 carry a byte count, all others have no data
+        let (input, data) = match command {
+            0x40..=0x77 => {
+                let (input, length) = be_u7(input)?;
+                take(length as usize)(input)?
+            }
+            _ => (input, &[][..]),
+        };
+        if command == LOCATE && data.len() == 6 && data[0] == LOCATE_TARGET {
+            if let Ok((_, timecode)) = Timecode::parse(&data[1..]) {
+                return Ok((input, Self::Locate(timecode)));
+            }
+        }
+        Ok((
+            input,
+            Self::Other(OtherCommand {
+                command,
+                data: data.to_vec(),
+            }),
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Stop => vec![0x01],
+            Self::Play => vec![0x02],
+            Self::DeferredPlay => vec![0x03],
+            Self::FastForward => vec![0x04],
+            Self::Rewind => vec![0x05],
+            Self::RecordStrobe => vec![0x06],
+            Self::RecordExit => vec![0x07],
+            Self::RecordPause => vec![0x08],
+            Self::Pause => vec![0x09],
+            Self::Eject => vec![0x0a],
+            Self::Chase => vec![0x0b],
+            Self::CommandErrorReset => vec![0x0c],
+            Self::MmcReset => vec![0x0d],
+            Self::Locate(timecode) => {
+                [&[LOCATE, 0x06, LOCATE_TARGET][..], &timecode.to_bytes()].concat()
+            }
+            Self::Other(OtherCommand { command, data }) => match command {
+                0x40..=0x77 => [&[*command, data.len() as u8][..], data].concat(),
+                _ => vec![*command],
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MachineControl {
+    pub device_id: u8,
+    pub commands: Vec<MmcCommand>,
+}
+
+impl MachineControl {
+    pub fn new(device_id: u8, command: MmcCommand) -> Self {
+        Self {
+            device_id,
+            commands: vec![command],
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(&[UNIVERSAL_REAL_TIME])(input)?;
+        let (input, device_id) = be_u7(input)?;
+        let (input, _) = tag(&[MMC_COMMAND])(input)?;
+        let (input, (commands, _)) = many_till(MmcCommand::parse, eof)(input)?;
+        Ok((
+            input,
+            Self {
+                device_id,
+                commands,
+            },
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![UNIVERSAL_REAL_TIME, self.device_id, MMC_COMMAND];
+        self.commands
+            .iter()
+            .for_each(|command| bytes.extend(command.to_bytes()));
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MachineControl, MmcCommand, OtherCommand, Timecode, TimecodeRate};
+
+    #[test]
+    fn test_machine_control() {
+        let bytes = [
+            0x7f, 0x7f, 0x06, 0x44, 0x06, 0x01, 0x61, 0x02, 0x03, 0x04, 0x00, 0x02,
+        ];
+        let (_, message) = MachineControl::parse(&bytes).unwrap();
+        assert_eq!(
+            message.commands,
+            [
+                MmcCommand::Locate(Timecode {
+                    rate: TimecodeRate::Fps30,
+                    hour: 1,
+                    minute: 2,
+                    second: 3,
+                    frame: 4,
+                    subframe: 0,
+                }),
+                MmcCommand::Play,
+            ]
+        );
+        assert_eq!(message.to_bytes(), bytes);
+        let message = MachineControl::new(0x10, MmcCommand::RecordStrobe);
+        assert_eq!(message.to_bytes(), [0x7f, 0x10, 0x06, 0x06]);
+        let (_, message) =
+            MachineControl::parse(&[0x7f, 0x00, 0x06, 0x47, 0x03, 0x01, 0x02, 0x03]).unwrap();
+        assert_eq!(
+            message.commands,
+            [MmcCommand::Other(
+                OtherCommand::new(0x47, &[0x01, 0x02, 0x03]).unwrap()
+            )]
+        );
+        assert_eq!(
+            message.commands[0].to_bytes(),
+            [0x47, 0x03, 0x01, 0x02, 0x03]
+        );
+        // Data after a command outside 0x40-0x77 would be read back as further commands
+        assert_eq!(OtherCommand::new(0x20, &[0x01]), None);
+        assert_eq!(OtherCommand::new(0x02, &[]), None);
+        assert_eq!(OtherCommand::new(0x47, &[0x80]), None);
+        assert!(MachineControl::parse(&[0x7f, 0x00, 0x06, 0x47, 0x03, 0x01]).is_err());
+    }
+}
@@ -0,0 +1,538 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take},
+    combinator::{eof, map, rest, verify},
+    multi::count,
+    sequence::terminated,
+    IResult,
+};
+
+pub mod assembly;
+pub mod mmc;
+pub mod msc;
+pub mod mts;
+
+use crate::{event::sysex_event::SysExEvent, midi_file::MidiFile, utils::be_u7};
+
+use self::{assembly::SysExItem, mmc::MachineControl, msc::ShowControl, mts::TuningMessage};
+
+pub const UNIVERSAL_NON_REAL_TIME: u8 = 0x7e;
+pub const UNIVERSAL_REAL_TIME: u8 = 0x7f;
+pub const ALL_CALL: u8 = 0x7f;
+const ROLAND: u8 = 0x41;
+const YAMAHA: u8 = 0x43;
+const ROLAND_GS_MODEL: u8 = 0x42;
+const ROLAND_DATA_SET: u8 = 0x12;
+const YAMAHA_XG_MODEL: u8 = 0x4c;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ManufacturerId {
+    OneByte(u8),
+    ThreeByte(u8, u8),
+}
+
+impl ManufacturerId {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, id) = be_u7(input)?;
+        if id == 0x00 {
+            let (input, high) = be_u7(input)?;
+            let (input, low) = be_u7(input)?;
+            Ok((input, Self::ThreeByte(high, low)))
+        } else {
+            Ok((input, Self::OneByte(id)))
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::OneByte(id) => vec![*id],
+            Self::ThreeByte(high, low) => vec![0x00, *high, *low],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum SynthStandard {
+    Gm,
+    Gm2,
+    Gs,
+    Xg,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SysExMessage {
+    GmSystemOn {
+        device_id: u8,
+    },
+    GmSystemOff {
+        device_id: u8,
+    },
+    Gm2SystemOn {
+        device_id: u8,
+    },
+    MasterVolume {
+        device_id: u8,
+        volume: u16,
+    },
+    MasterBalance {
+        device_id: u8,
+        balance: u16,
+    },
+    MasterFineTuning {
+        device_id: u8,
+        value: u16,
+    },
+    MasterCoarseTuning {
+        device_id: u8,
+        value: u8,
+    },
+    DeviceInquiryRequest {
+        device_id: u8,
+    },
+    DeviceInquiryReply {
+        device_id: u8,
+        manufacturer: ManufacturerId,
+        family: u16,
+        member: u16,
+        version: [u8; 4],
+    },
+    GsReset {
+        device_id: u8,
+    },
+    RolandDataSet {
+        device_id: u8,
+        model_id: u8,
+        address: [u8; 3],
+        data: Vec<u8>,
+    },
+    XgSystemOn {
+        device_id: u8,
+    },
+    XgParameterChange {
+        device_id: u8,
+        address: [u8; 3],
+        data: Vec<u8>,
+    },
+    UniversalNonRealTime {
+        device_id: u8,
+        sub_id_1: u8,
+        sub_id_2: u8,
+        data: Vec<u8>,
+    },
+    UniversalRealTime {
+        device_id: u8,
+        sub_id_1: u8,
+        sub_id_2: u8,
+        data: Vec<u8>,
+    },
+    Tuning(TuningMessage),
+    MachineControl(MachineControl),
+    ShowControl(ShowControl),
+    Manufacturer {
+        id: ManufacturerId,
+        data: Vec<u8>,
+    },
+}
+
+fn be_u14(input: &[u8]) -> IResult<&[u8], u16> {
+    let (input, lsb) = be_u7(input)?;
+    let (input, msb) = be_u7(input)?;
+    Ok((input, ((msb as u16) << 7) | lsb as u16))
+}
+
+fn u14_to_bytes(value: u16) -> [u8; 2] {
+    [(value & 0x7f) as u8, ((value >> 7) & 0x7f) as u8]
+}
+
+pub fn roland_checksum(bytes: &[u8]) -> u8 {
+    let sum = bytes.iter().map(|byte| *byte as u32).sum::<u32>();
+    ((128 - sum % 128) % 128) as u8
+}
+
+impl SysExMessage {
+    fn parse_non_real_time(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(&[UNIVERSAL_NON_REAL_TIME])(input)?;
+        let (input, device_id) = be_u7(input)?;
+        let (input, sub_id_1) = be_u7(input)?;
+        let (input, sub_id_2) = be_u7(input)?;
+        match (sub_id_1, sub_id_2) {
+            (0x09, 0x01) => Ok((input, Self::GmSystemOn { device_id })),
+            (0x09, 0x02) => Ok((input, Self::GmSystemOff { device_id })),
+            (0x09, 0x03) => Ok((input, Self::Gm2SystemOn { device_id })),
+            (0x06, 0x01) => Ok((input, Self::DeviceInquiryRequest { device_id })),
+            (0x06, 0x02) => {
+                let (input, manufacturer) = ManufacturerId::parse(input)?;
+                let (input, family) = be_u14(input)?;
+                let (input, member) = be_u14(input)?;
+                let (input, version) = take(4usize)(input)?;
+                Ok((
+                    input,
+                    Self::DeviceInquiryReply {
+                        device_id,
+                        manufacturer,
+                        family,
+                        member,
+                        version: [version[0], version[1], version[2], version[3]],
+                    },
+                ))
+            }
+            _ => {
+                let (input, data) = rest(input)?;
+                Ok((
+                    input,
+                    Self::UniversalNonRealTime {
+                        device_id,
+                        sub_id_1,
+                        sub_id_2,
+                        data: data.to_vec(),
+                    },
+                ))
+            }
+        }
+    }
+
+    fn parse_real_time(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(&[UNIVERSAL_REAL_TIME])(input)?;
+        let (input, device_id) = be_u7(input)?;
+        let (input, sub_id_1) = be_u7(input)?;
+        let (input, sub_id_2) = be_u7(input)?;
+        match (sub_id_1, sub_id_2) {
+            (0x04, 0x01) => {
+                let (input, volume) = terminated(be_u14, eof)(input)?;
+                Ok((input, Self::MasterVolume { device_id, volume }))
+            }
+            (0x04, 0x02) => {
+                let (input, balance) = terminated(be_u14, eof)(input)?;
+                Ok((input, Self::MasterBalance { device_id, balance }))
+            }
+            (0x04, 0x03) => {
+                let (input, value) = terminated(be_u14, eof)(input)?;
+                Ok((input, Self::MasterFineTuning { device_id, value }))
+            }
+            (0x04, 0x04) => {
+                let (input, _) = be_u7(input)?;
+                let (input, value) = terminated(be_u7, eof)(input)?;
+                Ok((input, Self::MasterCoarseTuning { device_id, value }))
+            }
+            _ => {
+                let (input, data) = rest(input)?;
+                Ok((
+                    input,
+                    Self::UniversalRealTime {
+                        device_id,
+                        sub_id_1,
+                        sub_id_2,
+                        data: data.to_vec(),
+                    },
+                ))
+            }
+        }
+    }
+
+    fn parse_roland(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(&[ROLAND])(input)?;
+        let (input, device_id) = be_u7(input)?;
+        let (input, model_id) = be_u7(input)?;
+        let (input, _) = tag(&[ROLAND_DATA_SET])(input)?;
+        let (input, body) = verify(rest, |body: &[u8]| {
+            body.len() >= 5
+                && body.iter().all(|byte| byte >> 7 == 0)
+                && roland_checksum(&body[..body.len() - 1]) == body[body.len() - 1]
+        })(input)?;
+        let address = [body[0], body[1], body[2]];
+        let data = body[3..body.len() - 1].to_vec();
+        if model_id == ROLAND_GS_MODEL && address == [0x40, 0x00, 0x7f] && data == [0x00] {
+            return Ok((input, Self::GsReset { device_id }));
+        }
+        Ok((
+            input,
+            Self::RolandDataSet {
+                device_id,
+                model_id,
+                address,
+                data,
+            },
+        ))
+    }
+
+    fn parse_yamaha_xg(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(&[YAMAHA])(input)?;
+        let (input, device) = verify(be_u7, |byte| byte >> 4 == 0x1)(input)?;
+        let device_id = device & 0x0f;
+        let (input, _) = tag(&[YAMAHA_XG_MODEL])(input)?;
+        let (input, address) = count(be_u7, 3)(input)?;
+        let (input, data) = verify(rest, |data: &[u8]| {
+            !data.is_empty() && data.iter().all(|byte| byte >> 7 == 0)
+        })(input)?;
+        let address = [address[0], address[1], address[2]];
+        if address == [0x00, 0x00, 0x7e] && data == [0x00] {
+            return Ok((input, Self::XgSystemOn { device_id }));
+        }
+        Ok((
+            input,
+            Self::XgParameterChange {
+                device_id,
+                address,
+                data: data.to_vec(),
+            },
+        ))
+    }
+
+    fn parse_manufacturer(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, id) = ManufacturerId::parse(input)?;
+        let (input, data) = rest(input)?;
+        Ok((
+            input,
+            Self::Manufacturer {
+                id,
+                data: data.to_vec(),
+            },
+        ))
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        alt((
+            map(TuningMessage::parse, Self::Tuning),
+            map(MachineControl::parse, Self::MachineControl),
+            map(ShowControl::parse, Self::ShowControl),
+            Self::parse_non_real_time,
+            Self::parse_real_time,
+            Self::parse_roland,
+            Self::parse_yamaha_xg,
+            Self::parse_manufacturer,
+        ))(input)
+    }
+
+    pub fn decode(event: &SysExEvent) -> Option<Self> {
+        let payload = event.get_payload()?;
+        match Self::parse(payload) {
+            Ok((_, message)) => Some(message),
+            Err(_) => None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::GmSystemOn { device_id } => {
+                vec![UNIVERSAL_NON_REAL_TIME, *device_id, 0x09, 0x01]
+            }
+            Self::GmSystemOff { device_id } => {
+                vec![UNIVERSAL_NON_REAL_TIME, *device_id, 0x09, 0x02]
+            }
+            Self::Gm2SystemOn { device_id } => {
+                vec![UNIVERSAL_NON_REAL_TIME, *device_id, 0x09, 0x03]
+            }
+            Self::MasterVolume { device_id, volume } => [
+                &[UNIVERSAL_REAL_TIME, *device_id, 0x04, 0x01],
+                &u14_to_bytes(*volume)[..],
+            ]
+            .concat(),
+            Self::MasterBalance { device_id, balance } => [
+                &[UNIVERSAL_REAL_TIME, *device_id, 0x04, 0x02],
+                &u14_to_bytes(*balance)[..],
+            ]
+            .concat(),
+            Self::MasterFineTuning { device_id, value } => [
+                &[UNIVERSAL_REAL_TIME, *device_id, 0x04, 0x03],
+                &u14_to_bytes(*value)[..],
+            ]
+            .concat(),
+            Self::MasterCoarseTuning { device_id, value } => {
+                vec![UNIVERSAL_REAL_TIME, *device_id, 0x04, 0x04, 0x00, *value]
+            }
+            Self::DeviceInquiryRequest { device_id } => {
+                vec![UNIVERSAL_NON_REAL_TIME, *device_id, 0x06, 0x01]
+            }
+            Self::DeviceInquiryReply {
+                device_id,
+                manufacturer,
+                family,
+                member,
+                version,
+            } => [
+                &[UNIVERSAL_NON_REAL_TIME, *device_id, 0x06, 0x02],
+                manufacturer.to_bytes().as_slice(),
+                &u14_to_bytes(*family),
+                &u14_to_bytes(*member),
+                version,
+            ]
+            .concat(),
+            Self::GsReset { device_id } => Self::RolandDataSet {
+                device_id: *device_id,
+                model_id: ROLAND_GS_MODEL,
+                address: [0x40, 0x00, 0x7f],
+                data: vec![0x00],
+            }
+            .to_bytes(),
+            Self::RolandDataSet {
+                device_id,
+                model_id,
+                address,
+                data,
+            } => {
+                let body = [address.as_slice(), data].concat();
+                [
+                    &[ROLAND, *device_id, *model_id, ROLAND_DATA_SET],
+                    body.as_slice(),
+                    &[roland_checksum(&body)],
+                ]
+                .concat()
+            }
+            Self::XgSystemOn { device_id } => Self::XgParameterChange {
+                device_id: *device_id,
+                address: [0x00, 0x00, 0x7e],
+                data: vec![0x00],
+            }
+            .to_bytes(),
+            Self::XgParameterChange {
+                device_id,
+                address,
+                data,
+            } => [
+                &[YAMAHA, 0x10 | *device_id, YAMAHA_XG_MODEL],
+                address.as_slice(),
+                data,
+            ]
+            .concat(),
+            Self::UniversalNonRealTime {
+                device_id,
+                sub_id_1,
+                sub_id_2,
+                data,
+            } => [
+                &[UNIVERSAL_NON_REAL_TIME, *device_id, *sub_id_1, *sub_id_2],
+                data.as_slice(),
+            ]
+            .concat(),
+            Self::UniversalRealTime {
+                device_id,
+                sub_id_1,
+                sub_id_2,
+                data,
+            } => [
+                &[UNIVERSAL_REAL_TIME, *device_id, *sub_id_1, *sub_id_2],
+                data.as_slice(),
+            ]
+            .concat(),
+            Self::Tuning(message) => message.to_bytes(),
+            Self::MachineControl(message) => message.to_bytes(),
+            Self::ShowControl(message) => message.to_bytes(),
+            Self::Manufacturer { id, data } => [id.to_bytes(), data.clone()].concat(),
+        }
+    }
+
+    pub fn to_sysex_event(&self) -> SysExEvent {
+        SysExEvent::from_payload(&self.to_bytes())
+    }
+
+    pub fn standard(&self) -> Option<SynthStandard> {
+        match self {
+            Self::GmSystemOn { .. } => Some(SynthStandard::Gm),
+            Self::Gm2SystemOn { .. } => Some(SynthStandard::Gm2),
+            Self::GsReset { .. } => Some(SynthStandard::Gs),
+            Self::RolandDataSet {
+                model_id: ROLAND_GS_MODEL,
+                ..
+            } => Some(SynthStandard::Gs),
+            Self::XgSystemOn { .. } | Self::XgParameterChange { .. } => Some(SynthStandard::Xg),
+            _ => None,
+        }
+    }
+}
+
+impl MidiFile {
+    pub fn sysex_messages(&self) -> Vec<(usize, u64, SysExMessage)> {
+        self.tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, track)| {
+                track
+                    .sysex_items()
+                    .into_iter()
+                    .filter_map(move |item| match item {
+                        SysExItem::Message(message) => {
+                            Some((index, message.start_tick(), message.decode()?))
+                        }
+                        SysExItem::Escape(_) => None,
+                    })
+            })
+            .collect()
+    }
+
+    pub fn synth_standard(&self) -> Option<SynthStandard> {
+        self.sysex_messages()
+            .iter()
+            .filter_map(|(_, _, message)| message.standard())
+            .max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ManufacturerId, SynthStandard, SysExMessage};
+
+    #[test]
+    fn test_decode() {
+        let cases = [
+            (
+                vec![0x7e, 0x7f, 0x09, 0x01],
+                SysExMessage::GmSystemOn { device_id: 0x7f },
+            ),
+            (
+                vec![0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7f, 0x00, 0x41],
+                SysExMessage::GsReset { device_id: 0x10 },
+            ),
+            (
+                vec![0x43, 0x10, 0x4c, 0x00, 0x00, 0x7e, 0x00],
+                SysExMessage::XgSystemOn { device_id: 0 },
+            ),
+            (
+                vec![0x7f, 0x7f, 0x04, 0x01, 0x00, 0x7f],
+                SysExMessage::MasterVolume {
+                    device_id: 0x7f,
+                    volume: 0x3f80,
+                },
+            ),
+            (
+                vec![
+                    0x7e, 0x00, 0x06, 0x02, 0x00, 0x20, 0x33, 0x01, 0x00, 0x02, 0x00, 0x01, 0x02,
+                    0x03, 0x04,
+                ],
+                SysExMessage::DeviceInquiryReply {
+                    device_id: 0,
+                    manufacturer: ManufacturerId::ThreeByte(0x20, 0x33),
+                    family: 1,
+                    member: 2,
+                    version: [1, 2, 3, 4],
+                },
+            ),
+        ];
+        for (bytes, message) in cases {
+            let event = SysExMessage::to_sysex_event(&message);
+            assert_eq!(event.get_payload(), Some(bytes.as_slice()));
+            assert_eq!(SysExMessage::decode(&event), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_roland_checksum() {
+        let message = SysExMessage::RolandDataSet {
+            device_id: 0x10,
+            model_id: 0x42,
+            address: [0x40, 0x01, 0x30],
+            data: vec![0x04],
+        };
+        let mut bytes = message.to_bytes();
+        assert_eq!(bytes.last(), Some(&0x0b));
+        assert_eq!(SysExMessage::parse(&bytes).unwrap().1, message);
+        assert_eq!(message.standard(), Some(SynthStandard::Gs));
+        *bytes.last_mut().unwrap() = 0x0c;
+        assert_eq!(
+            SysExMessage::parse(&bytes).unwrap().1,
+            SysExMessage::Manufacturer {
+                id: ManufacturerId::OneByte(0x41),
+                data: bytes[1..].to_vec(),
+            }
+        );
+    }
+}
@@ -0,0 +1,318 @@
+use nom::{
+    bytes::complete::tag,
+    combinator::{eof, rest, verify},
+    sequence::terminated,
+    IResult,
+};
+
+use crate::utils::be_u7;
+
+use super::{be_u14, mmc::Timecode, u14_to_bytes, UNIVERSAL_REAL_TIME};
+
+const MSC_COMMAND: u8 = 0x02;
+pub const LIGHTING: u8 = 0x01;
+pub const SOUND: u8 = 0x10;
+pub const ALL_TYPES: u8 = 0x7f;
+
+// Cue fields are nonempty 7-bit text without the 0x00 separator
+fn is_field(field: &str) -> bool {
+    !field.is_empty() && field.bytes().all(|byte| byte != 0 && byte >> 7 == 0)
+}
+
+// A path only exists inside a list, and a list only with a cue number
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cue {
+    number: String,
+    list: Option<String>,
+    path: Option<String>,
+}
+
+impl Cue {
+    pub fn new(number: &str) -> Option<Self> {
+        is_field(number).then(|| Self {
+            number: number.to_string(),
+            list: None,
+            path: None,
+        })
+    }
+
+    pub fn in_list(self, list: &str) -> Option<Self> {
+        is_field(list).then(|| Self {
+            list: Some(list.to_string()),
+            ..self
+        })
+    }
+
+    pub fn in_path(self, path: &str) -> Option<Self> {
+        (self.list.is_some() && is_field(path)).then(|| Self {
+            path: Some(path.to_string()),
+            ..self
+        })
+    }
+
+    pub fn number(&self) -> &str {
+        &self.number
+    }
+
+    pub fn list(&self) -> Option<&str> {
+        self.list.as_deref()
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    fn parse(input: &[u8]) -> IResult<&[u8], Option<Self>> {
+        let (input, data) = verify(rest, |data: &[u8]| {
+            let mut fields = data.split(|byte| *byte == 0);
+            data.iter().all(|byte| byte >> 7 == 0)
+                && (data.is_empty()
+                    || fields.clone().count() <= 3 && fields.all(|field| !field.is_empty()))
+        })(input)?;
+        let mut fields = data
+            .split(|byte| *byte == 0)
+            .filter(|field| !field.is_empty())
+            .map(|field| String::from_utf8_lossy(field).into_owned());
+        let cue = fields.next().map(|number| Self {
+            number,
+            list: fields.next(),
+            path: fields.next(),
+        });
+        Ok((input, cue))
+    }
+
+    fn to_bytes(cue: &Option<Self>) -> Vec<u8> {
+        let Some(cue) = cue else {
+            return Vec::new();
+        };
+        [Some(&cue.number), cue.list.as_ref(), cue.path.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|field| field.as_bytes())
+            .collect::<Vec<_>>()
+            .join(&0x00)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MscCommand {
+    Go(Option<Cue>),
+    Stop(Option<Cue>),
+    Resume(Option<Cue>),
+    TimedGo {
+        time: Timecode,
+        cue: Option<Cue>,
+    },
+    Load(Option<Cue>),
+    Set {
+        control: u16,
+        value: u16,
+        time: Option<Timecode>,
+    },
+    Fire {
+        macro_number: u8,
+    },
+    AllOff,
+    Restore,
+    Reset,
+    GoOff(Option<Cue>),
+    Other {
+        command: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl MscCommand {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, command) = be_u7(input)?;
+        match command {
+            0x01 => Cue::parse(input).map(|(input, cue)| (input, Self::Go(cue))),
+            0x02 => Cue::parse(input).map(|(input, cue)| (input, Self::Stop(cue))),
+            0x03 => Cue::parse(input).map(|(input, cue)| (input, Self::Resume(cue))),
+            0x04 => {
+                let (input, time) = Timecode::parse(input)?;
+                let (input, cue) = Cue::parse(input)?;
+                Ok((input, Self::TimedGo { time, cue }))
+            }
+            0x05 => Cue::parse(input).map(|(input, cue)| (input, Self::Load(cue))),
+            0x06 => {
+                let (input, control) = be_u14(input)?;
+                let (input, value) = be_u14(input)?;
+                let (input, time) = match eof::<_, nom::error::Error<_>>(input) {
+                    Ok(_) => (input, None),
+                    Err(_) => {
+                        let (input, time) = terminated(Timecode::parse, eof)(input)?;
+                        (input, Some(time))
+                    }
+                };
+                Ok((
+                    input,
+                    Self::Set {
+                        control,
+                        value,
+                        time,
+                    },
+                ))
+            }
+            0x07 => {
+                let (input, macro_number) = terminated(be_u7, eof)(input)?;
+                Ok((input, Self::Fire { macro_number }))
+            }
+            0x08 => eof(input).map(|(input, _)| (input, Self::AllOff)),
+            0x09 => eof(input).map(|(input, _)| (input, Self::Restore)),
+            0x0a => eof(input).map(|(input, _)| (input, Self::Reset)),
+            0x0b => Cue::parse(input).map(|(input, cue)| (input, Self::GoOff(cue))),
+            _ => {
+                let (input, data) = rest(input)?;
+                Ok((
+                    input,
+                    Self::Other {
+                        command,
+                        data: data.to_vec(),
+                    },
+                ))
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Go(cue) => [&[0x01][..], &Cue::to_bytes(cue)].concat(),
+            Self::Stop(cue) => [&[0x02][..], &Cue::to_bytes(cue)].concat(),
+            Self::Resume(cue) => [&[0x03][..], &Cue::to_bytes(cue)].concat(),
+            Self::TimedGo { time, cue } => {
+                [&[0x04][..], &time.to_bytes(), &Cue::to_bytes(cue)].concat()
+            }
+            Self::Load(cue) => [&[0x05][..], &Cue::to_bytes(cue)].concat(),
+            Self::Set {
+                control,
+                value,
+                time,
+            } => {
+                let mut bytes = vec![0x06];
+                bytes.extend(u14_to_bytes(*control));
+                bytes.extend(u14_to_bytes(*value));
+                if let Some(time) = time {
+                    bytes.extend(time.to_bytes());
+                }
+                bytes
+            }
+            Self::Fire { macro_number } => vec![0x07, *macro_number],
+            Self::AllOff => vec![0x08],
+            Self::Restore => vec![0x09],
+            Self::Reset => vec![0x0a],
+            Self::GoOff(cue) => [&[0x0b][..], &Cue::to_bytes(cue)].concat(),
+            Self::Other { command, data } => [&[*command][..], data].concat(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShowControl {
+    pub device_id: u8,
+    pub command_format: u8,
+    pub command: MscCommand,
+}
+
+impl ShowControl {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(&[UNIVERSAL_REAL_TIME])(input)?;
+        let (input, device_id) = be_u7(input)?;
+        let (input, _) = tag(&[MSC_COMMAND])(input)?;
+        let (input, command_format) = be_u7(input)?;
+        let (input, command) = MscCommand::parse(input)?;
+        Ok((
+            input,
+            Self {
+                device_id,
+                command_format,
+                command,
+            },
+        ))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [
+            &[
+                UNIVERSAL_REAL_TIME,
+                self.device_id,
+                MSC_COMMAND,
+                self.command_format,
+            ][..],
+            &self.command.to_bytes(),
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sysex::mmc::{Timecode, TimecodeRate};
+
+    use super::{Cue, MscCommand, ShowControl, LIGHTING};
+
+    #[test]
+    fn test_show_control() {
+        let bytes = [
+            0x7f, 0x01, 0x02, 0x01, 0x01, b'2', b'3', b'5', b'.', b'6', 0x00, b'3', b'7',
+        ];
+        let (_, message) = ShowControl::parse(&bytes).unwrap();
+        assert_eq!(
+            message,
+            ShowControl {
+                device_id: 0x01,
+                command_format: LIGHTING,
+                command: MscCommand::Go(Cue::new("235.6").and_then(|cue| cue.in_list("37"))),
+            }
+        );
+        assert_eq!(message.to_bytes(), bytes);
+
+        let message = ShowControl {
+            device_id: 0x7f,
+            command_format: LIGHTING,
+            command: MscCommand::TimedGo {
+                time: Timecode {
+                    rate: TimecodeRate::Fps25,
+                    hour: 0,
+                    minute: 1,
+                    second: 0,
+                    frame: 0,
+                    subframe: 0,
+                },
+                cue: Cue::new("5"),
+            },
+        };
+        assert_eq!(ShowControl::parse(&message.to_bytes()).unwrap().1, message);
+        let message = ShowControl {
+            device_id: 0x01,
+            command_format: LIGHTING,
+            command: MscCommand::Load(
+                Cue::new("1")
+                    .and_then(|cue| cue.in_list("2"))
+                    .and_then(|cue| cue.in_path("3")),
+            ),
+        };
+        assert_eq!(message.to_bytes()[5..], *b"1\x002\x003");
+        assert_eq!(ShowControl::parse(&message.to_bytes()).unwrap().1, message);
+        let (_, message) = ShowControl::parse(&[0x7f, 0x01, 0x02, 0x01, 0x03]).unwrap();
+        assert_eq!(message.command, MscCommand::Resume(None));
+        // A list needs a cue number and a path needs a list
+        assert!(ShowControl::parse(&[0x7f, 0x01, 0x02, 0x01, 0x01, 0x00, b'3']).is_err());
+        assert!(
+            ShowControl::parse(&[0x7f, 0x01, 0x02, 0x01, 0x01, b'1', 0x00, 0x00, b'3']).is_err()
+        );
+        assert_eq!(Cue::new(""), None);
+        assert_eq!(Cue::new("1\0").and_then(|cue| cue.in_list("2")), None);
+        assert_eq!(Cue::new("1").and_then(|cue| cue.in_path("3")), None);
+        let (_, message) =
+            ShowControl::parse(&[0x7f, 0x01, 0x02, 0x01, 0x06, 0x05, 0x00, 0x7f, 0x00]).unwrap();
+        assert_eq!(
+            message.command,
+            MscCommand::Set {
+                control: 5,
+                value: 127,
+                time: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,490 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take},
+    combinator::{eof, verify},
+    multi::count,
+    sequence::{terminated, tuple},
+    IResult,
+};
+
+use crate::utils::be_u7;
+
+use super::{UNIVERSAL_NON_REAL_TIME, UNIVERSAL_REAL_TIME};
+
+const MIDI_TUNING: u8 = 0x08;
+const BULK_DUMP: u8 = 0x01;
+const SINGLE_NOTE_TUNING_CHANGE: u8 = 0x02;
+const KEY_BASED_DUMP: u8 = 0x04;
+const SINGLE_NOTE_TUNING_CHANGE_BANK: u8 = 0x07;
+const SCALE_OCTAVE_1_BYTE: u8 = 0x08;
+const SCALE_OCTAVE_2_BYTE: u8 = 0x09;
+
+pub fn equal_temperament_frequency(note: u8) -> f64 {
+    440.0 * 2f64.powf((note as f64 - 69.0) / 12.0)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NoteTuning {
+    pub semitone: u8,
+    pub fraction: u16,
+}
+
+impl NoteTuning {
+    fn parse(input: &[u8]) -> IResult<&[u8], Option<Self>> {
+        let (input, (semitone, msb, lsb)) = tuple((be_u7, be_u7, be_u7))(input)?;
+        if (semitone, msb, lsb) == (0x7f, 0x7f, 0x7f) {
+            return Ok((input, None));
+        }
+        Ok((
+            input,
+            Some(Self {
+                semitone,
+                fraction: ((msb as u16) << 7) | lsb as u16,
+            }),
+        ))
+    }
+
+    fn to_bytes(tuning: &Option<Self>) -> [u8; 3] {
+        match tuning {
+            Some(tuning) => [
+                tuning.semitone,
+                ((tuning.fraction >> 7) & 0x7f) as u8,
+                (tuning.fraction & 0x7f) as u8,
+            ],
+            None => [0x7f, 0x7f, 0x7f],
+        }
+    }
+
+    pub fn from_frequency(frequency: f64) -> Self {
+        assert!(frequency > 0.0, "Invalid frequency: {}", frequency);
+        let note = (69.0 + 12.0 * (frequency / 440.0).log2()).clamp(0.0, 127.0);
+        let mut semitone = note.floor() as u8;
+        let mut fraction = ((note - semitone as f64) * 16384.0).round() as u16;
+        if fraction >= 0x4000 {
+            semitone += 1;
+            fraction = 0;
+        }
+        // 0x7f 0x7f 0x7f is reserved for "no change"
+        if semitone == 0x7f && fraction == 0x3fff {
+            fraction = 0x3ffe;
+        }
+        Self { semitone, fraction }
+    }
+
+    pub fn frequency(&self) -> f64 {
+        let note = self.semitone as f64 + self.fraction as f64 / 16384.0;
+        440.0 * 2f64.powf((note - 69.0) / 12.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScaleOffsets {
+    OneByte([i8; 12]),
+    TwoByte([u16; 12]),
+}
+
+impl ScaleOffsets {
+    pub fn cents(&self) -> [f64; 12] {
+        match self {
+            Self::OneByte(offsets) => offsets.map(|offset| offset as f64),
+            Self::TwoByte(offsets) => {
+                offsets.map(|offset| (offset as f64 - 8192.0) / 8192.0 * 100.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TuningMessage {
+    BulkDump {
+        device_id: u8,
+        program: u8,
+        name: String,
+        tunings: Vec<Option<NoteTuning>>,
+    },
+    KeyBasedDump {
+        device_id: u8,
+        bank: u8,
+        program: u8,
+        name: String,
+        tunings: Vec<Option<NoteTuning>>,
+    },
+    SingleNoteTuningChange {
+        real_time: bool,
+        device_id: u8,
+        bank: Option<u8>,
+        program: u8,
+        changes: Vec<(u8, Option<NoteTuning>)>,
+    },
+    ScaleOctaveTuning {
+        real_time: bool,
+        device_id: u8,
+        channels: u16,
+        offsets: ScaleOffsets,
+    },
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |checksum, byte| checksum ^ byte) & 0x7f
+}
+
+fn parse_name(input: &[u8]) -> IResult<&[u8], String> {
+    let (input, name) = verify(take(16usize), |name: &[u8]| {
+        name.iter().all(|byte| byte >> 7 == 0)
+    })(input)?;
+    // Names are padded with spaces to their 16 bytes
+    let name = String::from_utf8_lossy(name);
+    Ok((input, name.trim_end_matches(' ').to_string()))
+}
+
+fn name_to_bytes(name: &str) -> [u8; 16] {
+    let mut bytes = [b' '; 16];
+    name.bytes()
+        .filter(|byte| byte >> 7 == 0)
+        .take(16)
+        .enumerate()
+        .for_each(|(index, byte)| bytes[index] = byte);
+    bytes
+}
+
+fn parse_channels(input: &[u8]) -> IResult<&[u8], u16> {
+    let (input, (high, middle, low)) = tuple((be_u7, be_u7, be_u7))(input)?;
+    Ok((
+        input,
+        (((high & 0x03) as u16) << 14) | ((middle as u16) << 7) | low as u16,
+    ))
+}
+
+fn parse_changes(input: &[u8]) -> IResult<&[u8], Vec<(u8, Option<NoteTuning>)>> {
+    let (input, length) = be_u7(input)?;
+    count(tuple((be_u7, NoteTuning::parse)), length as usize)(input)
+}
+
+impl TuningMessage {
+    fn parse_dump<'a>(
+        start: &[u8],
+        input: &'a [u8],
+        sub_id_2: u8,
+        device_id: u8,
+    ) -> IResult<&'a [u8], Self> {
+        let (input, bank) = match sub_id_2 {
+            KEY_BASED_DUMP => be_u7(input).map(|(input, bank)| (input, Some(bank)))?,
+            _ => (input, None),
+        };
+        let (input, program) = be_u7(input)?;
+        let (input, name) = parse_name(input)?;
+        let (input, tunings) = count(NoteTuning::parse, 128)(input)?;
+        let expected = checksum(&start[..start.len() - input.len()]);
+        let (input, _) = terminated(verify(be_u7, |byte| *byte == expected), eof)(input)?;
+        let message = match bank {
+            Some(bank) => Self::KeyBasedDump {
+                device_id,
+                bank,
+                program,
+                name,
+                tunings,
+            },
+            None => Self::BulkDump {
+                device_id,
+                program,
+                name,
+                tunings,
+            },
+        };
+        Ok((input, message))
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let start = input;
+        let (input, universal) =
+            alt((tag(&[UNIVERSAL_NON_REAL_TIME]), tag(&[UNIVERSAL_REAL_TIME])))(input)?;
+        let real_time = universal[0] == UNIVERSAL_REAL_TIME;
+        let (input, device_id) = be_u7(input)?;
+        let (input, _) = tag(&[MIDI_TUNING])(input)?;
+        let (input, sub_id_2) = be_u7(input)?;
+        match (real_time, sub_id_2) {
+            (false, BULK_DUMP) | (false, KEY_BASED_DUMP) => {
+                Self::parse_dump(start, input, sub_id_2, device_id)
+            }
+            (true, SINGLE_NOTE_TUNING_CHANGE) | (_, SINGLE_NOTE_TUNING_CHANGE_BANK) => {
+                let (input, bank) = match sub_id_2 {
+                    SINGLE_NOTE_TUNING_CHANGE_BANK => {
+                        be_u7(input).map(|(input, bank)| (input, Some(bank)))?
+                    }
+                    _ => (input, None),
+                };
+                let (input, program) = be_u7(input)?;
+                let (input, changes) = terminated(parse_changes, eof)(input)?;
+                Ok((
+                    input,
+                    Self::SingleNoteTuningChange {
+                        real_time,
+                        device_id,
+                        bank,
+                        program,
+                        changes,
+                    },
+                ))
+            }
+            (_, SCALE_OCTAVE_1_BYTE) => {
+                let (input, channels) = parse_channels(input)?;
+                let (input, offsets) = terminated(count(be_u7, 12), eof)(input)?;
+                let mut cents = [0; 12];
+                for (cent, offset) in cents.iter_mut().zip(offsets) {
+                    *cent = offset as i8 - 64;
+                }
+                Ok((
+                    input,
+                    Self::ScaleOctaveTuning {
+                        real_time,
+                        device_id,
+                        channels,
+                        offsets: ScaleOffsets::OneByte(cents),
+                    },
+                ))
+            }
+            (_, SCALE_OCTAVE_2_BYTE) => {
+                let (input, channels) = parse_channels(input)?;
+                let (input, offsets) = terminated(count(tuple((be_u7, be_u7)), 12), eof)(input)?;
+                let mut values = [0; 12];
+                for (value, (msb, lsb)) in values.iter_mut().zip(offsets) {
+                    *value = ((msb as u16) << 7) | lsb as u16;
+                }
+                Ok((
+                    input,
+                    Self::ScaleOctaveTuning {
+                        real_time,
+                        device_id,
+                        channels,
+                        offsets: ScaleOffsets::TwoByte(values),
+                    },
+                ))
+            }
+            _ => Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Tag,
+            ))),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::BulkDump {
+                device_id,
+                program,
+                name,
+                tunings,
+            }
+            | Self::KeyBasedDump {
+                device_id,
+                program,
+                name,
+                tunings,
+                ..
+            } => {
+                assert_eq!(tunings.len(), 128, "Expected 128 tunings");
+                let mut bytes = vec![UNIVERSAL_NON_REAL_TIME, *device_id, MIDI_TUNING];
+                match self {
+                    Self::KeyBasedDump { bank, .. } => bytes.extend([KEY_BASED_DUMP, *bank]),
+                    _ => bytes.push(BULK_DUMP),
+                }
+                bytes.push(*program);
+                bytes.extend(name_to_bytes(name));
+                tunings
+                    .iter()
+                    .for_each(|tuning| bytes.extend(NoteTuning::to_bytes(tuning)));
+                bytes.push(checksum(&bytes));
+                bytes
+            }
+            Self::SingleNoteTuningChange {
+                real_time,
+                device_id,
+                bank,
+                program,
+                changes,
+            } => {
+                assert!(changes.len() < 128, "Too many changes: {}", changes.len());
+                let universal = match real_time {
+                    true => UNIVERSAL_REAL_TIME,
+                    false => UNIVERSAL_NON_REAL_TIME,
+                };
+                let mut bytes = vec![universal, *device_id, MIDI_TUNING];
+                match bank {
+                    Some(bank) => bytes.extend([SINGLE_NOTE_TUNING_CHANGE_BANK, *bank]),
+                    None => bytes.push(SINGLE_NOTE_TUNING_CHANGE),
+                }
+                bytes.extend([*program, changes.len() as u8]);
+                changes.iter().for_each(|(key, tuning)| {
+                    bytes.push(*key);
+                    bytes.extend(NoteTuning::to_bytes(tuning));
+                });
+                bytes
+            }
+            Self::ScaleOctaveTuning {
+                real_time,
+                device_id,
+                channels,
+                offsets,
+            } => {
+                let universal = match real_time {
+                    true => UNIVERSAL_REAL_TIME,
+                    false => UNIVERSAL_NON_REAL_TIME,
+                };
+                let mut bytes = vec![universal, *device_id, MIDI_TUNING];
+                bytes.push(match offsets {
+                    ScaleOffsets::OneByte(_) => SCALE_OCTAVE_1_BYTE,
+                    ScaleOffsets::TwoByte(_) => SCALE_OCTAVE_2_BYTE,
+                });
+                bytes.extend([
+                    ((channels >> 14) & 0x03) as u8,
+                    ((channels >> 7) & 0x7f) as u8,
+                    (channels & 0x7f) as u8,
+                ]);
+                match offsets {
+                    ScaleOffsets::OneByte(offsets) => offsets
+                        .iter()
+                        .for_each(|offset| bytes.push((*offset as i16 + 64).clamp(0, 127) as u8)),
+                    ScaleOffsets::TwoByte(offsets) => offsets.iter().for_each(|offset| {
+                        bytes.extend([((offset >> 7) & 0x7f) as u8, (offset & 0x7f) as u8])
+                    }),
+                }
+                bytes
+            }
+        }
+    }
+
+    pub fn key_frequencies(&self) -> Vec<(u8, f64)> {
+        match self {
+            Self::BulkDump { tunings, .. } | Self::KeyBasedDump { tunings, .. } => tunings
+                .iter()
+                .enumerate()
+                .filter_map(|(key, tuning)| tuning.map(|tuning| (key as u8, tuning.frequency())))
+                .collect(),
+            Self::SingleNoteTuningChange { changes, .. } => changes
+                .iter()
+                .filter_map(|(key, tuning)| tuning.map(|tuning| (*key, tuning.frequency())))
+                .collect(),
+            Self::ScaleOctaveTuning { offsets, .. } => {
+                let cents = offsets.cents();
+                (0..128)
+                    .map(|key| {
+                        let offset = cents[key as usize % 12];
+                        (
+                            key,
+                            equal_temperament_frequency(key) * 2f64.powf(offset / 1200.0),
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TuningTable {
+    pub frequencies: [f64; 128],
+}
+
+impl Default for TuningTable {
+    fn default() -> Self {
+        let mut frequencies = [0.0; 128];
+        for (key, frequency) in frequencies.iter_mut().enumerate() {
+            *frequency = equal_temperament_frequency(key as u8);
+        }
+        Self { frequencies }
+    }
+}
+
+impl TuningTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn apply(&mut self, message: &TuningMessage) {
+        for (key, frequency) in message.key_frequencies() {
+            self.frequencies[key as usize] = frequency;
+        }
+    }
+
+    pub fn frequency(&self, key: u8) -> f64 {
+        self.frequencies[key as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NoteTuning, ScaleOffsets, TuningMessage, TuningTable};
+
+    #[test]
+    fn test_bulk_dump() {
+        let mut tunings = (0..128)
+            .map(|key| {
+                Some(NoteTuning {
+                    semitone: key,
+                    fraction: 0,
+                })
+            })
+            .collect::<Vec<_>>();
+        tunings[60] = Some(NoteTuning::from_frequency(262.0));
+        tunings[61] = None;
+        let message = TuningMessage::BulkDump {
+            device_id: 0x7f,
+            program: 3,
+            name: "Just".to_string(),
+            tunings,
+        };
+        let mut bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 406);
+        assert_eq!(&bytes[5..21], b"Just            ");
+        assert_eq!(TuningMessage::parse(&bytes).unwrap().1, message);
+        let mut table = TuningTable::new();
+        table.apply(&message);
+        assert!((table.frequency(60) - 262.0).abs() < 0.01);
+        assert!((table.frequency(69) - 440.0).abs() < 1e-9);
+        *bytes.last_mut().unwrap() ^= 0x01;
+        assert!(TuningMessage::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_single_note_and_scale_octave() {
+        let bytes = [0x7f, 0x00, 0x08, 0x02, 0x00, 0x01, 0x45, 0x45, 0x40, 0x00];
+        let (_, message) = TuningMessage::parse(&bytes).unwrap();
+        assert_eq!(
+            message,
+            TuningMessage::SingleNoteTuningChange {
+                real_time: true,
+                device_id: 0,
+                bank: None,
+                program: 0,
+                changes: vec![(
+                    0x45,
+                    Some(NoteTuning {
+                        semitone: 0x45,
+                        fraction: 0x2000
+                    })
+                )],
+            }
+        );
+        let (key, frequency) = message.key_frequencies()[0];
+        assert_eq!(key, 0x45);
+        assert!((frequency - 452.893).abs() < 0.001);
+        assert_eq!(message.to_bytes(), bytes);
+
+        let mut offsets = [0; 12];
+        offsets[4] = -14;
+        let message = TuningMessage::ScaleOctaveTuning {
+            real_time: false,
+            device_id: 0x7f,
+            channels: 0xffff,
+            offsets: ScaleOffsets::OneByte(offsets),
+        };
+        let bytes = message.to_bytes();
+        assert_eq!(&bytes[..7], [0x7e, 0x7f, 0x08, 0x08, 0x03, 0x7f, 0x7f]);
+        assert_eq!(bytes[11], 50);
+        assert_eq!(TuningMessage::parse(&bytes).unwrap().1, message);
+        let frequencies = message.key_frequencies();
+        assert_eq!(frequencies.len(), 128);
+        assert!((frequencies[64].1 - 326.97).abs() < 0.01);
+        assert_eq!(ScaleOffsets::TwoByte([0x2000; 12]).cents(), [0.0; 12]);
+    }
+}
@@ -1,32 +1,17 @@
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::prelude::*;
 use std::path::Path;
-use walkdir::WalkDir;
 
-use crate::midi_file::MidiFile;
+use crate::scan::{collect_files, scan_files, Outcome, ScanOptions};
 
 #[test]
 fn test_parse() {
     let test_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test_files");
-    let sample_size = 1000;
-    let walker = WalkDir::new(test_dir).into_iter();
-    let midi_files = walker
-        .filter_map(|e| e.ok())
-        .filter(|e| {
-            e.file_type().is_file()
-                && match e.path().extension() {
-                    Some(ext) => ext == "mid",
-                    None => false,
-                }
-        })
-        .collect::<Vec<_>>();
-
-    let mut rng = thread_rng();
-
-    let midi_files = midi_files
-        .choose_multiple(&mut rng, sample_size as usize)
-        .cloned()
-        .collect::<Vec<_>>();
+    let options = ScanOptions {
+        extensions: vec!["mid".to_string()],
+        sample: Some(1000),
+        ..ScanOptions::default()
+    };
+    let midi_files = collect_files(&test_dir, &options);
 
     let pb = ProgressBar::new(midi_files.len() as u64);
     let pb_style = ProgressStyle::default_bar()
@@ -37,58 +22,29 @@ fn test_parse() {
         .progress_chars("##-");
     pb.set_style(pb_style);
 
-    for file in midi_files {
-        let path = file.path();
-        let file_name_osstr = match path.file_name() {
-            Some(s) => s,
-            None => {
-                panic!("No file name for path: {:?}", path);
-            }
-        };
-        let file_name = match file_name_osstr.to_os_string().into_string() {
-            Ok(s) => s,
-            Err(_) => {
-                panic!("Invalid file name for path: {:?}", path);
-            }
-        };
-        pb.set_message(file_name);
-
-        let data = match std::fs::read(path) {
-            Ok(d) => d,
-            Err(_) => {
-                panic!("Could not read file: {:?}", path);
-            }
-        };
-        let midi_file = match MidiFile::parse(&data) {
-            Ok((_, m)) => m,
-            Err(e) => {
-                println!("Could not parse file: {:?}, {:?}", path, e);
-                continue;
-            }
-        };
-        let parsed_data = midi_file.to_bytes();
-        let reopened_midi_file = match MidiFile::parse(&parsed_data) {
-            Ok((_, m)) => m,
-            Err(e) => {
-                println!("Could not re-parse file: {:?}, {:?}", path, e);
-                continue;
-            }
-        };
-        assert_eq!(midi_file.header, reopened_midi_file.header);
-        assert_eq!(midi_file.tracks.len(), reopened_midi_file.tracks.len());
-        for (track, reopened_track) in midi_file
-            .tracks
-            .iter()
-            .zip(reopened_midi_file.tracks.iter())
-        {
-            assert_eq!(track.chunk_type, reopened_track.chunk_type);
-            assert_eq!(track.data.len(), reopened_track.data.len());
-            for (event, reopened_event) in track.data.iter().zip(reopened_track.data.iter()) {
-                assert_eq!(event.delta_time, reopened_event.delta_time);
-                assert_eq!(event.event, reopened_event.event);
-            }
+    let report = scan_files(&midi_files, &options, &|file| {
+        if let Some(file_name) = file.path.file_name() {
+            pb.set_message(file_name.to_string_lossy().to_string());
         }
         pb.inc(1);
-    }
+    });
     pb.finish();
+
+    for file in report.files.iter() {
+        if let Outcome::Error { kind, message, .. } = &file.outcome {
+            println!(
+                "Could not parse file: {:?}, {} {}",
+                file.path, kind, message
+            );
+            assert_ne!(kind, "Panic");
+        }
+        assert!(
+            !file
+                .warnings
+                .iter()
+                .any(|warning| warning == "round trip mismatch"),
+            "Round trip mismatch: {:?}",
+            file.path
+        );
+    }
 }
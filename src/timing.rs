@@ -0,0 +1,239 @@
+use crate::{
+    event::{meta_event::MetaEvent, Event},
+    header::Division,
+    midi_file::MidiFile,
+};
+
+pub const DEFAULT_TEMPO: u32 = 500000;
+
+pub(crate) fn ticks_per_second(frames: u8, ticks: u8) -> f64 {
+    let fps = match -(frames as i8) {
+        29 => 29.97,
+        fps => fps as f64,
+    };
+    fps * ticks as f64
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TimePosition {
+    Ticks(u64),
+    Seconds(f64),
+    Bars(u32),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TempoMap {
+    division: Division,
+    tempos: Vec<(u64, u32)>,
+    seconds: Vec<f64>,
+    time_signatures: Vec<(u64, u8, u8)>,
+}
+
+impl TempoMap {
+    pub fn new(midi_file: &MidiFile) -> Self {
+        let mut tempos = Vec::new();
+        let mut time_signatures = Vec::new();
+        for track in midi_file.tracks.iter() {
+            for (tick, event) in track.to_absolute() {
+                match event {
+                    Event::MetaEvent(MetaEvent::SetTempo { tempo }) if tempo > 0 => {
+                        tempos.push((tick, tempo))
+                    }
+                    Event::MetaEvent(MetaEvent::TimeSignature {
+                        numerator,
+                        denominator,
+                        ..
+                    }) if numerator > 0 => time_signatures.push((tick, numerator, denominator)),
+                    _ => {}
+                }
+            }
+        }
+        Self::from_changes(midi_file.header.data.division, tempos, time_signatures)
+    }
+
+    pub fn from_changes(
+        division: Division,
+        mut tempos: Vec<(u64, u32)>,
+        mut time_signatures: Vec<(u64, u8, u8)>,
+    ) -> Self {
+        tempos.sort_by_key(|(tick, _)| *tick);
+        tempos.reverse();
+        tempos.dedup_by_key(|(tick, _)| *tick);
+        tempos.reverse();
+        if tempos.first().is_none_or(|(tick, _)| *tick > 0) {
+            tempos.insert(0, (0, DEFAULT_TEMPO));
+        }
+        time_signatures.sort_by_key(|(tick, _, _)| *tick);
+        time_signatures.reverse();
+        time_signatures.dedup_by_key(|(tick, _, _)| *tick);
+        time_signatures.reverse();
+        if time_signatures.first().is_none_or(|(tick, _, _)| *tick > 0) {
+            time_signatures.insert(0, (0, 4, 2));
+        }
+
+        let mut seconds = Vec::with_capacity(tempos.len());
+        let mut elapsed = 0.0;
+        for (index, (tick, _)) in tempos.iter().enumerate() {
+            if index > 0 {
+                let (last_tick, last_tempo) = tempos[index - 1];
+                elapsed += Self::segment_seconds(division, tick - last_tick, last_tempo);
+            }
+            seconds.push(elapsed);
+        }
+        Self {
+            division,
+            tempos,
+            seconds,
+            time_signatures,
+        }
+    }
+
+    fn segment_seconds(division: Division, ticks: u64, tempo: u32) -> f64 {
+        match division {
+            Division::TicksPerQuarterNote { ticks: tpqn } => {
+                ticks as f64 * tempo as f64 / (tpqn as f64 * 1_000_000.0)
+            }
+            Division::FramesPerSecond { frames, ticks: tpf } => {
+                ticks as f64 / ticks_per_second(frames, tpf)
+            }
+        }
+    }
+
+    pub fn division(&self) -> Division {
+        self.division
+    }
+
+    pub fn tempo_at(&self, tick: u64) -> u32 {
+        let index = self.tempos.partition_point(|(start, _)| *start <= tick) - 1;
+        self.tempos[index].1
+    }
+
+    pub fn time_signature_at(&self, tick: u64) -> (u8, u8) {
+        let index = self
+            .time_signatures
+            .partition_point(|(start, _, _)| *start <= tick)
+            - 1;
+        let (_, numerator, denominator) = self.time_signatures[index];
+        (numerator, denominator)
+    }
+
+    pub fn tick_to_seconds(&self, tick: u64) -> f64 {
+        let index = self.tempos.partition_point(|(start, _)| *start <= tick) - 1;
+        let (start, tempo) = self.tempos[index];
+        self.seconds[index] + Self::segment_seconds(self.division, tick - start, tempo)
+    }
+
+    pub fn seconds_to_tick(&self, seconds: f64) -> u64 {
+        let seconds = seconds.max(0.0);
+        let index = self.seconds.partition_point(|start| *start <= seconds) - 1;
+        let (start, tempo) = self.tempos[index];
+        let remaining = seconds - self.seconds[index];
+        let ticks = match self.division {
+            Division::TicksPerQuarterNote { ticks } => {
+                remaining * ticks as f64 * 1_000_000.0 / tempo as f64
+            }
+            Division::FramesPerSecond { frames, ticks } => {
+                remaining * ticks_per_second(frames, ticks)
+            }
+        };
+        start + ticks.round() as u64
+    }
+
+    pub fn ticks_per_quarter(&self, tick: u64) -> f64 {
+        match self.division {
+            Division::TicksPerQuarterNote { ticks } => ticks as f64,
+            Division::FramesPerSecond { frames, ticks } => {
+                ticks_per_second(frames, ticks) * self.tempo_at(tick) as f64 / 1_000_000.0
+            }
+        }
+    }
+
+    fn ticks_per_bar(&self, index: usize) -> u64 {
+        let (tick, numerator, denominator) = self.time_signatures[index];
+        let quarters = numerator as f64 * 4.0 / 2f64.powi(denominator as i32);
+        ((quarters * self.ticks_per_quarter(tick)).round() as u64).max(1)
+    }
+
+    pub fn bar_to_tick(&self, bar: u32) -> u64 {
+        let mut remaining = bar as u64;
+        for index in 0..self.time_signatures.len() {
+            let start = self.time_signatures[index].0;
+            let bar_length = self.ticks_per_bar(index);
+            if let Some((next, _, _)) = self.time_signatures.get(index + 1) {
+                let bars = (next - start).div_ceil(bar_length);
+                if remaining >= bars {
+                    remaining -= bars;
+                    continue;
+                }
+            }
+            return start + remaining * bar_length;
+        }
+        unreachable!()
+    }
+
+    pub fn tick_to_bar(&self, tick: u64) -> (u32, u64) {
+        let mut bar = 0;
+        for index in 0..self.time_signatures.len() {
+            let start = self.time_signatures[index].0;
+            let bar_length = self.ticks_per_bar(index);
+            match self.time_signatures.get(index + 1) {
+                Some((next, _, _)) if *next <= tick => {
+                    bar += (next - start).div_ceil(bar_length);
+                }
+                _ => {
+                    let offset = tick - start;
+                    return ((bar + offset / bar_length) as u32, offset % bar_length);
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    pub fn to_tick(&self, position: TimePosition) -> u64 {
+        match position {
+            TimePosition::Ticks(tick) => tick,
+            TimePosition::Seconds(seconds) => self.seconds_to_tick(seconds),
+            TimePosition::Bars(bar) => self.bar_to_tick(bar),
+        }
+    }
+}
+
+impl MidiFile {
+    pub fn tempo_map(&self) -> TempoMap {
+        TempoMap::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::header::Division;
+
+    use super::TempoMap;
+
+    #[test]
+    fn test_seconds() {
+        let tempo_map = TempoMap::from_changes(
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![(96, 250000)],
+            vec![],
+        );
+        assert_eq!(tempo_map.tick_to_seconds(96), 0.5);
+        assert_eq!(tempo_map.tick_to_seconds(192), 0.75);
+        assert_eq!(tempo_map.seconds_to_tick(0.75), 192);
+        assert_eq!(tempo_map.seconds_to_tick(0.25), 48);
+    }
+
+    #[test]
+    fn test_bars() {
+        let tempo_map = TempoMap::from_changes(
+            Division::TicksPerQuarterNote { ticks: 4 },
+            vec![],
+            vec![(32, 3, 3)],
+        );
+        assert_eq!(tempo_map.bar_to_tick(1), 16);
+        assert_eq!(tempo_map.bar_to_tick(2), 32);
+        assert_eq!(tempo_map.bar_to_tick(3), 38);
+        assert_eq!(tempo_map.tick_to_bar(40), (3, 2));
+        assert_eq!(tempo_map.tick_to_bar(31), (1, 15));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::midi_file::MidiFile;
+
+use super::{Token, Tokenizer};
+
+// Family, bar or position, time signature, tempo, program, pitch, velocity and duration
+pub const COMPOUND_FIELDS: usize = 8;
+
+impl Tokenizer {
+    // REMI tokens grouped into metrical and note words
+    pub fn compound(&self, midi_file: &MidiFile) -> Option<Vec<[Token; COMPOUND_FIELDS]>> {
+        let mut words: Vec<[Token; COMPOUND_FIELDS]> = Vec::new();
+        for token in self.remi(midi_file)? {
+            let field = match token {
+                Token::Bar | Token::Position(_) => 1,
+                Token::TimeSignature(..) => 2,
+                Token::Tempo(_) => 3,
+                Token::Program(_) => 4,
+                Token::Pitch(_) => 5,
+                Token::Velocity(_) => 6,
+                _ => 7,
+            };
+            let family = match field {
+                1..=3 => Token::Metrical,
+                _ => Token::Note,
+            };
+            // A bar, a position, a program or a pitch without a program starts a new word
+            let continues = words
+                .last()
+                .is_some_and(|word| word[0] == family && word[field] == Token::Ignore)
+                && field != 1
+                && (field != 5 || self.config.programs);
+            if !continues {
+                let mut word = [Token::Ignore; COMPOUND_FIELDS];
+                word[0] = family;
+                words.push(word);
+            }
+            words.last_mut().unwrap()[field] = token;
+        }
+        Some(words)
+    }
+
+    pub fn from_compound(&self, words: &[[Token; COMPOUND_FIELDS]]) -> Option<MidiFile> {
+        let tokens = words
+            .iter()
+            .flat_map(|word| word[1..].iter().copied())
+            .filter(|token| *token != Token::Ignore)
+            .collect::<Vec<_>>();
+        self.from_remi(&tokens)
+    }
+}
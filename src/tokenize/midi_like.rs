@@ -0,0 +1,91 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::midi_file::MidiFile;
+
+use super::{GridNote, Token, Tokenizer};
+
+impl Tokenizer {
+    // Note on and note off events separated by time shifts
+    pub fn midi_like(&self, midi_file: &MidiFile) -> Option<Vec<Token>> {
+        let piece = self.quantize(midi_file)?;
+        let program = |note: &GridNote| match self.config.programs {
+            true => vec![Token::Program(note.program)],
+            false => Vec::new(),
+        };
+        // Note offs go first at a step, then tempo changes, then note ons
+        let mut events = Vec::with_capacity(piece.notes.len() * 2 + piece.tempos.len());
+        for note in piece.notes.iter() {
+            let mut off = program(note);
+            off.push(Token::NoteOff(note.pitch));
+            events.push((note.start + note.duration, 0, off));
+            let mut on = program(note);
+            on.push(Token::Velocity(note.velocity));
+            on.push(Token::NoteOn(note.pitch));
+            events.push((note.start, 2, on));
+        }
+        for (step, bin) in piece.tempos.iter() {
+            events.push((*step, 1, vec![Token::Tempo(*bin)]));
+        }
+        events.sort_by_key(|(step, order, _)| (*step, *order));
+
+        let max_shift = (self.config.max_time_shift * self.config.beat_resolution) as u64;
+        let mut tokens = Vec::new();
+        let mut time = 0;
+        for (step, _, event) in events {
+            while time < step {
+                let shift = (step - time).min(max_shift);
+                tokens.push(Token::TimeShift(shift as u16));
+                time += shift;
+            }
+            tokens.extend(event);
+        }
+        Some(tokens)
+    }
+
+    // Notes are paired first in, first out and any left sounding end with the sequence
+    pub fn from_midi_like(&self, tokens: &[Token]) -> Option<MidiFile> {
+        let mut notes = Vec::new();
+        let mut tempos = Vec::new();
+        let mut sounding: HashMap<(u8, u8), VecDeque<(u64, u8)>> = HashMap::new();
+        let mut time = 0;
+        let mut program = 0;
+        let mut velocity = self.velocity_bin(64);
+        for token in tokens.iter().filter(|token| self.id(token).is_some()) {
+            match *token {
+                Token::TimeShift(shift) => time += shift as u64,
+                Token::Tempo(bin) => tempos.push((time, bin)),
+                Token::Program(number) => program = number,
+                Token::Velocity(bin) => velocity = bin,
+                Token::NoteOn(pitch) => sounding
+                    .entry((program, pitch))
+                    .or_default()
+                    .push_back((time, velocity)),
+                Token::NoteOff(pitch) => {
+                    if let Some((start, velocity)) = sounding
+                        .get_mut(&(program, pitch))
+                        .and_then(|starts| starts.pop_front())
+                    {
+                        notes.push(GridNote {
+                            start,
+                            duration: (time - start).max(1),
+                            program,
+                            pitch,
+                            velocity,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        for ((program, pitch), starts) in sounding {
+            notes.extend(starts.into_iter().map(|(start, velocity)| GridNote {
+                start,
+                duration: (time - start).max(1),
+                program,
+                pitch,
+                velocity,
+            }));
+        }
+        self.assemble(notes, tempos, Vec::new())
+    }
+}
@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+
+use crate::{
+    channel_state::value_at,
+    event::{meta_event::MetaEvent, midi_event::ChannelVoiceMessage, Event},
+    general_midi::{DRUMS, PERCUSSION_CHANNEL},
+    header::Division,
+    midi_file::MidiFile,
+    note::Note,
+    track::TrackChunk,
+};
+
+pub mod compound;
+pub mod midi_like;
+pub mod octuple;
+pub mod remi;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Token {
+    Pad,
+    Bos,
+    Eos,
+    // Fills the unused fields of compound and octuple words
+    Ignore,
+    Metrical,
+    Note,
+    Bar,
+    BarIndex(u16),
+    Position(u16),
+    TimeSignature(u8, u8),
+    Tempo(u8),
+    Program(u8),
+    Pitch(u8),
+    NoteOn(u8),
+    NoteOff(u8),
+    Velocity(u8),
+    Duration(u16),
+    TimeShift(u16),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TokenizerConfig {
+    // Grid steps per quarter note, for positions, durations and time shifts
+    pub beat_resolution: u16,
+    pub velocity_bins: u8,
+    pub tempo_bins: u8,
+    pub tempo_range: (f64, f64),
+    // In quarter notes
+    pub max_duration: u16,
+    pub max_time_shift: u16,
+    pub max_bars: u16,
+    // Other time signatures are read as 4/4
+    pub time_signatures: Vec<(u8, u8)>,
+    pub programs: bool,
+}
+
+impl Default for TokenizerConfig {
+    fn default() -> Self {
+        Self {
+            beat_resolution: 8,
+            velocity_bins: 32,
+            tempo_bins: 32,
+            tempo_range: (40.0, 250.0),
+            max_duration: 8,
+            max_time_shift: 4,
+            max_bars: 512,
+            time_signatures: vec![
+                (2, 2),
+                (3, 2),
+                (2, 4),
+                (3, 4),
+                (4, 4),
+                (5, 4),
+                (6, 4),
+                (3, 8),
+                (5, 8),
+                (6, 8),
+                (7, 8),
+                (9, 8),
+                (12, 8),
+            ],
+            programs: true,
+        }
+    }
+}
+
+// Notes on the grid, with the velocity as a bin
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct GridNote {
+    start: u64,
+    duration: u64,
+    program: u8,
+    pitch: u8,
+    velocity: u8,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Bar {
+    start: u64,
+    length: u64,
+    time_signature: (u8, u8),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+struct Piece {
+    notes: Vec<GridNote>,
+    tempos: Vec<(u64, u8)>,
+    time_signatures: Vec<(u64, (u8, u8))>,
+}
+
+pub struct Tokenizer {
+    pub config: TokenizerConfig,
+    vocabulary: Vec<Token>,
+    ids: HashMap<Token, u32>,
+}
+
+impl Tokenizer {
+    pub fn new(config: TokenizerConfig) -> Self {
+        assert!(config.beat_resolution > 0 && config.velocity_bins > 0 && config.tempo_bins > 0);
+        assert!(config.max_duration > 0 && config.max_time_shift > 0 && config.max_bars > 0);
+        assert!(config.time_signatures.contains(&(4, 4)));
+        // Every bar has to be a whole number of steps
+        assert!(config
+            .time_signatures
+            .iter()
+            .all(|(numerator, denominator)| *numerator > 0
+                && (config.beat_resolution as u64 * 4).is_multiple_of(*denominator as u64)));
+
+        let mut vocabulary = vec![
+            Token::Pad,
+            Token::Bos,
+            Token::Eos,
+            Token::Ignore,
+            Token::Metrical,
+            Token::Note,
+            Token::Bar,
+        ];
+        vocabulary.extend((0..config.max_bars).map(Token::BarIndex));
+        let positions = config
+            .time_signatures
+            .iter()
+            .map(|(numerator, denominator)| {
+                *numerator as u16 * config.beat_resolution * 4 / *denominator as u16
+            })
+            .max()
+            .unwrap();
+        vocabulary.extend((0..positions).map(Token::Position));
+        vocabulary.extend(
+            config
+                .time_signatures
+                .iter()
+                .map(|(numerator, denominator)| Token::TimeSignature(*numerator, *denominator)),
+        );
+        vocabulary.extend((0..config.tempo_bins).map(Token::Tempo));
+        vocabulary.extend((0..=DRUMS).map(Token::Program));
+        vocabulary.extend((0..128).map(Token::Pitch));
+        vocabulary.extend((0..128).map(Token::NoteOn));
+        vocabulary.extend((0..128).map(Token::NoteOff));
+        vocabulary.extend((0..config.velocity_bins).map(Token::Velocity));
+        vocabulary.extend((1..=config.max_duration * config.beat_resolution).map(Token::Duration));
+        vocabulary
+            .extend((1..=config.max_time_shift * config.beat_resolution).map(Token::TimeShift));
+        let ids = vocabulary
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (*token, id as u32))
+            .collect();
+        Self {
+            config,
+            vocabulary,
+            ids,
+        }
+    }
+
+    pub fn vocabulary(&self) -> &[Token] {
+        &self.vocabulary
+    }
+
+    pub fn id(&self, token: &Token) -> Option<u32> {
+        self.ids.get(token).copied()
+    }
+
+    pub fn token(&self, id: u32) -> Option<Token> {
+        self.vocabulary.get(id as usize).copied()
+    }
+
+    pub fn to_ids(&self, tokens: &[Token]) -> Option<Vec<u32>> {
+        tokens.iter().map(|token| self.id(token)).collect()
+    }
+
+    pub fn from_ids(&self, ids: &[u32]) -> Option<Vec<Token>> {
+        ids.iter().map(|id| self.token(*id)).collect()
+    }
+
+    // Bins are spread evenly from the quietest to the loudest velocity
+    fn velocity(&self, bin: u8) -> u8 {
+        ((bin as f64 + 1.0) * 127.0 / self.config.velocity_bins as f64).round() as u8
+    }
+
+    fn velocity_bin(&self, velocity: u8) -> u8 {
+        (0..self.config.velocity_bins)
+            .min_by_key(|bin| self.velocity(*bin).abs_diff(velocity))
+            .unwrap()
+    }
+
+    fn bpm(&self, bin: u8) -> f64 {
+        let (min, max) = self.config.tempo_range;
+        match self.config.tempo_bins {
+            1 => min,
+            bins => min + (max - min) * bin as f64 / (bins - 1) as f64,
+        }
+    }
+
+    fn tempo_bin(&self, tempo: u32) -> u8 {
+        let bpm = 60_000_000.0 / tempo as f64;
+        (0..self.config.tempo_bins)
+            .min_by(|a, b| {
+                (self.bpm(*a) - bpm)
+                    .abs()
+                    .total_cmp(&(self.bpm(*b) - bpm).abs())
+            })
+            .unwrap()
+    }
+
+    fn bar_length(&self, (numerator, denominator): (u8, u8)) -> u64 {
+        numerator as u64 * self.config.beat_resolution as u64 * 4 / denominator as u64
+    }
+
+    // Time signature changes take effect at the next bar line
+    fn bars(&self, time_signatures: &[(u64, (u8, u8))], end: u64) -> Vec<Bar> {
+        let mut bars = Vec::new();
+        let mut changes = time_signatures.iter().peekable();
+        let mut time_signature = (4, 4);
+        let mut start = 0;
+        while bars.is_empty() || start <= end {
+            while let Some((_, change)) = changes.next_if(|(step, _)| *step <= start) {
+                time_signature = *change;
+            }
+            let length = self.bar_length(time_signature);
+            bars.push(Bar {
+                start,
+                length,
+                time_signature,
+            });
+            start += length;
+        }
+        bars
+    }
+
+    fn max_duration(&self) -> u64 {
+        (self.config.max_duration * self.config.beat_resolution) as u64
+    }
+
+    fn quantize(&self, midi_file: &MidiFile) -> Option<Piece> {
+        let Division::TicksPerQuarterNote { ticks } = midi_file.header.data.division else {
+            return None;
+        };
+        let step = |tick: u64| {
+            (tick as f64 * self.config.beat_resolution as f64 / ticks as f64).round() as u64
+        };
+        let mut piece = Piece::default();
+        let programs = midi_file.program_changes();
+        let mut tempos = Vec::new();
+        for track in midi_file.tracks.iter() {
+            for (tick, event) in track.to_absolute() {
+                match event {
+                    Event::MetaEvent(MetaEvent::SetTempo { tempo }) if tempo > 0 => {
+                        tempos.push((step(tick), self.tempo_bin(tempo)))
+                    }
+                    Event::MetaEvent(MetaEvent::TimeSignature {
+                        numerator,
+                        denominator,
+                        ..
+                    }) => {
+                        let time_signature =
+                            (numerator, 1u8.checked_shl(denominator as u32).unwrap_or(0));
+                        piece.time_signatures.push((
+                            step(tick),
+                            match self.config.time_signatures.contains(&time_signature) {
+                                true => time_signature,
+                                false => (4, 4),
+                            },
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        tempos.sort_by_key(|(step, _)| *step);
+        piece.time_signatures.sort_by_key(|(step, _)| *step);
+        for (step, bin) in tempos {
+            match piece.tempos.last_mut() {
+                Some((last, last_bin)) if *last == step => *last_bin = bin,
+                Some((_, last_bin)) if *last_bin == bin => {}
+                _ => piece.tempos.push((step, bin)),
+            }
+        }
+
+        for note in midi_file.tracks.iter().flat_map(|track| track.notes()) {
+            let program = match note.channel {
+                // Without programs there is no way to tell drums from pitched notes
+                PERCUSSION_CHANNEL if !self.config.programs => continue,
+                PERCUSSION_CHANNEL => DRUMS,
+                _ if !self.config.programs => 0,
+                channel => value_at(&programs[channel as usize], note.start).unwrap_or(0),
+            };
+            let start = step(note.start);
+            piece.notes.push(GridNote {
+                start,
+                duration: step(note.end).saturating_sub(start).max(1),
+                program,
+                pitch: note.key,
+                velocity: self.velocity_bin(note.velocity),
+            });
+        }
+        piece
+            .notes
+            .sort_by_key(|note| (note.start, note.program, note.pitch));
+        Some(piece)
+    }
+
+    // A conductor track with the tempos and time signatures, then one track per program
+    fn assemble(
+        &self,
+        mut notes: Vec<GridNote>,
+        tempos: Vec<(u64, u8)>,
+        time_signatures: Vec<(u64, (u8, u8))>,
+    ) -> Option<MidiFile> {
+        notes.sort_by_key(|note| (note.start, note.program, note.pitch));
+        let end = notes
+            .iter()
+            .map(|note| note.start + note.duration)
+            .chain(tempos.iter().map(|(step, _)| *step))
+            .chain(time_signatures.iter().map(|(step, _)| *step))
+            .max()
+            .unwrap_or(0);
+        let mut conductor = Vec::new();
+        for (step, bin) in tempos {
+            conductor.push((
+                step,
+                Event::MetaEvent(MetaEvent::SetTempo {
+                    tempo: (60_000_000.0 / self.bpm(bin)).round() as u32,
+                }),
+            ));
+        }
+        for (step, (numerator, denominator)) in time_signatures {
+            conductor.push((
+                step,
+                Event::MetaEvent(MetaEvent::TimeSignature {
+                    numerator,
+                    denominator: denominator.trailing_zeros() as u8,
+                    clocks_per_metronome_click: 24,
+                    thirty_seconds_per_quarter_note: 8,
+                }),
+            ));
+        }
+        conductor.sort_by_key(|(step, _)| *step);
+        conductor.push((end, Event::MetaEvent(MetaEvent::EndOfTrack)));
+        let mut tracks = vec![TrackChunk::from_absolute(conductor)];
+
+        let mut programs = notes.iter().map(|note| note.program).collect::<Vec<_>>();
+        programs.sort();
+        programs.dedup();
+        // Each program keeps a channel of its own, so there is no room for more than 15
+        let mut channels = (0..16).filter(|channel| *channel != PERCUSSION_CHANNEL);
+        for program in programs {
+            let channel = match program {
+                DRUMS => PERCUSSION_CHANNEL,
+                _ => channels.next()?,
+            };
+            let track_notes = notes
+                .iter()
+                .filter(|note| note.program == program)
+                .map(|note| Note {
+                    channel,
+                    key: note.pitch,
+                    velocity: self.velocity(note.velocity).max(1),
+                    start: note.start,
+                    end: note.start + note.duration,
+                })
+                .collect::<Vec<_>>();
+            let mut events = TrackChunk::from_notes(&track_notes).to_absolute();
+            if program != DRUMS {
+                events.insert(
+                    0,
+                    (
+                        0,
+                        Event::from(ChannelVoiceMessage::ProgramChange { channel, program }),
+                    ),
+                );
+            }
+            tracks.push(TrackChunk::from_absolute(events));
+        }
+        Some(MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote {
+                ticks: self.config.beat_resolution,
+            },
+            tracks,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{meta_event::MetaEvent, midi_event::ChannelVoiceMessage, Event},
+        header::Division,
+        midi_file::MidiFile,
+        note::Note,
+        track::TrackChunk,
+    };
+
+    use super::{Token, Tokenizer, TokenizerConfig, DRUMS};
+
+    fn instrument(channel: u8, program: u8, notes: &[(u8, u8, u64, u64)]) -> TrackChunk {
+        let notes = notes
+            .iter()
+            .map(|(key, velocity, start, end)| Note {
+                channel,
+                key: *key,
+                velocity: *velocity,
+                start: *start,
+                end: *end,
+            })
+            .collect::<Vec<_>>();
+        let mut events = TrackChunk::from_notes(&notes).to_absolute();
+        events.insert(
+            0,
+            (
+                0,
+                Event::from(ChannelVoiceMessage::ProgramChange { channel, program }),
+            ),
+        );
+        TrackChunk::from_absolute(events)
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let time_signature = |numerator, denominator| {
+            Event::MetaEvent(MetaEvent::TimeSignature {
+                numerator,
+                denominator,
+                clocks_per_metronome_click: 24,
+                thirty_seconds_per_quarter_note: 8,
+            })
+        };
+        let conductor = TrackChunk::from_absolute(vec![
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 500000 })),
+            (0, time_signature(3, 2)),
+            (288, time_signature(4, 2)),
+            (480, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let midi_file = MidiFile::new(
+            1,
+            Division::TicksPerQuarterNote { ticks: 96 },
+            vec![
+                conductor,
+                instrument(
+                    0,
+                    0,
+                    &[(60, 100, 0, 96), (64, 80, 96, 144), (67, 90, 288, 480)],
+                ),
+                instrument(1, 33, &[(36, 90, 0, 288)]),
+                instrument(9, 0, &[(36, 127, 0, 6)]),
+            ],
+        );
+        let tokenizer = Tokenizer::new(TokenizerConfig::default());
+
+        let remi = tokenizer.remi(&midi_file).unwrap();
+        let note = |program, pitch, velocity, duration| {
+            [
+                Token::Program(program),
+                Token::Pitch(pitch),
+                Token::Velocity(velocity),
+                Token::Duration(duration),
+            ]
+        };
+        let mut expected = vec![
+            Token::Bar,
+            Token::TimeSignature(3, 4),
+            Token::Position(0),
+            Token::Tempo(12),
+        ];
+        expected.extend(note(0, 60, 24, 8));
+        expected.extend(note(33, 36, 22, 24));
+        expected.extend(note(DRUMS, 36, 31, 1));
+        expected.push(Token::Position(8));
+        expected.extend(note(0, 64, 19, 4));
+        expected.extend([Token::Bar, Token::TimeSignature(4, 4), Token::Position(0)]);
+        expected.extend(note(0, 67, 22, 16));
+        assert_eq!(remi, expected);
+        let decoded = tokenizer.from_remi(&remi).unwrap();
+        assert_eq!(decoded.tracks.len(), 4);
+        assert_eq!(
+            decoded.notes()[3],
+            [Note {
+                channel: 9,
+                key: 36,
+                velocity: 127,
+                start: 0,
+                end: 1,
+            }]
+        );
+        assert_eq!(tokenizer.remi(&decoded).unwrap(), remi);
+        // Every program needs a channel of its own
+        let programs = |count| {
+            (0..count)
+                .flat_map(|program| note(program, 60, 20, 4))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(tokenizer.from_remi(&programs(15)).unwrap().tracks.len(), 16);
+        assert_eq!(tokenizer.from_remi(&programs(16)), None);
+
+        let ids = tokenizer.to_ids(&remi).unwrap();
+        assert_eq!(tokenizer.from_ids(&ids).unwrap(), remi);
+        assert_eq!(tokenizer.id(&tokenizer.vocabulary()[100]), Some(100));
+
+        let midi_like = tokenizer.midi_like(&midi_file).unwrap();
+        assert_eq!(
+            midi_like[..8],
+            [
+                Token::Tempo(12),
+                Token::Program(0),
+                Token::Velocity(24),
+                Token::NoteOn(60),
+                Token::Program(33),
+                Token::Velocity(22),
+                Token::NoteOn(36),
+                Token::Program(DRUMS),
+            ]
+        );
+        assert_eq!(
+            tokenizer
+                .midi_like(&tokenizer.from_midi_like(&midi_like).unwrap())
+                .unwrap(),
+            midi_like
+        );
+
+        let compound = tokenizer.compound(&midi_file).unwrap();
+        assert_eq!(compound.len(), 10);
+        assert_eq!(
+            compound[1],
+            [
+                Token::Metrical,
+                Token::Position(0),
+                Token::Ignore,
+                Token::Tempo(12),
+                Token::Ignore,
+                Token::Ignore,
+                Token::Ignore,
+                Token::Ignore,
+            ]
+        );
+        assert_eq!(
+            tokenizer
+                .compound(&tokenizer.from_compound(&compound).unwrap())
+                .unwrap(),
+            compound
+        );
+
+        let octuple = tokenizer.octuple(&midi_file).unwrap();
+        assert_eq!(
+            octuple[4],
+            [
+                Token::Pitch(67),
+                Token::Velocity(22),
+                Token::Duration(16),
+                Token::Program(0),
+                Token::Position(0),
+                Token::BarIndex(1),
+                Token::Tempo(12),
+                Token::TimeSignature(4, 4),
+            ]
+        );
+        assert_eq!(
+            tokenizer
+                .octuple(&tokenizer.from_octuple(&octuple).unwrap())
+                .unwrap(),
+            octuple
+        );
+
+        let smpte = MidiFile::new(
+            0,
+            Division::FramesPerSecond {
+                frames: 0xe7,
+                ticks: 40,
+            },
+            vec![],
+        );
+        assert!(tokenizer.remi(&smpte).is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{midi_file::MidiFile, timing::DEFAULT_TEMPO};
+
+use super::{GridNote, Token, Tokenizer};
+
+// Pitch, velocity, duration, program, position, bar, tempo and time signature
+pub const OCTUPLE_FIELDS: usize = 8;
+
+impl Tokenizer {
+    // One word per note, None when a note lies beyond the last bar in the vocabulary
+    pub fn octuple(&self, midi_file: &MidiFile) -> Option<Vec<[Token; OCTUPLE_FIELDS]>> {
+        let piece = self.quantize(midi_file)?;
+        let end = piece.notes.last().map_or(0, |note| note.start);
+        let bars = self.bars(&piece.time_signatures, end);
+        let mut tempos = piece.tempos.iter().peekable();
+        let mut tempo = self.tempo_bin(DEFAULT_TEMPO);
+        let mut words = Vec::with_capacity(piece.notes.len());
+        for note in piece.notes.iter() {
+            while let Some((_, bin)) = tempos.next_if(|(step, _)| *step <= note.start) {
+                tempo = *bin;
+            }
+            let index = bars.partition_point(|bar| bar.start <= note.start) - 1;
+            if index >= self.config.max_bars as usize {
+                return None;
+            }
+            let bar = bars[index];
+            words.push([
+                Token::Pitch(note.pitch),
+                Token::Velocity(note.velocity),
+                Token::Duration(note.duration.min(self.max_duration()) as u16),
+                match self.config.programs {
+                    true => Token::Program(note.program),
+                    false => Token::Ignore,
+                },
+                Token::Position((note.start - bar.start) as u16),
+                Token::BarIndex(index as u16),
+                Token::Tempo(tempo),
+                Token::TimeSignature(bar.time_signature.0, bar.time_signature.1),
+            ]);
+        }
+        Some(words)
+    }
+
+    // Bars without notes keep the time signature of the bar before them
+    pub fn from_octuple(&self, words: &[[Token; OCTUPLE_FIELDS]]) -> Option<MidiFile> {
+        let mut parsed = Vec::new();
+        for word in words {
+            if word.iter().any(|token| self.id(token).is_none()) {
+                continue;
+            }
+            let [Token::Pitch(pitch), Token::Velocity(velocity), Token::Duration(duration), program, Token::Position(position), Token::BarIndex(bar), Token::Tempo(tempo), Token::TimeSignature(numerator, denominator)] =
+                *word
+            else {
+                continue;
+            };
+            let program = match program {
+                Token::Program(program) => program,
+                _ => 0,
+            };
+            parsed.push((
+                bar as usize,
+                position,
+                (numerator, denominator),
+                tempo,
+                GridNote {
+                    start: 0,
+                    duration: duration as u64,
+                    program,
+                    pitch,
+                    velocity,
+                },
+            ));
+        }
+
+        let bar_count = parsed.iter().map(|(bar, ..)| bar + 1).max().unwrap_or(0);
+        let mut signatures = vec![None; bar_count];
+        for (bar, _, time_signature, ..) in parsed.iter() {
+            signatures[*bar].get_or_insert(*time_signature);
+        }
+        let mut starts = Vec::with_capacity(bar_count);
+        let mut time_signatures = Vec::new();
+        let mut time_signature = (4, 4);
+        let mut start = 0;
+        for signature in signatures {
+            time_signature = signature.unwrap_or(time_signature);
+            if time_signatures
+                .last()
+                .is_none_or(|(_, last)| *last != time_signature)
+            {
+                time_signatures.push((start, time_signature));
+            }
+            starts.push(start);
+            start += self.bar_length(time_signature);
+        }
+
+        let mut notes = parsed
+            .into_iter()
+            .map(|(bar, position, _, tempo, note)| {
+                (
+                    tempo,
+                    GridNote {
+                        start: starts[bar] + position as u64,
+                        ..note
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        notes.sort_by_key(|(_, note)| note.start);
+        let mut tempos: Vec<(u64, u8)> = Vec::new();
+        for (tempo, note) in notes.iter() {
+            match tempos.last() {
+                None => tempos.push((0, *tempo)),
+                Some((_, last)) if last != tempo => tempos.push((note.start, *tempo)),
+                _ => {}
+            }
+        }
+        self.assemble(
+            notes.into_iter().map(|(_, note)| note).collect(),
+            tempos,
+            time_signatures,
+        )
+    }
+}
@@ -0,0 +1,107 @@
+use crate::midi_file::MidiFile;
+
+use super::{GridNote, Token, Tokenizer};
+
+impl Tokenizer {
+    fn note_tokens(&self, note: &GridNote) -> Vec<Token> {
+        let mut tokens = Vec::with_capacity(4);
+        if self.config.programs {
+            tokens.push(Token::Program(note.program));
+        }
+        tokens.push(Token::Pitch(note.pitch));
+        tokens.push(Token::Velocity(note.velocity));
+        tokens.push(Token::Duration(
+            note.duration.min(self.max_duration()) as u16
+        ));
+        tokens
+    }
+
+    // Each bar starts with Bar and each onset with its Position in the bar
+    pub fn remi(&self, midi_file: &MidiFile) -> Option<Vec<Token>> {
+        let piece = self.quantize(midi_file)?;
+        let end = piece
+            .notes
+            .iter()
+            .map(|note| note.start)
+            .chain(piece.tempos.iter().map(|(step, _)| *step))
+            .max()
+            .unwrap_or(0);
+        let bars = self.bars(&piece.time_signatures, end);
+        let mut notes = piece.notes.iter().peekable();
+        let mut tempos = piece.tempos.iter().peekable();
+        let mut tokens = Vec::new();
+        for (index, bar) in bars.iter().enumerate() {
+            tokens.push(Token::Bar);
+            if index == 0 || bars[index - 1].time_signature != bar.time_signature {
+                let (numerator, denominator) = bar.time_signature;
+                tokens.push(Token::TimeSignature(numerator, denominator));
+            }
+            loop {
+                let step = notes
+                    .peek()
+                    .map(|note| note.start)
+                    .into_iter()
+                    .chain(tempos.peek().map(|(step, _)| *step))
+                    .min();
+                let Some(step) = step.filter(|step| *step < bar.start + bar.length) else {
+                    break;
+                };
+                tokens.push(Token::Position((step - bar.start) as u16));
+                if let Some((_, bin)) = tempos.next_if(|(tempo_step, _)| *tempo_step == step) {
+                    tokens.push(Token::Tempo(*bin));
+                }
+                while let Some(note) = notes.next_if(|note| note.start == step) {
+                    tokens.extend(self.note_tokens(note));
+                }
+            }
+        }
+        Some(tokens)
+    }
+
+    // Tokens outside the vocabulary and incomplete notes are skipped
+    pub fn from_remi(&self, tokens: &[Token]) -> Option<MidiFile> {
+        let mut notes = Vec::new();
+        let mut tempos = Vec::new();
+        let mut time_signatures = Vec::new();
+        let mut bar_start = None;
+        let mut bar_length = self.bar_length((4, 4));
+        let mut position = 0;
+        let mut program = 0;
+        let mut pitch = None;
+        let mut velocity = None;
+        for token in tokens.iter().filter(|token| self.id(token).is_some()) {
+            match *token {
+                Token::Bar => {
+                    let start = bar_start.map_or(0, |start| start + bar_length);
+                    bar_start = Some(start);
+                    position = start;
+                }
+                Token::TimeSignature(numerator, denominator) => {
+                    bar_length = self.bar_length((numerator, denominator));
+                    time_signatures.push((bar_start.unwrap_or(0), (numerator, denominator)));
+                }
+                Token::Position(offset) => position = bar_start.unwrap_or(0) + offset as u64,
+                Token::Tempo(bin) => tempos.push((position, bin)),
+                Token::Program(number) => program = number,
+                Token::Pitch(number) => {
+                    pitch = Some(number);
+                    velocity = None;
+                }
+                Token::Velocity(bin) => velocity = Some(bin),
+                Token::Duration(duration) => {
+                    if let (Some(pitch), Some(velocity)) = (pitch.take(), velocity.take()) {
+                        notes.push(GridNote {
+                            start: position,
+                            duration: duration as u64,
+                            program,
+                            pitch,
+                            velocity,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.assemble(notes, tempos, time_signatures)
+    }
+}
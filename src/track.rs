@@ -1,11 +1,15 @@
-use crate::event::{meta_event::MetaEvent, Event, MTrkEvent};
+use crate::{
+    event::{meta_event::MetaEvent, Event, MTrkEvent},
+    variable_length_quantity::VariableLengthQuantity,
+};
 use nom::{
     bytes::complete::{tag, take},
     number::complete::be_u32,
     IResult,
 };
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TrackChunk {
     pub chunk_type: String,
     pub length: u32,
@@ -43,6 +47,63 @@ impl TrackChunk {
         ))
     }
 
+    pub fn new(data: Vec<MTrkEvent>) -> Self {
+        let length = data.iter().map(|event| event.to_bytes().len() as u32).sum();
+        Self {
+            chunk_type: "MTrk".to_string(),
+            length,
+            data,
+        }
+    }
+
+    pub fn from_absolute(events: Vec<(u64, Event)>) -> Self {
+        let mut last_tick = 0;
+        let data = events
+            .into_iter()
+            .map(|(tick, event)| {
+                assert!(tick >= last_tick, "Events are not sorted by tick");
+                assert!(tick - last_tick <= 0x0fff_ffff, "Delta time too large");
+                let delta_time = VariableLengthQuantity {
+                    value: (tick - last_tick) as u32,
+                };
+                last_tick = tick;
+                MTrkEvent { delta_time, event }
+            })
+            .collect();
+        Self::new(data)
+    }
+
+    pub fn to_absolute(&self) -> Vec<(u64, Event)> {
+        let mut tick = 0;
+        self.data
+            .iter()
+            .map(|event| {
+                tick += event.delta_time.value as u64;
+                (tick, event.event.clone())
+            })
+            .collect()
+    }
+
+    pub fn filter_map_events<F>(&self, mut f: F) -> Self
+    where
+        F: FnMut(&Event) -> Option<Event>,
+    {
+        // Dropped events pass their time on, and from_absolute checks the summed deltas
+        Self::from_absolute(
+            self.to_absolute()
+                .into_iter()
+                .filter_map(|(tick, event)| Some((tick, f(&event)?)))
+                .collect(),
+        )
+    }
+
+    pub fn end_tick(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|event| event.delta_time.value as u64)
+            .sum()
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         let chunk_bytes = self.chunk_type.as_bytes().to_vec();
         let data_bytes = self
@@ -0,0 +1,490 @@
+use nom::{
+    bytes::complete::tag,
+    combinator::{eof, verify},
+    multi::many_till,
+    IResult,
+};
+
+use crate::{
+    event::{meta_event::MetaEvent, midi_event::MidiMessage, Event},
+    header::Division,
+    midi_file::MidiFile,
+    sysex::assembly::SysExItem,
+    track::TrackChunk,
+};
+
+use super::{
+    translate::{assemble_sysex7, Midi2Translator},
+    FlexData, SysEx7Status, Ump, UmpStream, UtilityMessage,
+};
+
+const CLIP_HEADER: &[u8] = b"SMF2CLIP";
+const START_OF_CLIP: u16 = 0x20;
+const END_OF_CLIP: u16 = 0x21;
+const MAX_DELTA_CLOCKSTAMP: u64 = 0xfffff;
+const SETUP_AND_PERFORMANCE: u8 = 0x00;
+const METADATA_TEXT: u8 = 0x01;
+const PERFORMANCE_TEXT: u8 = 0x02;
+const TIME_SIGNATURE: u8 = 0x01;
+const METRONOME: u8 = 0x02;
+const KEY_SIGNATURE: u8 = 0x05;
+// Letters in the order of fifths, as sharps are added to a key
+const FIFTHS: &[u8] = b"FCGDAEB";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClipProtocol {
+    Midi1,
+    Midi2,
+}
+
+fn stream_message(status: u16) -> Ump {
+    Ump::Stream(UmpStream {
+        form: 0,
+        status,
+        data: [0; 14],
+    })
+}
+
+fn delta_clockstamps(delta: u64) -> Vec<Ump> {
+    let mut packets = vec![];
+    let mut delta = delta;
+    while delta > MAX_DELTA_CLOCKSTAMP {
+        packets.push(Ump::Utility(UtilityMessage::DeltaClockstamp {
+            ticks: MAX_DELTA_CLOCKSTAMP as u32,
+        }));
+        delta -= MAX_DELTA_CLOCKSTAMP;
+    }
+    packets.push(Ump::Utility(UtilityMessage::DeltaClockstamp {
+        ticks: delta as u32,
+    }));
+    packets
+}
+
+fn flex_text(group: u8, status_bank: u8, status: u8, text: &str) -> Vec<Ump> {
+    let chunks = text.as_bytes().chunks(12).collect::<Vec<_>>();
+    let last = chunks.len().saturating_sub(1);
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut data = [0; 12];
+            data[..chunk.len()].copy_from_slice(chunk);
+            Ump::FlexData(FlexData {
+                group,
+                form: match (index, last) {
+                    (0, 0) => 0,
+                    (0, _) => 1,
+                    (index, last) if index == last => 3,
+                    _ => 2,
+                },
+                address: 1,
+                channel: 0,
+                status_bank,
+                status,
+                data,
+            })
+        })
+        .collect()
+}
+
+fn flex_setup(group: u8, status: u8, bytes: &[u8]) -> Ump {
+    let mut data = [0; 12];
+    data[..bytes.len()].copy_from_slice(bytes);
+    Ump::FlexData(FlexData {
+        group,
+        form: 0,
+        address: 1,
+        channel: 0,
+        status_bank: SETUP_AND_PERFORMANCE,
+        status,
+        data,
+    })
+}
+
+// The tonic is a letter from A = 1 to G = 7, its accidental follows from the key
+fn tonic(sharps: i8, minor: bool) -> u8 {
+    let index = (sharps as i32 + if minor { 4 } else { 1 }).rem_euclid(7);
+    FIFTHS[index as usize] - b'A' + 1
+}
+
+fn meta_to_flex(group: u8, event: &MetaEvent) -> Vec<Ump> {
+    match event {
+        MetaEvent::SetTempo { tempo } => vec![Ump::FlexData(FlexData::set_tempo(group, *tempo))],
+        MetaEvent::TimeSignature {
+            numerator,
+            denominator,
+            clocks_per_metronome_click,
+            thirty_seconds_per_quarter_note,
+        } => vec![
+            flex_setup(
+                group,
+                TIME_SIGNATURE,
+                &[*numerator, *denominator, *thirty_seconds_per_quarter_note],
+            ),
+            flex_setup(group, METRONOME, &[*clocks_per_metronome_click]),
+        ],
+        MetaEvent::KeySignature { key, scale } => {
+            let sharps = *key as i8;
+            if !(-7..=7).contains(&sharps) {
+                return Vec::new();
+            }
+            let byte = ((sharps as u8 & 0x0f) << 4) | tonic(sharps, *scale == 1);
+            vec![flex_setup(group, KEY_SIGNATURE, &[byte])]
+        }
+        MetaEvent::CopyrightNotice { text, .. } => flex_text(group, METADATA_TEXT, 0x04, text),
+        MetaEvent::SequenceOrTrackName { text, .. } => flex_text(group, METADATA_TEXT, 0x03, text),
+        MetaEvent::Lyric { text, .. } => flex_text(group, PERFORMANCE_TEXT, 0x01, text),
+        MetaEvent::TextEvent { text, .. } => flex_text(group, METADATA_TEXT, 0x00, text),
+        _ => Vec::new(),
+    }
+}
+
+fn flex_to_meta(message: &FlexData, text: &str) -> Option<MetaEvent> {
+    match (message.status_bank, message.status) {
+        (SETUP_AND_PERFORMANCE, 0x00) => Some(MetaEvent::SetTempo {
+            tempo: message.tempo()?,
+        }),
+        (SETUP_AND_PERFORMANCE, TIME_SIGNATURE) => Some(MetaEvent::TimeSignature {
+            numerator: message.data[0],
+            denominator: message.data[1],
+            clocks_per_metronome_click: 24,
+            thirty_seconds_per_quarter_note: match message.data[2] {
+                0 => 8,
+                value => value,
+            },
+        }),
+        (SETUP_AND_PERFORMANCE, KEY_SIGNATURE) => {
+            // Sharps and flats are a signed nibble, -8 marks a key outside the usual ones
+            let sharps = (message.data[0] as i8) >> 4;
+            (sharps >= -7).then(|| MetaEvent::KeySignature {
+                key: sharps as u8,
+                scale: (message.data[0] & 0x0f == tonic(sharps, true)) as u8,
+            })
+        }
+        (METADATA_TEXT, 0x00) => Some(MetaEvent::text(0x01, text)),
+        (METADATA_TEXT, 0x03) => Some(MetaEvent::text(0x03, text)),
+        (METADATA_TEXT, 0x04) => Some(MetaEvent::text(0x02, text)),
+        (PERFORMANCE_TEXT, 0x01) => Some(MetaEvent::text(0x05, text)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ClipFile {
+    pub ticks_per_quarter_note: u16,
+    pub header: Vec<Ump>,
+    pub events: Vec<(u64, Ump)>,
+    pub end_tick: u64,
+}
+
+impl ClipFile {
+    pub fn new(ticks_per_quarter_note: u16) -> Self {
+        Self {
+            ticks_per_quarter_note,
+            header: Vec::new(),
+            events: Vec::new(),
+            end_tick: 0,
+        }
+    }
+
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag(CLIP_HEADER)(input)?;
+        let (input, (packets, _)) = many_till(Ump::parse, eof)(input)?;
+        let mut clip = Self::new(0);
+        let mut tick = 0;
+        let mut started = false;
+        let mut ticks_per_quarter_note = None;
+        for packet in packets {
+            match packet {
+                Ump::Utility(UtilityMessage::DeltaClockstampTicksPerQuarterNote { ticks }) => {
+                    ticks_per_quarter_note = Some(ticks)
+                }
+                Ump::Utility(UtilityMessage::DeltaClockstamp { ticks }) => {
+                    if started {
+                        tick += ticks as u64;
+                    }
+                }
+                Ump::Utility(UtilityMessage::Noop) => {}
+                Ump::Stream(UmpStream {
+                    status: START_OF_CLIP,
+                    ..
+                }) => started = true,
+                Ump::Stream(UmpStream {
+                    status: END_OF_CLIP,
+                    ..
+                }) => {
+                    clip.end_tick = tick;
+                    break;
+                }
+                packet if started => clip.events.push((tick, packet)),
+                packet => clip.header.push(packet),
+            }
+        }
+        let (input, _) = verify(eof, |_: &[u8]| ticks_per_quarter_note.is_some())(input)?;
+        clip.ticks_per_quarter_note = ticks_per_quarter_note.unwrap();
+        clip.end_tick = clip
+            .end_tick
+            .max(clip.events.last().map(|(tick, _)| *tick).unwrap_or(0));
+        Ok((input, clip))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut packets = vec![
+            Ump::Utility(UtilityMessage::DeltaClockstampTicksPerQuarterNote {
+                ticks: self.ticks_per_quarter_note,
+            }),
+            Ump::Utility(UtilityMessage::DeltaClockstamp { ticks: 0 }),
+        ];
+        packets.extend(self.header.iter().cloned());
+        packets.push(Ump::Utility(UtilityMessage::DeltaClockstamp { ticks: 0 }));
+        packets.push(stream_message(START_OF_CLIP));
+        let mut tick = 0;
+        for (event_tick, packet) in self.events.iter() {
+            assert!(*event_tick >= tick, "Clip events out of order");
+            packets.extend(delta_clockstamps(event_tick - tick));
+            packets.push(packet.clone());
+            tick = *event_tick;
+        }
+        packets.extend(delta_clockstamps(self.end_tick.saturating_sub(tick)));
+        packets.push(stream_message(END_OF_CLIP));
+        [
+            CLIP_HEADER.to_vec(),
+            packets
+                .iter()
+                .flat_map(|packet| packet.to_bytes())
+                .collect(),
+        ]
+        .concat()
+    }
+
+    pub fn from_track(
+        track: &TrackChunk,
+        ticks_per_quarter_note: u16,
+        group: u8,
+        protocol: ClipProtocol,
+    ) -> Self {
+        assert!(group < 16);
+        let mut clip = Self::new(ticks_per_quarter_note);
+        let mut translator = Midi2Translator::new();
+        let mut sysex = track
+            .sysex_items()
+            .into_iter()
+            .filter_map(|item| match item {
+                SysExItem::Message(message) if message.complete => {
+                    Some((message.start_tick(), message.payload()))
+                }
+                _ => None,
+            })
+            .peekable();
+        for (tick, event) in track.to_absolute() {
+            let packets = match &event {
+                Event::MidiEvent(MidiMessage::ChannelMessage(message)) => match protocol {
+                    ClipProtocol::Midi1 => vec![Ump::Midi1ChannelVoice {
+                        group,
+                        message: *message,
+                    }],
+                    ClipProtocol::Midi2 => translator
+                        .to_midi2(message)
+                        .map(|message| Ump::Midi2ChannelVoice { group, message })
+                        .into_iter()
+                        .collect(),
+                },
+                Event::MidiEvent(message) => vec![Ump::from_midi_message(group, message)],
+                Event::SysExEvent(event) if event.get_status() == 0xf0 => {
+                    match sysex.next_if(|(sysex_tick, _)| *sysex_tick == tick) {
+                        Some((_, payload)) => Ump::sysex7_packets(group, &payload),
+                        None => Vec::new(),
+                    }
+                }
+                Event::MetaEvent(MetaEvent::EndOfTrack) => {
+                    clip.end_tick = tick;
+                    Vec::new()
+                }
+                Event::MetaEvent(event) => meta_to_flex(group, event),
+                Event::SysExEvent(_) => Vec::new(),
+            };
+            clip.events
+                .extend(packets.into_iter().map(|packet| (tick, packet)));
+        }
+        clip.end_tick = clip
+            .end_tick
+            .max(clip.events.last().map(|(tick, _)| *tick).unwrap_or(0));
+        clip
+    }
+
+    pub fn to_track(&self) -> TrackChunk {
+        let mut events = Vec::new();
+        let mut sysex = Vec::new();
+        let mut text = Vec::new();
+        for (tick, packet) in self.events.iter() {
+            match packet {
+                Ump::Midi1ChannelVoice { .. } | Ump::System { .. } => {
+                    events.push((*tick, Event::MidiEvent(packet.to_midi_message().unwrap())))
+                }
+                Ump::Midi2ChannelVoice { message, .. } => {
+                    events.extend(message.to_midi1().into_iter().map(|message| {
+                        (
+                            *tick,
+                            Event::MidiEvent(MidiMessage::ChannelMessage(message)),
+                        )
+                    }))
+                }
+                Ump::SysEx7 { status, .. } => {
+                    if matches!(status, SysEx7Status::Complete | SysEx7Status::Start) {
+                        sysex.clear();
+                    }
+                    sysex.push(packet.clone());
+                    if matches!(status, SysEx7Status::Complete | SysEx7Status::End) {
+                        if let Some(event) = assemble_sysex7(&sysex) {
+                            events.push((*tick, Event::SysExEvent(event)));
+                        }
+                        sysex.clear();
+                    }
+                }
+                // SMF keeps the metronome click in the time signature at the same tick
+                Ump::FlexData(message)
+                    if (message.status_bank, message.status)
+                        == (SETUP_AND_PERFORMANCE, METRONOME) =>
+                {
+                    if let Some((
+                        last_tick,
+                        Event::MetaEvent(MetaEvent::TimeSignature {
+                            clocks_per_metronome_click,
+                            ..
+                        }),
+                    )) = events.last_mut()
+                    {
+                        if *last_tick == *tick && message.data[0] > 0 {
+                            *clocks_per_metronome_click = message.data[0];
+                        }
+                    }
+                }
+                Ump::FlexData(message) => {
+                    if matches!(message.form, 0 | 1) {
+                        text.clear();
+                    }
+                    text.extend(message.data.iter().copied().filter(|byte| *byte != 0));
+                    if matches!(message.form, 0 | 3) {
+                        if let Some(event) = flex_to_meta(message, &String::from_utf8_lossy(&text))
+                        {
+                            events.push((*tick, Event::MetaEvent(event)));
+                        }
+                        text.clear();
+                    }
+                }
+                _ => {}
+            }
+        }
+        events.push((self.end_tick, Event::MetaEvent(MetaEvent::EndOfTrack)));
+        TrackChunk::from_absolute(events)
+    }
+
+    pub fn to_midi_file(&self) -> MidiFile {
+        MidiFile::new(
+            0,
+            Division::TicksPerQuarterNote {
+                ticks: self.ticks_per_quarter_note,
+            },
+            vec![self.to_track()],
+        )
+    }
+}
+
+impl MidiFile {
+    pub fn track_to_clip(
+        &self,
+        index: usize,
+        group: u8,
+        protocol: ClipProtocol,
+    ) -> Option<ClipFile> {
+        let track = self.tracks.get(index)?;
+        match self.header.data.division {
+            Division::TicksPerQuarterNote { ticks } => {
+                Some(ClipFile::from_track(track, ticks, group, protocol))
+            }
+            Division::FramesPerSecond { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        event::{
+            meta_event::MetaEvent, midi_event::ChannelVoiceMessage, sysex_event::SysExEvent, Event,
+        },
+        header::Division,
+        midi_file::MidiFile,
+        track::TrackChunk,
+    };
+
+    use super::{ClipFile, ClipProtocol, Ump};
+
+    #[test]
+    fn test_round_trip() {
+        let track = TrackChunk::from_absolute(vec![
+            (
+                0,
+                Event::MetaEvent(MetaEvent::text(0x03, "A rather long track name")),
+            ),
+            (0, Event::MetaEvent(MetaEvent::SetTempo { tempo: 600000 })),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::TimeSignature {
+                    numerator: 6,
+                    denominator: 3,
+                    clocks_per_metronome_click: 36,
+                    thirty_seconds_per_quarter_note: 8,
+                }),
+            ),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::KeySignature {
+                    key: -3i8 as u8,
+                    scale: 1,
+                }),
+            ),
+            (
+                0,
+                Event::MetaEvent(MetaEvent::KeySignature { key: 2, scale: 0 }),
+            ),
+            (
+                0,
+                Event::SysExEvent(SysExEvent::from_payload(&[0x7e, 0x7f, 0x09, 0x01])),
+            ),
+            (
+                0,
+                Event::from(ChannelVoiceMessage::NoteOn {
+                    channel: 0,
+                    note: 60,
+                    velocity: 100,
+                }),
+            ),
+            (
+                0x200000,
+                Event::from(ChannelVoiceMessage::NoteOff {
+                    channel: 0,
+                    note: 60,
+                    velocity: 64,
+                }),
+            ),
+            (0x200010, Event::MetaEvent(MetaEvent::EndOfTrack)),
+        ]);
+        let midi_file = MidiFile::new(0, Division::TicksPerQuarterNote { ticks: 480 }, vec![track]);
+        for protocol in [ClipProtocol::Midi1, ClipProtocol::Midi2] {
+            let clip = midi_file.track_to_clip(0, 0, protocol).unwrap();
+            // C minor is three flats with C as its tonic
+            assert!(clip.events.iter().any(|(_, packet)| matches!(
+                packet,
+                Ump::FlexData(message) if message.status == 0x05 && message.data[0] == 0xd3
+            )));
+            let bytes = clip.to_bytes();
+            assert_eq!(&bytes[..8], b"SMF2CLIP");
+            let (_, parsed) = ClipFile::parse(&bytes).unwrap();
+            assert_eq!(parsed, clip);
+            assert_eq!(parsed.to_midi_file(), midi_file);
+        }
+        assert!(ClipFile::parse(b"SMF2CLIP").is_err());
+        assert!(midi_file.track_to_clip(1, 0, ClipProtocol::Midi1).is_none());
+    }
+}
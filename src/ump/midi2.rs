@@ -0,0 +1,294 @@
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Midi2ChannelVoiceMessage {
+    RegisteredPerNoteController {
+        channel: u8,
+        note: u8,
+        index: u8,
+        value: u32,
+    },
+    AssignablePerNoteController {
+        channel: u8,
+        note: u8,
+        index: u8,
+        value: u32,
+    },
+    RegisteredController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: u32,
+    },
+    AssignableController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: u32,
+    },
+    RelativeRegisteredController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: i32,
+    },
+    RelativeAssignableController {
+        channel: u8,
+        bank: u8,
+        index: u8,
+        value: i32,
+    },
+    PerNotePitchBend {
+        channel: u8,
+        note: u8,
+        value: u32,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    PolyphonicKeyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u32,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u32,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+        bank: Option<(u8, u8)>,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u32,
+    },
+    PitchBendChange {
+        channel: u8,
+        value: u32,
+    },
+    PerNoteManagement {
+        channel: u8,
+        note: u8,
+        detach: bool,
+        reset: bool,
+    },
+}
+
+impl Midi2ChannelVoiceMessage {
+    pub fn from_words(first: u32, second: u32) -> Option<Self> {
+        let status = ((first >> 20) & 0x0f) as u8;
+        let channel = ((first >> 16) & 0x0f) as u8;
+        let byte3 = ((first >> 8) & 0x7f) as u8;
+        let byte4 = (first & 0xff) as u8;
+        let message = match status {
+            0x0 => Self::RegisteredPerNoteController {
+                channel,
+                note: byte3,
+                index: byte4,
+                value: second,
+            },
+            0x1 => Self::AssignablePerNoteController {
+                channel,
+                note: byte3,
+                index: byte4,
+                value: second,
+            },
+            0x2 => Self::RegisteredController {
+                channel,
+                bank: byte3,
+                index: byte4 & 0x7f,
+                value: second,
+            },
+            0x3 => Self::AssignableController {
+                channel,
+                bank: byte3,
+                index: byte4 & 0x7f,
+                value: second,
+            },
+            0x4 => Self::RelativeRegisteredController {
+                channel,
+                bank: byte3,
+                index: byte4 & 0x7f,
+                value: second as i32,
+            },
+            0x5 => Self::RelativeAssignableController {
+                channel,
+                bank: byte3,
+                index: byte4 & 0x7f,
+                value: second as i32,
+            },
+            0x6 => Self::PerNotePitchBend {
+                channel,
+                note: byte3,
+                value: second,
+            },
+            0x8 => Self::NoteOff {
+                channel,
+                note: byte3,
+                velocity: (second >> 16) as u16,
+                attribute_type: byte4,
+                attribute: second as u16,
+            },
+            0x9 => Self::NoteOn {
+                channel,
+                note: byte3,
+                velocity: (second >> 16) as u16,
+                attribute_type: byte4,
+                attribute: second as u16,
+            },
+            0xa => Self::PolyphonicKeyPressure {
+                channel,
+                note: byte3,
+                pressure: second,
+            },
+            0xb => Self::ControlChange {
+                channel,
+                controller: byte3,
+                value: second,
+            },
+            0xc => Self::ProgramChange {
+                channel,
+                program: ((second >> 24) & 0x7f) as u8,
+                bank: match byte4 & 0x01 {
+                    0 => None,
+                    _ => Some((((second >> 8) & 0x7f) as u8, (second & 0x7f) as u8)),
+                },
+            },
+            0xd => Self::ChannelPressure {
+                channel,
+                pressure: second,
+            },
+            0xe => Self::PitchBendChange {
+                channel,
+                value: second,
+            },
+            0xf => Self::PerNoteManagement {
+                channel,
+                note: byte3,
+                detach: byte4 & 0x02 != 0,
+                reset: byte4 & 0x01 != 0,
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    // The message type and group nibbles of the first word are left to the packet
+    pub fn to_words(&self) -> (u32, u32) {
+        let (byte3, byte4, second) = match *self {
+            Self::RegisteredPerNoteController {
+                note, index, value, ..
+            }
+            | Self::AssignablePerNoteController {
+                note, index, value, ..
+            } => (note, index, value),
+            Self::RegisteredController {
+                bank, index, value, ..
+            }
+            | Self::AssignableController {
+                bank, index, value, ..
+            } => (bank, index, value),
+            Self::RelativeRegisteredController {
+                bank, index, value, ..
+            }
+            | Self::RelativeAssignableController {
+                bank, index, value, ..
+            } => (bank, index, value as u32),
+            Self::PerNotePitchBend { note, value, .. } => (note, 0, value),
+            Self::NoteOff {
+                note,
+                velocity,
+                attribute_type,
+                attribute,
+                ..
+            }
+            | Self::NoteOn {
+                note,
+                velocity,
+                attribute_type,
+                attribute,
+                ..
+            } => (
+                note,
+                attribute_type,
+                ((velocity as u32) << 16) | attribute as u32,
+            ),
+            Self::PolyphonicKeyPressure { note, pressure, .. } => (note, 0, pressure),
+            Self::ControlChange {
+                controller, value, ..
+            } => (controller, 0, value),
+            Self::ProgramChange { program, bank, .. } => match bank {
+                Some((msb, lsb)) => (
+                    0,
+                    0x01,
+                    ((program as u32) << 24) | ((msb as u32) << 8) | lsb as u32,
+                ),
+                None => (0, 0, (program as u32) << 24),
+            },
+            Self::ChannelPressure { pressure, .. } => (0, 0, pressure),
+            Self::PitchBendChange { value, .. } => (0, 0, value),
+            Self::PerNoteManagement {
+                note,
+                detach,
+                reset,
+                ..
+            } => (note, ((detach as u8) << 1) | reset as u8, 0),
+        };
+        let first =
+            ((self.get_status() as u32) << 16) | ((byte3 as u32 & 0x7f) << 8) | byte4 as u32;
+        (first, second)
+    }
+
+    pub fn get_status(&self) -> u8 {
+        let opcode = match self {
+            Self::RegisteredPerNoteController { .. } => 0x00,
+            Self::AssignablePerNoteController { .. } => 0x10,
+            Self::RegisteredController { .. } => 0x20,
+            Self::AssignableController { .. } => 0x30,
+            Self::RelativeRegisteredController { .. } => 0x40,
+            Self::RelativeAssignableController { .. } => 0x50,
+            Self::PerNotePitchBend { .. } => 0x60,
+            Self::NoteOff { .. } => 0x80,
+            Self::NoteOn { .. } => 0x90,
+            Self::PolyphonicKeyPressure { .. } => 0xa0,
+            Self::ControlChange { .. } => 0xb0,
+            Self::ProgramChange { .. } => 0xc0,
+            Self::ChannelPressure { .. } => 0xd0,
+            Self::PitchBendChange { .. } => 0xe0,
+            Self::PerNoteManagement { .. } => 0xf0,
+        };
+        opcode | self.get_channel()
+    }
+
+    pub fn get_channel(&self) -> u8 {
+        match *self {
+            Self::RegisteredPerNoteController { channel, .. }
+            | Self::AssignablePerNoteController { channel, .. }
+            | Self::RegisteredController { channel, .. }
+            | Self::AssignableController { channel, .. }
+            | Self::RelativeRegisteredController { channel, .. }
+            | Self::RelativeAssignableController { channel, .. }
+            | Self::PerNotePitchBend { channel, .. }
+            | Self::NoteOff { channel, .. }
+            | Self::NoteOn { channel, .. }
+            | Self::PolyphonicKeyPressure { channel, .. }
+            | Self::ControlChange { channel, .. }
+            | Self::ProgramChange { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::PitchBendChange { channel, .. }
+            | Self::PerNoteManagement { channel, .. } => channel,
+        }
+    }
+}
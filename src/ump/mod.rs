@@ -0,0 +1,410 @@
+use nom::{combinator::peek, multi::count, number::complete::be_u32, IResult};
+
+use crate::event::midi_event::{ChannelMessage, MidiMessage, SystemMessage};
+
+use self::midi2::Midi2ChannelVoiceMessage;
+
+pub mod clip;
+pub mod midi2;
+pub mod translate;
+
+pub fn packet_words(message_type: u8) -> usize {
+    match message_type {
+        0x0..=0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8..=0xa => 2,
+        0xb | 0xc => 3,
+        _ => 4,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UtilityMessage {
+    Noop,
+    JrClock { time: u16 },
+    JrTimestamp { timestamp: u16 },
+    DeltaClockstampTicksPerQuarterNote { ticks: u16 },
+    DeltaClockstamp { ticks: u32 },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SysEx7Status {
+    Complete,
+    Start,
+    Continue,
+    End,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FlexData {
+    pub group: u8,
+    pub form: u8,
+    pub address: u8,
+    pub channel: u8,
+    pub status_bank: u8,
+    pub status: u8,
+    pub data: [u8; 12],
+}
+
+impl FlexData {
+    pub fn set_tempo(group: u8, tempo: u32) -> Self {
+        let mut data = [0; 12];
+        data[..4].copy_from_slice(&(tempo * 100).to_be_bytes());
+        Self {
+            group,
+            form: 0,
+            address: 1,
+            channel: 0,
+            status_bank: 0,
+            status: 0,
+            data,
+        }
+    }
+
+    pub fn tempo(&self) -> Option<u32> {
+        match (self.status_bank, self.status) {
+            (0, 0) => {
+                let value =
+                    u32::from_be_bytes([self.data[0], self.data[1], self.data[2], self.data[3]]);
+                Some(value / 100)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn text(&self) -> Option<String> {
+        match self.status_bank {
+            0x01 | 0x02 => {
+                let end = self.data.iter().position(|byte| *byte == 0).unwrap_or(12);
+                Some(String::from_utf8_lossy(&self.data[..end]).into_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UmpStream {
+    pub form: u8,
+    pub status: u16,
+    pub data: [u8; 14],
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Ump {
+    Utility(UtilityMessage),
+    System {
+        group: u8,
+        message: SystemMessage,
+    },
+    Midi1ChannelVoice {
+        group: u8,
+        message: ChannelMessage,
+    },
+    SysEx7 {
+        group: u8,
+        status: SysEx7Status,
+        data: Vec<u8>,
+    },
+    Midi2ChannelVoice {
+        group: u8,
+        message: Midi2ChannelVoiceMessage,
+    },
+    FlexData(FlexData),
+    Stream(UmpStream),
+    Unknown(Vec<u32>),
+}
+
+fn word_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+fn bytes_word(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |word, byte| (word << 8) | *byte as u32)
+}
+
+impl Ump {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, first) = peek(be_u32)(input)?;
+        let (input, words) = count(be_u32, packet_words((first >> 28) as u8))(input)?;
+        Ok((input, Self::from_words(&words)))
+    }
+
+    pub fn parse_words(words: &[u32]) -> Vec<Self> {
+        let mut packets = Vec::new();
+        let mut words = words;
+        while let Some(first) = words.first() {
+            // A truncated packet at the end is kept as it is
+            let size = packet_words((first >> 28) as u8).min(words.len());
+            packets.push(Self::from_words(&words[..size]));
+            words = &words[size..];
+        }
+        packets
+    }
+
+    pub fn from_words(words: &[u32]) -> Self {
+        let message_type = match words.first() {
+            Some(first) if words.len() == packet_words((first >> 28) as u8) => (first >> 28) as u8,
+            _ => return Self::Unknown(words.to_vec()),
+        };
+        let group = ((words[0] >> 24) & 0x0f) as u8;
+        let status = ((words[0] >> 20) & 0x0f) as u8;
+        let bytes = word_bytes(words);
+        let message = match message_type {
+            0x0 => {
+                let data = words[0] & 0xfffff;
+                match status {
+                    0x0 => Some(Self::Utility(UtilityMessage::Noop)),
+                    0x1 => Some(Self::Utility(UtilityMessage::JrClock { time: data as u16 })),
+                    0x2 => Some(Self::Utility(UtilityMessage::JrTimestamp {
+                        timestamp: data as u16,
+                    })),
+                    0x3 => Some(Self::Utility(
+                        UtilityMessage::DeltaClockstampTicksPerQuarterNote { ticks: data as u16 },
+                    )),
+                    0x4 => Some(Self::Utility(UtilityMessage::DeltaClockstamp {
+                        ticks: data,
+                    })),
+                    _ => None,
+                }
+            }
+            0x1 => match translate::midi1_message(bytes[1], bytes[2], bytes[3]) {
+                Some(MidiMessage::SystemMessage(message)) => Some(Self::System { group, message }),
+                _ => None,
+            },
+            0x2 => match translate::midi1_message(bytes[1], bytes[2], bytes[3]) {
+                Some(MidiMessage::ChannelMessage(message)) => {
+                    Some(Self::Midi1ChannelVoice { group, message })
+                }
+                _ => None,
+            },
+            0x3 => {
+                let length = ((words[0] >> 16) & 0x0f) as usize;
+                let status = match status {
+                    0x0 => Some(SysEx7Status::Complete),
+                    0x1 => Some(SysEx7Status::Start),
+                    0x2 => Some(SysEx7Status::Continue),
+                    0x3 => Some(SysEx7Status::End),
+                    _ => None,
+                };
+                match status {
+                    Some(status) if length <= 6 => Some(Self::SysEx7 {
+                        group,
+                        status,
+                        data: bytes[2..2 + length].to_vec(),
+                    }),
+                    _ => None,
+                }
+            }
+            0x4 => Midi2ChannelVoiceMessage::from_words(words[0], words[1])
+                .map(|message| Self::Midi2ChannelVoice { group, message }),
+            0xd => {
+                let mut data = [0; 12];
+                data.copy_from_slice(&bytes[4..]);
+                Some(Self::FlexData(FlexData {
+                    group,
+                    form: (bytes[1] >> 6) & 0x03,
+                    address: (bytes[1] >> 4) & 0x03,
+                    channel: bytes[1] & 0x0f,
+                    status_bank: bytes[2],
+                    status: bytes[3],
+                    data,
+                }))
+            }
+            0xf => {
+                let mut data = [0; 14];
+                data.copy_from_slice(&bytes[2..]);
+                Some(Self::Stream(UmpStream {
+                    form: ((words[0] >> 26) & 0x03) as u8,
+                    status: ((words[0] >> 16) & 0x3ff) as u16,
+                    data,
+                }))
+            }
+            _ => None,
+        };
+        message.unwrap_or_else(|| Self::Unknown(words.to_vec()))
+    }
+
+    pub fn to_words(&self) -> Vec<u32> {
+        match self {
+            Self::Utility(message) => {
+                let (status, data) = match message {
+                    UtilityMessage::Noop => (0x0, 0),
+                    UtilityMessage::JrClock { time } => (0x1, *time as u32),
+                    UtilityMessage::JrTimestamp { timestamp } => (0x2, *timestamp as u32),
+                    UtilityMessage::DeltaClockstampTicksPerQuarterNote { ticks } => {
+                        (0x3, *ticks as u32)
+                    }
+                    UtilityMessage::DeltaClockstamp { ticks } => (0x4, ticks & 0xfffff),
+                };
+                vec![(status << 20) | data]
+            }
+            Self::System { group, message } => {
+                let bytes = message.to_bytes();
+                vec![bytes_word(&[
+                    0x10 | group,
+                    bytes[0],
+                    *bytes.get(1).unwrap_or(&0),
+                    *bytes.get(2).unwrap_or(&0),
+                ])]
+            }
+            Self::Midi1ChannelVoice { group, message } => {
+                let bytes = message.to_bytes();
+                vec![bytes_word(&[
+                    0x20 | group,
+                    bytes[0],
+                    bytes[1],
+                    *bytes.get(2).unwrap_or(&0),
+                ])]
+            }
+            Self::SysEx7 {
+                group,
+                status,
+                data,
+            } => {
+                assert!(data.len() <= 6, "Too many SysEx7 bytes: {}", data.len());
+                let status = match status {
+                    SysEx7Status::Complete => 0x0,
+                    SysEx7Status::Start => 0x1,
+                    SysEx7Status::Continue => 0x2,
+                    SysEx7Status::End => 0x3,
+                };
+                let mut bytes = [0; 8];
+                bytes[0] = 0x30 | group;
+                bytes[1] = (status << 4) | data.len() as u8;
+                bytes[2..2 + data.len()].copy_from_slice(data);
+                vec![bytes_word(&bytes[..4]), bytes_word(&bytes[4..])]
+            }
+            Self::Midi2ChannelVoice { group, message } => {
+                let (first, second) = message.to_words();
+                vec![0x40000000 | ((*group as u32) << 24) | first, second]
+            }
+            Self::FlexData(message) => {
+                let header = [
+                    0xd0 | message.group,
+                    (message.form << 6) | (message.address << 4) | message.channel,
+                    message.status_bank,
+                    message.status,
+                ];
+                [&header[..], &message.data]
+                    .concat()
+                    .chunks(4)
+                    .map(bytes_word)
+                    .collect()
+            }
+            Self::Stream(message) => {
+                let header = 0xf0000000
+                    | ((message.form as u32 & 0x03) << 26)
+                    | ((message.status as u32 & 0x3ff) << 16);
+                let words = message.data.chunks(4).map(bytes_word).collect::<Vec<_>>();
+                [vec![header | (words[0] & 0xffff)], words[1..].to_vec()].concat()
+            }
+            Self::Unknown(words) => words.clone(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        word_bytes(&self.to_words())
+    }
+
+    pub fn get_group(&self) -> Option<u8> {
+        match self {
+            Self::System { group, .. }
+            | Self::Midi1ChannelVoice { group, .. }
+            | Self::SysEx7 { group, .. }
+            | Self::Midi2ChannelVoice { group, .. } => Some(*group),
+            Self::FlexData(message) => Some(message.group),
+            Self::Utility(_) | Self::Stream(_) | Self::Unknown(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::midi_event::{
+        ChannelMessage, ChannelVoiceMessage, SystemMessage, SystemRealTimeMessage,
+    };
+
+    use super::{midi2::Midi2ChannelVoiceMessage, FlexData, SysEx7Status, Ump, UtilityMessage};
+
+    #[test]
+    fn test_round_trip() {
+        let words = [
+            0x00200010, 0x11f80000, 0x20903c64, 0x31037e7f, 0x09000000, 0x40903c00, 0xffff0000,
+            0x4ab24000, 0x80000000, 0xd0100000, 0x02faf080, 0x00000000, 0x00000000, 0xf0010000,
+            0x00000000, 0x00000000, 0x00000000, 0x60000000,
+        ];
+        let packets = Ump::parse_words(&words);
+        assert_eq!(
+            packets[..6],
+            [
+                Ump::Utility(UtilityMessage::JrTimestamp { timestamp: 0x10 }),
+                Ump::System {
+                    group: 1,
+                    message: SystemMessage::SystemRealTimeMessage(
+                        SystemRealTimeMessage::TimingClock
+                    ),
+                },
+                Ump::Midi1ChannelVoice {
+                    group: 0,
+                    message: ChannelMessage::ChannelVoiceMessage(ChannelVoiceMessage::NoteOn {
+                        channel: 0,
+                        note: 60,
+                        velocity: 100,
+                    }),
+                },
+                Ump::SysEx7 {
+                    group: 1,
+                    status: SysEx7Status::Complete,
+                    data: vec![0x7e, 0x7f, 0x09],
+                },
+                Ump::Midi2ChannelVoice {
+                    group: 0,
+                    message: Midi2ChannelVoiceMessage::NoteOn {
+                        channel: 0,
+                        note: 60,
+                        velocity: 0xffff,
+                        attribute_type: 0,
+                        attribute: 0,
+                    },
+                },
+                Ump::Midi2ChannelVoice {
+                    group: 0xa,
+                    message: Midi2ChannelVoiceMessage::ControlChange {
+                        channel: 2,
+                        controller: 0x40,
+                        value: 0x80000000,
+                    },
+                },
+            ]
+        );
+        assert_eq!(packets[6], Ump::FlexData(FlexData::set_tempo(0, 500000)));
+        assert_eq!(packets[6].get_group(), Some(0));
+        assert!(matches!(packets[7], Ump::Stream(stream) if stream.status == 1));
+        assert_eq!(packets[8], Ump::Unknown(vec![0x60000000]));
+        let mut bytes = Vec::new();
+        packets
+            .iter()
+            .for_each(|packet| bytes.extend(packet.to_bytes()));
+        assert_eq!(
+            bytes,
+            words
+                .iter()
+                .flat_map(|word| word.to_be_bytes())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Ump::parse(&bytes).unwrap().1, packets[0]);
+
+        // Truncated packets are kept as unknown words
+        assert_eq!(
+            Ump::parse_words(&[0x20903c64, 0x40903c00]),
+            [packets[2].clone(), Ump::Unknown(vec![0x40903c00]),]
+        );
+        assert_eq!(Ump::from_words(&[]), Ump::Unknown(Vec::new()));
+        assert_eq!(
+            Ump::from_words(&[0x20903c64, 0]),
+            Ump::Unknown(vec![0x20903c64, 0])
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use crate::event::{
+    midi_event::{ChannelMessage, ChannelVoiceMessage, MidiMessage},
+    sysex_event::SysExEvent,
+};
+
+use super::{midi2::Midi2ChannelVoiceMessage, SysEx7Status, Ump};
+
+const BANK_SELECT_MSB: u8 = 0x00;
+const DATA_ENTRY_MSB: u8 = 0x06;
+const BANK_SELECT_LSB: u8 = 0x20;
+const DATA_ENTRY_LSB: u8 = 0x26;
+const NRPN_LSB: u8 = 0x62;
+const NRPN_MSB: u8 = 0x63;
+const RPN_LSB: u8 = 0x64;
+const RPN_MSB: u8 = 0x65;
+
+// Min-center-max scaling from the MIDI 2.0 protocol specification
+pub fn scale_up(value: u32, source_bits: u32, destination_bits: u32) -> u32 {
+    assert!(source_bits > 1 && source_bits <= destination_bits && destination_bits <= 32);
+    let value = value as u64 & ((1 << source_bits) - 1);
+    let scale_bits = destination_bits - source_bits;
+    let center = 1 << (source_bits - 1);
+    if value <= center {
+        return (value << scale_bits) as u32;
+    }
+    let repeat_bits = source_bits - 1;
+    let mut repeat = value & ((1 << repeat_bits) - 1);
+    if scale_bits > repeat_bits {
+        repeat <<= scale_bits - repeat_bits;
+    } else {
+        repeat >>= repeat_bits - scale_bits;
+    }
+    let mut scaled = value << scale_bits;
+    while repeat != 0 {
+        scaled |= repeat;
+        repeat >>= repeat_bits;
+    }
+    scaled as u32
+}
+
+pub fn scale_down(value: u32, source_bits: u32, destination_bits: u32) -> u32 {
+    assert!(destination_bits <= source_bits && source_bits <= 32);
+    ((value as u64 & ((1 << source_bits) - 1)) >> (source_bits - destination_bits)) as u32
+}
+
+pub(crate) fn midi1_message(status: u8, data1: u8, data2: u8) -> Option<MidiMessage> {
+    let valid = match status {
+        0x80..=0xef => data1 >> 7 == 0 && data2 >> 7 == 0,
+        0xf2 | 0xf3 => data1 >> 7 == 0 && data2 >> 7 == 0,
+        0xf6 | 0xf8 | 0xfa | 0xfb | 0xfc | 0xfe | 0xff => true,
+        _ => false,
+    };
+    // Local control only accepts on and off
+    let data2 = match (status >> 4, data1) {
+        (0xb, 0x7a) if data2 >= 0x40 => 0x7f,
+        (0xb, 0x7a) => 0x00,
+        _ => data2,
+    };
+    if !valid {
+        return None;
+    }
+    MidiMessage::parse(&[data1, data2], status)
+        .ok()
+        .map(|(_, message)| message)
+}
+
+impl Ump {
+    pub fn from_midi_message(group: u8, message: &MidiMessage) -> Self {
+        assert!(group < 16);
+        match message {
+            MidiMessage::ChannelMessage(message) => Self::Midi1ChannelVoice {
+                group,
+                message: *message,
+            },
+            MidiMessage::SystemMessage(message) => Self::System {
+                group,
+                message: *message,
+            },
+        }
+    }
+
+    pub fn to_midi_message(&self) -> Option<MidiMessage> {
+        match self {
+            Self::Midi1ChannelVoice { message, .. } => Some(MidiMessage::ChannelMessage(*message)),
+            Self::System { message, .. } => Some(MidiMessage::SystemMessage(*message)),
+            _ => None,
+        }
+    }
+
+    pub fn sysex7_packets(group: u8, payload: &[u8]) -> Vec<Self> {
+        assert!(group < 16);
+        let chunks = payload.chunks(6).collect::<Vec<_>>();
+        if chunks.is_empty() {
+            return vec![Self::SysEx7 {
+                group,
+                status: SysEx7Status::Complete,
+                data: Vec::new(),
+            }];
+        }
+        let last = chunks.len() - 1;
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| Self::SysEx7 {
+                group,
+                status: match (index, last) {
+                    (0, 0) => SysEx7Status::Complete,
+                    (0, _) => SysEx7Status::Start,
+                    (index, last) if index == last => SysEx7Status::End,
+                    _ => SysEx7Status::Continue,
+                },
+                data: chunk.to_vec(),
+            })
+            .collect()
+    }
+
+    pub fn from_sysex_event(group: u8, event: &SysExEvent) -> Option<Vec<Self>> {
+        Some(Self::sysex7_packets(group, event.get_payload()?))
+    }
+}
+
+pub fn assemble_sysex7(packets: &[Ump]) -> Option<SysExEvent> {
+    let mut payload = Vec::new();
+    for (index, packet) in packets.iter().enumerate() {
+        let (status, data) = match packet {
+            Ump::SysEx7 { status, data, .. } => (status, data),
+            _ => return None,
+        };
+        let expected = match (index, packets.len() - 1) {
+            (0, 0) => SysEx7Status::Complete,
+            (0, _) => SysEx7Status::Start,
+            (index, last) if index == last => SysEx7Status::End,
+            _ => SysEx7Status::Continue,
+        };
+        if *status != expected {
+            return None;
+        }
+        payload.extend(data);
+    }
+    Some(SysExEvent::from_payload(&payload))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+struct ParameterState {
+    bank_msb: Option<u8>,
+    bank_lsb: Option<u8>,
+    registered: bool,
+    parameter_msb: Option<u8>,
+    parameter_lsb: Option<u8>,
+    data_msb: u8,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Midi2Translator {
+    channels: [ParameterState; 16],
+}
+
+impl Midi2Translator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn control_change(
+        &mut self,
+        channel: u8,
+        controller: u8,
+        value: u8,
+    ) -> Option<Midi2ChannelVoiceMessage> {
+        let state = &mut self.channels[channel as usize];
+        match controller {
+            BANK_SELECT_MSB => state.bank_msb = Some(value),
+            BANK_SELECT_LSB => state.bank_lsb = Some(value),
+            RPN_MSB | RPN_LSB | NRPN_MSB | NRPN_LSB => {
+                let registered = matches!(controller, RPN_MSB | RPN_LSB);
+                if registered != state.registered {
+                    state.registered = registered;
+                    state.parameter_msb = None;
+                    state.parameter_lsb = None;
+                }
+                match controller {
+                    RPN_MSB | NRPN_MSB => state.parameter_msb = Some(value),
+                    _ => state.parameter_lsb = Some(value),
+                }
+            }
+            DATA_ENTRY_MSB | DATA_ENTRY_LSB => {
+                let (bank, index) = match (state.parameter_msb, state.parameter_lsb) {
+                    (Some(0x7f), Some(0x7f)) | (None, _) | (_, None) => {
+                        return Some(Midi2ChannelVoiceMessage::ControlChange {
+                            channel,
+                            controller,
+                            value: scale_up(value as u32, 7, 32),
+                        })
+                    }
+                    (Some(msb), Some(lsb)) => (msb, lsb),
+                };
+                let data_lsb = match controller {
+                    DATA_ENTRY_MSB => {
+                        state.data_msb = value;
+                        0
+                    }
+                    _ => value,
+                };
+                let value = scale_up(((state.data_msb as u32) << 7) | data_lsb as u32, 14, 32);
+                return Some(match state.registered {
+                    true => Midi2ChannelVoiceMessage::RegisteredController {
+                        channel,
+                        bank,
+                        index,
+                        value,
+                    },
+                    false => Midi2ChannelVoiceMessage::AssignableController {
+                        channel,
+                        bank,
+                        index,
+                        value,
+                    },
+                });
+            }
+            _ => {
+                return Some(Midi2ChannelVoiceMessage::ControlChange {
+                    channel,
+                    controller,
+                    value: scale_up(value as u32, 7, 32),
+                })
+            }
+        }
+        None
+    }
+
+    pub fn to_midi2(&mut self, message: &ChannelMessage) -> Option<Midi2ChannelVoiceMessage> {
+        let message = match message {
+            ChannelMessage::ChannelVoiceMessage(message) => *message,
+            ChannelMessage::ChannelModeMessage(message) => {
+                let bytes = message.get_message().to_bytes();
+                return Some(Midi2ChannelVoiceMessage::ControlChange {
+                    channel: message.get_channel(),
+                    controller: bytes[0],
+                    value: scale_up(bytes[1] as u32, 7, 32),
+                });
+            }
+        };
+        let translated = match message {
+            ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity: 0,
+            } => Midi2ChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity: 0,
+                attribute_type: 0,
+                attribute: 0,
+            },
+            ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => Midi2ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                velocity: scale_up(velocity as u32, 7, 16) as u16,
+                attribute_type: 0,
+                attribute: 0,
+            },
+            ChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => Midi2ChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity: scale_up(velocity as u32, 7, 16) as u16,
+                attribute_type: 0,
+                attribute: 0,
+            },
+            ChannelVoiceMessage::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            } => Midi2ChannelVoiceMessage::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure: scale_up(pressure as u32, 7, 32),
+            },
+            ChannelVoiceMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => return self.control_change(channel, controller, value),
+            ChannelVoiceMessage::ProgramChange { channel, program } => {
+                let state = &self.channels[channel as usize];
+                Midi2ChannelVoiceMessage::ProgramChange {
+                    channel,
+                    program,
+                    bank: match (state.bank_msb, state.bank_lsb) {
+                        (None, None) => None,
+                        (msb, lsb) => Some((msb.unwrap_or(0), lsb.unwrap_or(0))),
+                    },
+                }
+            }
+            ChannelVoiceMessage::ChannelPressure { channel, pressure } => {
+                Midi2ChannelVoiceMessage::ChannelPressure {
+                    channel,
+                    pressure: scale_up(pressure as u32, 7, 32),
+                }
+            }
+            ChannelVoiceMessage::PitchBendChange { channel, value } => {
+                Midi2ChannelVoiceMessage::PitchBendChange {
+                    channel,
+                    value: scale_up(value as u32, 14, 32),
+                }
+            }
+        };
+        Some(translated)
+    }
+}
+
+impl Midi2ChannelVoiceMessage {
+    pub fn to_midi1(&self) -> Vec<ChannelMessage> {
+        let control = |channel, controller, value| ChannelVoiceMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        };
+        let messages = match *self {
+            Self::NoteOn {
+                channel,
+                note,
+                velocity,
+                ..
+            } => vec![ChannelVoiceMessage::NoteOn {
+                channel,
+                note,
+                // A scaled velocity of zero would turn this into a note off
+                velocity: (scale_down(velocity as u32, 16, 7) as u8).max(1),
+            }],
+            Self::NoteOff {
+                channel,
+                note,
+                velocity,
+                ..
+            } => vec![ChannelVoiceMessage::NoteOff {
+                channel,
+                note,
+                velocity: scale_down(velocity as u32, 16, 7) as u8,
+            }],
+            Self::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure,
+            } => vec![ChannelVoiceMessage::PolyphonicKeyPressure {
+                channel,
+                note,
+                pressure: scale_down(pressure, 32, 7) as u8,
+            }],
+            Self::ControlChange {
+                channel,
+                controller,
+                value,
+            } => {
+                let value = scale_down(value, 32, 7) as u8;
+                return midi1_message(0xb0 | channel, controller & 0x7f, value)
+                    .and_then(|message| match message {
+                        MidiMessage::ChannelMessage(message) => Some(message),
+                        MidiMessage::SystemMessage(_) => None,
+                    })
+                    .into_iter()
+                    .collect();
+            }
+            Self::RegisteredController {
+                channel,
+                bank,
+                index,
+                value,
+            }
+            | Self::AssignableController {
+                channel,
+                bank,
+                index,
+                value,
+            } => {
+                let (msb, lsb) = match self {
+                    Self::RegisteredController { .. } => (RPN_MSB, RPN_LSB),
+                    _ => (NRPN_MSB, NRPN_LSB),
+                };
+                let value = scale_down(value, 32, 14);
+                vec![
+                    control(channel, msb, bank & 0x7f),
+                    control(channel, lsb, index & 0x7f),
+                    control(channel, DATA_ENTRY_MSB, (value >> 7) as u8),
+                    control(channel, DATA_ENTRY_LSB, (value & 0x7f) as u8),
+                ]
+            }
+            Self::ProgramChange {
+                channel,
+                program,
+                bank,
+            } => {
+                let mut messages = Vec::new();
+                if let Some((msb, lsb)) = bank {
+                    messages.push(control(channel, BANK_SELECT_MSB, msb & 0x7f));
+                    messages.push(control(channel, BANK_SELECT_LSB, lsb & 0x7f));
+                }
+                messages.push(ChannelVoiceMessage::ProgramChange { channel, program });
+                messages
+            }
+            Self::ChannelPressure { channel, pressure } => {
+                vec![ChannelVoiceMessage::ChannelPressure {
+                    channel,
+                    pressure: scale_down(pressure, 32, 7) as u8,
+                }]
+            }
+            Self::PitchBendChange { channel, value } => {
+                vec![ChannelVoiceMessage::PitchBendChange {
+                    channel,
+                    value: scale_down(value, 32, 14) as u16,
+                }]
+            }
+            // Per-note and relative messages have no MIDI 1.0 equivalent
+            _ => Vec::new(),
+        };
+        messages
+            .into_iter()
+            .map(ChannelMessage::ChannelVoiceMessage)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event::{
+        midi_event::{ChannelMessage, ChannelVoiceMessage},
+        sysex_event::SysExEvent,
+    };
+
+    use super::{
+        super::{midi2::Midi2ChannelVoiceMessage, Ump},
+        assemble_sysex7, scale_down, scale_up, Midi2Translator,
+    };
+
+    #[test]
+    fn test_scaling() {
+        assert_eq!(scale_up(0, 7, 16), 0);
+        assert_eq!(scale_up(64, 7, 16), 0x8000);
+        assert_eq!(scale_up(127, 7, 16), 0xffff);
+        assert_eq!(scale_up(127, 7, 32), 0xffffffff);
+        assert_eq!(scale_up(0x2000, 14, 32), 0x80000000);
+        assert_eq!(scale_up(0x3fff, 14, 32), 0xffffffff);
+        assert_eq!(scale_up(1, 7, 16), 0x0200);
+        for value in 0..128 {
+            assert_eq!(scale_down(scale_up(value, 7, 32), 32, 7), value);
+        }
+    }
+
+    #[test]
+    fn test_translation() {
+        let voice = |message| ChannelMessage::ChannelVoiceMessage(message);
+        let mut translator = Midi2Translator::new();
+        let messages = [
+            ChannelVoiceMessage::ControlChange {
+                channel: 1,
+                controller: 0x00,
+                value: 1,
+            },
+            ChannelVoiceMessage::ControlChange {
+                channel: 1,
+                controller: 0x20,
+                value: 2,
+            },
+            ChannelVoiceMessage::ProgramChange {
+                channel: 1,
+                program: 5,
+            },
+            ChannelVoiceMessage::ControlChange {
+                channel: 1,
+                controller: 0x65,
+                value: 0,
+            },
+            ChannelVoiceMessage::ControlChange {
+                channel: 1,
+                controller: 0x64,
+                value: 0,
+            },
+            ChannelVoiceMessage::ControlChange {
+                channel: 1,
+                controller: 0x06,
+                value: 12,
+            },
+        ];
+        let translated = messages
+            .iter()
+            .filter_map(|message| translator.to_midi2(&voice(*message)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            translated,
+            [
+                Midi2ChannelVoiceMessage::ProgramChange {
+                    channel: 1,
+                    program: 5,
+                    bank: Some((1, 2)),
+                },
+                Midi2ChannelVoiceMessage::RegisteredController {
+                    channel: 1,
+                    bank: 0,
+                    index: 0,
+                    value: 12 << 25,
+                },
+            ]
+        );
+        assert_eq!(
+            translated[0].to_midi1(),
+            messages[..3].iter().copied().map(voice).collect::<Vec<_>>()
+        );
+        assert_eq!(translated[1].to_midi1().len(), 4);
+        assert_eq!(
+            translator.to_midi2(&voice(ChannelVoiceMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 0,
+            })),
+            Some(Midi2ChannelVoiceMessage::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 0,
+                attribute_type: 0,
+                attribute: 0,
+            })
+        );
+        let note_on = Midi2ChannelVoiceMessage::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity: 0x0100,
+            attribute_type: 0,
+            attribute: 0,
+        };
+        assert_eq!(
+            note_on.to_midi1(),
+            [voice(ChannelVoiceMessage::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 1,
+            })]
+        );
+    }
+
+    #[test]
+    fn test_sysex7() {
+        let event = SysExEvent::from_payload(&[0x7e, 0x7f, 0x06, 0x02, 0x00, 0x20, 0x33, 0x01]);
+        let packets = Ump::from_sysex_event(3, &event).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(assemble_sysex7(&packets), Some(event));
+        assert_eq!(assemble_sysex7(&packets[1..]), None);
+    }
+}
@@ -1,5 +1,12 @@
 use nom::{combinator::verify, number::complete::be_u8, IResult};
 
 pub fn be_u7(input: &[u8]) -> IResult<&[u8], u8> {
-    Ok(verify(be_u8, |value| value >> 7 == 0)(input)?)
+    verify(be_u8, |value| value >> 7 == 0)(input)
+}
+
+pub(crate) fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        b => gcd(b, a % b),
+    }
 }
@@ -4,6 +4,8 @@ use nom::{
     IResult,
 };
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct VariableLengthQuantity {
     pub value: u32,
 }